@@ -1,46 +1,81 @@
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::sync::Mutex;
 
-use crate::engine::engine::EngineMetrics;
+use crate::engine::metrics::Metrics;
 
 /// Internal doubly-linked list node (key-based, no references)
 struct Node<K, V> {
-    key: K,
     value: V,
     prev: Option<K>,
     next: Option<K>,
 }
 
+/// LRU cache that can be shared between threads.
+/// Every call takes an internal lock; values are cloned out, so keep them cheap to clone (e.g. `Arc`).
 pub struct LruCache<K, V>
 where
     K: Eq + Hash + Clone,
 {
-    capacity: usize,
-    map: HashMap<K, Node<K, V>>,
-    head: Option<K>, // Most recently used
-    tail: Option<K>, // Least recently used
+    inner: Mutex<LruList<K, V>>,
 }
 
 impl<K, V> LruCache<K, V>
 where
     K: Eq + Hash + Clone,
+    V: Clone,
 {
     pub fn new(capacity: usize) -> Self {
         assert!(capacity > 0, "LRU capacity must be > 0");
 
         Self {
-            capacity,
-            map: HashMap::new(),
-            head: None,
-            tail: None,
+            inner: Mutex::new(LruList {
+                capacity,
+                map: HashMap::new(),
+                head: None,
+                tail: None,
+            }),
         }
     }
 
     pub fn len(&self) -> usize {
-        self.map.len()
+        self.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().map.is_empty()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.lock().get(key).cloned()
     }
 
-    pub fn get(&mut self, key: &K) -> Option<&V> {
+    pub fn put(&self, key: K, value: V, metrics: &Metrics) {
+        if self.lock().put(key, value) {
+            Metrics::incr(&metrics.page_cache_evictions);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruList<K, V>> {
+        self.inner.lock().unwrap()
+    }
+}
+
+struct LruList<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    capacity: usize,
+    map: HashMap<K, Node<K, V>>,
+    head: Option<K>, // Most recently used
+    tail: Option<K>, // Least recently used
+}
+
+impl<K, V> LruList<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn get(&mut self, key: &K) -> Option<&V> {
         if !self.map.contains_key(key) {
             return None;
         }
@@ -50,34 +85,33 @@ where
         self.map.get(&key).map(|n| &n.value)
     }
 
-    pub fn put(&mut self, key: K, value: V, metrics: &mut EngineMetrics) {
+    /// Insert or update `key`. Returns whether another entry was evicted to make room.
+    fn put(&mut self, key: K, value: V) -> bool {
         if self.map.contains_key(&key) {
             // Update existing
             if let Some(node) = self.map.get_mut(&key) {
                 node.value = value;
             }
             self.move_to_head(&key);
-            return;
+            return false;
         }
 
         // Evict if needed
-        if self.map.len() == self.capacity {
+        let evicted = self.map.len() == self.capacity;
+        if evicted {
             self.evict_lru();
-            metrics.page_cache_evictions += 1;
         }
 
         // Insert new node at head
         let node = Node {
-            key: key.clone(),
             value,
             prev: None,
             next: self.head.clone(),
         };
 
-        if let Some(old_head) = &self.head {
-            if let Some(h) = self.map.get_mut(old_head) {
-                h.prev = Some(key.clone());
-            }
+        if let Some(old_head) = &self.head
+            && let Some(h) = self.map.get_mut(old_head) {
+            h.prev = Some(key.clone());
         }
 
         if self.tail.is_none() {
@@ -86,6 +120,7 @@ where
 
         self.head = Some(key.clone());
         self.map.insert(key, node);
+        evicted
     }
 
     fn move_to_head(&mut self, key: &K) {
@@ -99,16 +134,14 @@ where
         };
 
         // Detach node
-        if let Some(p) = prev.clone() {
-            if let Some(pn) = self.map.get_mut(&p) {
-                pn.next = next.clone();
-            }
+        if let Some(p) = prev.clone()
+            && let Some(pn) = self.map.get_mut(&p) {
+            pn.next = next.clone();
         }
 
-        if let Some(n) = next.clone() {
-            if let Some(nn) = self.map.get_mut(&n) {
-                nn.prev = prev.clone();
-            }
+        if let Some(n) = next.clone()
+            && let Some(nn) = self.map.get_mut(&n) {
+            nn.prev = prev.clone();
         }
 
         // Update tail if needed
@@ -124,10 +157,9 @@ where
             node.next = old_head.clone();
         }
 
-        if let Some(h) = old_head {
-            if let Some(hn) = self.map.get_mut(&h) {
-                hn.prev = Some(key.clone());
-            }
+        if let Some(h) = old_head
+            && let Some(hn) = self.map.get_mut(&h) {
+            hn.prev = Some(key.clone());
         }
 
         self.head = Some(key.clone());
@@ -140,10 +172,9 @@ where
         if let Some(lru_key) = self.tail.clone() {
             let prev = self.map.get(&lru_key).and_then(|n| n.prev.clone());
 
-            if let Some(p) = prev.clone() {
-                if let Some(pn) = self.map.get_mut(&p) {
-                    pn.next = None;
-                }
+            if let Some(p) = prev.clone()
+                && let Some(pn) = self.map.get_mut(&p) {
+                pn.next = None;
             }
 
             self.map.remove(&lru_key);
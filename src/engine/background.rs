@@ -0,0 +1,136 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// How long a stalled writer sleeps before re-checking, in case a wake-up was missed.
+const STALL_POLL: Duration = Duration::from_millis(50);
+
+/// Runs flushes and compactions on a dedicated thread. Writers ask for a round with
+/// `schedule` and never wait for it, unless they are stalled on the L0 hard limit.
+#[derive(Default)]
+pub struct Background {
+    state: Mutex<State>,
+    /// Signalled when work is scheduled or shutdown is requested.
+    wake: Condvar,
+    /// Signalled after every round, and on shutdown.
+    progress: Condvar,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Default)]
+struct State {
+    running: bool,
+    pending: bool,
+    shutdown: bool,
+    /// First error a round hit. The worker stops and writes report it from then on.
+    error: Option<String>,
+}
+
+impl Background {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the worker thread. `round` runs one pass of maintenance and returns `None`
+    /// once the engine it works for is gone.
+    pub fn start<F>(self: &Arc<Self>, mut round: F) -> Result<()>
+    where
+        F: FnMut() -> Option<Result<()>> + Send + 'static,
+    {
+        self.state.lock().unwrap().running = true;
+        let this = Arc::clone(self);
+        let handle = std::thread::Builder::new()
+            .name("shunyadb-maintenance".to_string())
+            .spawn(move || {
+                while this.next_round() {
+                    let Some(result) = round() else {
+                        break;
+                    };
+                    this.finish_round(result);
+                }
+                let mut state = this.state.lock().unwrap();
+                state.running = false;
+                this.progress.notify_all();
+            })?;
+        *self.handle.lock().unwrap() = Some(handle);
+        Ok(())
+    }
+
+    /// Whether maintenance is left to the worker rather than done inline.
+    pub fn is_running(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.running && !state.shutdown
+    }
+
+    /// Ask the worker for a round. Does nothing if it isn't running.
+    pub fn schedule(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.running && !state.pending {
+            state.pending = true;
+            self.wake.notify_one();
+        }
+    }
+
+    /// The error that stopped the worker, if any.
+    pub fn check(&self) -> Result<()> {
+        match &self.state.lock().unwrap().error {
+            Some(e) => Err(Error::Background(e.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Keep scheduling rounds until `done` holds, the worker stops, or it fails.
+    pub fn wait_until(&self, mut done: impl FnMut() -> bool) -> Result<()> {
+        loop {
+            if done() {
+                return Ok(());
+            }
+            let mut state = self.state.lock().unwrap();
+            if let Some(e) = &state.error {
+                return Err(Error::Background(e.clone()));
+            }
+            if !state.running || state.shutdown {
+                return Ok(());
+            }
+            state.pending = true;
+            self.wake.notify_one();
+            drop(self.progress.wait_timeout(state, STALL_POLL).unwrap());
+        }
+    }
+
+    /// Stop the worker and wait for a round in progress to finish. Safe to call more than once.
+    pub fn shutdown(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.shutdown = true;
+            self.wake.notify_all();
+            self.progress.notify_all();
+        }
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            let _ = handle.join();
+        }
+    }
+
+    fn next_round(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while !state.pending && !state.shutdown {
+            state = self.wake.wait(state).unwrap();
+        }
+        if state.shutdown || state.error.is_some() {
+            return false;
+        }
+        state.pending = false;
+        true
+    }
+
+    fn finish_round(&self, result: Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        if let Err(e) = result {
+            state.error = Some(format!("{:#}", e));
+        }
+        self.progress.notify_all();
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use crate::engine::table::DEFAULT_TABLE;
+use crate::storage::record::FieldValue;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    Put {
+        table: String,
+        id: String,
+        value: BTreeMap<String, FieldValue>,
+    },
+    Delete {
+        table: String,
+        id: String,
+    },
+}
+
+/// A group of writes applied atomically by `Engine::write`.
+/// Operations get contiguous seqnos in insertion order, so a later op on the same id wins.
+/// A batch may span several tables.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, id: impl Into<String>, value: BTreeMap<String, FieldValue>) -> &mut Self {
+        self.put_in(DEFAULT_TABLE, id, value)
+    }
+
+    pub fn delete(&mut self, id: impl Into<String>) -> &mut Self {
+        self.delete_in(DEFAULT_TABLE, id)
+    }
+
+    pub fn put_in(&mut self, table: impl Into<String>, id: impl Into<String>, value: BTreeMap<String, FieldValue>) -> &mut Self {
+        self.ops.push(BatchOp::Put { table: table.into(), id: id.into(), value });
+        self
+    }
+
+    pub fn delete_in(&mut self, table: impl Into<String>, id: impl Into<String>) -> &mut Self {
+        self.ops.push(BatchOp::Delete { table: table.into(), id: id.into() });
+        self
+    }
+
+    /// Every table the batch writes to.
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.ops.iter().map(|op| match op {
+            BatchOp::Put { table, .. } | BatchOp::Delete { table, .. } => table.as_str(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+}
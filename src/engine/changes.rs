@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::Duration;
+
+use crate::storage::wal::WalEntry;
+
+/// Fans committed WAL entries out to live subscriptions.
+/// Writers publish under the log lock, so every subscription sees entries in seqno order.
+#[derive(Default)]
+pub struct ChangeFeed {
+    subscribers: Mutex<Vec<Sender<WalEntry>>>,
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a subscription. It receives every entry published from now on.
+    pub fn subscribe(&self, backlog: Vec<WalEntry>) -> Subscription {
+        let (sender, live) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        Subscription {
+            backlog: backlog.into_iter(),
+            live,
+        }
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    /// Send `entries` to every subscription, forgetting the ones that were dropped.
+    pub fn publish(&self, entries: &[WalEntry]) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|s| entries.iter().all(|e| s.send(e.clone()).is_ok()));
+    }
+}
+
+/// Committed changes in seqno order: first those already in the WAL when it was created,
+/// then each new commit as it happens. Iterating blocks until the next change arrives,
+/// and ends once the engine is gone.
+pub struct Subscription {
+    backlog: std::vec::IntoIter<WalEntry>,
+    live: Receiver<WalEntry>,
+}
+
+impl Subscription {
+    /// The next change if one is ready, without waiting.
+    pub fn try_next(&mut self) -> Option<WalEntry> {
+        if let Some(entry) = self.backlog.next() {
+            return Some(entry);
+        }
+        self.live.try_recv().ok()
+    }
+
+    /// The next change, waiting up to `timeout` for one.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<WalEntry> {
+        if let Some(entry) = self.backlog.next() {
+            return Some(entry);
+        }
+        self.live.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = WalEntry;
+
+    fn next(&mut self) -> Option<WalEntry> {
+        self.backlog.next().or_else(|| self.live.recv().ok())
+    }
+}
@@ -1,186 +1,1323 @@
-use anyhow::{Ok, Result};
+use std::fs::{File, OpenOptions, TryLockError};
 use std::path::{Path, PathBuf};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::ops::{Bound, RangeBounds};
+use std::time::Duration;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use crate::storage::record::Record;
-use crate::engine::reader::Reader;
+use crate::engine::background::Background;
+use crate::engine::batch::{BatchOp, WriteBatch};
+use crate::engine::changes::{ChangeFeed, Subscription};
+use crate::engine::metrics::Metrics;
+use crate::engine::options::EngineOptions;
 use crate::engine::writer::Writer;
-use crate::engine::recovery::recover;
-use crate::storage::memtable::MemTable;
+use crate::engine::recovery::{recover, recover_read_only};
+use crate::engine::scan::{Direction, KeyRange};
+use crate::engine::snapshot::{Snapshot, SnapshotList};
+use crate::engine::table::{DEFAULT_TABLE, Table};
+use crate::error::{Error, Result};
+use crate::engine::transaction::Transaction;
+use crate::engine::query::{Plan, Query};
+use crate::engine::schema::Schema;
+use crate::index::simple_index::{self, index_table, is_index_table};
+use crate::lsm::merge::MergeIterator;
+use crate::storage::patch::Patch;
 use crate::storage::record::FieldValue;
-use crate::storage::wal::Wal;
+use crate::storage::typed;
+use crate::storage::wal::group::{PendingSync, SyncGroup};
+use crate::storage::wal::{SyncMode, Wal, WalEntry, WalOp};
+use crate::storage::wal::replay::ReplayResult;
 use crate::storage::page::builder::Page;
 use crate::meta::{TableMeta, PageMeta};
 use crate::lsm::compaction_plan::plan_l0_to_l1;
 use crate::lsm::compaction::execute_l0_to_l1;
 use crate::storage::page::io::delete_older_pages;
 use crate::cache::lru::LruCache;
+use crate::util::now_millis;
 
-#[derive(Debug, Default, Clone)]
-pub struct EngineMetrics {
-    // User operations
-    pub reads: u64,
-    pub writes: u64,
+pub use crate::engine::metrics::EngineMetrics;
 
-    // WAL
-    pub wal_appends: u64,
-    pub wal_rewrites: u64,
+/// Carried by `Error::ConditionFailed` when a conditional write finds another version than expected.
+/// `None` means the key is absent (never written, deleted or expired).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionFailed {
+    pub id: String,
+    pub expected: Option<u64>,
+    pub actual: Option<u64>,
+}
+
+impl std::fmt::Display for ConditionFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ConditionFailed: key {:?} expected seqno {:?}, found {:?}",
+            self.id, self.expected, self.actual
+        )
+    }
+}
 
-    // Storage
-    pub flushes: u64,
-    pub compactions: u64,
+impl std::error::Error for ConditionFailed {}
 
-    // Read path
-    pub page_cache_hits: u64,
-    pub page_cache_misses: u64,
-    pub pages_read_from_disk: u64,
+/// Advisory lock file held by a writable engine for as long as it is open.
+const LOCK_FILE: &str = "LOCK";
+/// Index table names hold the field in hex, and must fit in a directory name.
+const MAX_INDEX_FIELD_LEN: usize = 100;
+/// Records indexed per WAL entry group while an index is built.
+const INDEX_BUILD_CHUNK: usize = 1024;
 
-    // Eviction
-    pub page_cache_evictions: u64,
+/// Handle to an open database. Cheap to clone; every clone shares the same engine and can be
+/// used from any thread. Reads run in parallel with each other and with the one active writer.
+#[derive(Clone)]
+pub struct Engine {
+    inner: Arc<EngineInner>,
+    /// Shared by the handles given out to callers, never by the background worker,
+    /// so dropping the last of them stops the worker before the drop returns.
+    _stop_worker: Arc<StopWorker>,
 }
 
-pub struct Engine {
-    page_cache: LruCache<u64, Page>,
-    memtable: MemTable,
-    pub wal: Wal,
-    reader: Reader,
+struct StopWorker(Option<Arc<Background>>);
+
+impl Drop for StopWorker {
+    fn drop(&mut self) {
+        if let Some(background) = &self.0 {
+            background.shutdown();
+        }
+    }
+}
+
+struct EngineInner {
+    /// Memtables and page sets. Readers hold it shared for a consistent view of both;
+    /// writers hold it exclusively only long enough to publish a change.
+    tables: RwLock<BTreeMap<String, Table>>,
+    /// Held by one writer at a time, from the first check to the last publish.
+    /// Memtable rotation and table changes run under it too.
+    log: Mutex<WalState>,
+    /// The log's fsync state. Writers wait on it after releasing `log`, sharing fsyncs.
+    wal_sync: Arc<SyncGroup>,
+    /// Held by a flush or compaction while it builds pages, so only one runs at a time.
+    /// Taken after `log` when both are needed.
+    maintenance: Mutex<()>,
+    background: Arc<Background>,
+    page_cache: LruCache<(u64, u64), Arc<Page>>,
     writer: Writer,
-    pub meta: TableMeta,
     data_dir: PathBuf,
-    pub metrics: EngineMetrics
+    options: EngineOptions,
+    snapshots: SnapshotList,
+    metrics: Metrics,
+    changes: ChangeFeed,
+    /// Newest seqno published to readers; new snapshots start here.
+    visible_seqno: AtomicU64,
+    /// Holds the directory lock; `None` for a read-only engine, which takes no lock.
+    _lock: Option<File>,
 }
 
-const MEMTABLE_FLUSH_BYTES: usize = 32 * 1024; // 32 KB
+struct WalState {
+    wal: Wal,
+    checkpoint: u64, // WAL entries up to here have been dropped
+    /// The fsync the appends made under the current hold still need; see `Engine::logged`.
+    unsynced: Option<PendingSync>,
+}
 
 impl Engine {
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with(path, EngineOptions::default())
+    }
+
+    /// Fails with `DirectoryLocked` if another engine has the directory open.
+    pub fn open_with(path: impl AsRef<Path>, options: EngineOptions) -> Result<Self> {
+        options.validate()?;
         let path = path.as_ref().to_path_buf();
+        options.prepare_dir(&path)?;
+        let lock = lock_dir(&path)?;
+        let wal = Wal::open_with(path.join("wal.log"), options.sync_mode)?;
+        Self::open_inner(path, options, wal, Some(lock))
+    }
 
-        let mut wal = Wal::open(path.join("wal.log"))?;
-        let mut memtable = MemTable::new();
-        let mut meta = TableMeta::load(path.join("meta.json"))?;
+    /// Open an existing database for inspection, alongside a live writer if there is one.
+    /// Takes no lock and never writes the WAL, metadata or pages: unflushed WAL entries are
+    /// replayed into memory only. Everything that would write fails with `ReadOnly`.
+    /// Shows the database as of the moment it was opened. When the writer's compaction
+    /// deletes a page the view still uses, the next read that needs it reloads the view
+    /// from disk and shows the database as of then; reads at older snapshots may then miss
+    /// versions the compaction dropped.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let wal_path = path.join("wal.log");
+        if !wal_path.exists() {
+            return Err(Error::NotFound(path.display().to_string()));
+        }
+        retry_missing_files(|| {
+            // The WAL is opened before any meta is read: a checkpoint that rewrites it later
+            // leaves this handle on the old file, which still holds every entry the metas
+            // read afterwards don't cover
+            let wal = Wal::open_read_only(&wal_path)?;
+            Self::open_inner(path.clone(), EngineOptions::default(), wal, None)
+        }, || Ok(()))
+    }
 
-        let reader = Reader::new(path.clone());
-        let writer = Writer::new();
+    fn open_inner(path: PathBuf, options: EngineOptions, mut wal: Wal, lock: Option<File>) -> Result<Self> {
+        let read_only = lock.is_none();
+        let writer = Writer::new(&options);
+        let mut tables = load_tables(&path, &writer, read_only)?;
 
         // Recovery
-        recover(
-            &mut wal,
-            &mut memtable,
-            &writer,
-            &mut meta,
-            &path,
-        )?;
-
-        for entry in std::fs::read_dir(&path)? {
-            let p = entry?.path();
-            if p.extension().and_then(|e| e.to_str()) == Some("new") {
-                let _ = std::fs::remove_file(p);
-            }
-        }
-
-        Ok(Self {
-            page_cache: LruCache::new(128),
-            memtable,
-            wal,
-            reader,
-            writer,
-            meta,
-            data_dir: path,
-            metrics: EngineMetrics::default(),
+        if read_only {
+            recover_read_only(&mut wal, &mut tables, &writer, &path)?;
+        } else {
+            recover(
+                &mut wal,
+                &mut tables,
+                &writer,
+                &path,
+            )?;
+
+            for table in tables.values() {
+                for entry in std::fs::read_dir(&table.dir)? {
+                    let p = entry?.path();
+                    if p.extension().and_then(|e| e.to_str()) == Some("new") {
+                        let _ = std::fs::remove_file(p);
+                    }
+                }
+            }
+        }
+
+        let recovered_seqno = writer.seqno().current();
+        let wal_checkpoint = tables[DEFAULT_TABLE].meta.wal_checkpoint;
+        let background = Arc::new(Background::new());
+        let engine = Self {
+            _stop_worker: Arc::new(StopWorker(Some(background.clone()))),
+            inner: Arc::new(EngineInner {
+                tables: RwLock::new(tables),
+                wal_sync: wal.sync_group(),
+                log: Mutex::new(WalState { wal, checkpoint: wal_checkpoint, unsynced: None }),
+                maintenance: Mutex::new(()),
+                background,
+                page_cache: LruCache::new(options.page_cache_capacity),
+                writer,
+                data_dir: path,
+                options,
+                snapshots: SnapshotList::new(),
+                metrics: Metrics::default(),
+                changes: ChangeFeed::new(),
+                visible_seqno: AtomicU64::new(recovered_seqno),
+                _lock: lock,
+            }),
+        };
+
+        if !read_only {
+            engine.drop_unfinished_indexes()?;
+        }
+        if engine.inner.options.background_worker && !read_only {
+            let weak = Arc::downgrade(&engine.inner);
+            engine.inner.background.start(move || {
+                let inner = weak.upgrade()?;
+                let engine = Engine { inner, _stop_worker: Arc::new(StopWorker(None)) };
+                Some(engine.background_round())
+            })?;
+        }
+        Ok(engine)
+    }
+
+    /// Stop the background worker, letting a round in progress finish, and sync the WAL.
+    /// The engine stays usable: later writes flush and compact inline.
+    pub fn close(&self) -> Result<()> {
+        self.inner.background.shutdown();
+        self.inner.background.check()?;
+        self.sync()
+    }
+
+    fn log(&self) -> MutexGuard<'_, WalState> {
+        self.inner.log.lock().unwrap()
+    }
+
+    /// Take the log for a write. With a background worker, first wait while any table's L0
+    /// or immutable memtable queue is at its hard limit, so writes can't outrun maintenance.
+    fn write_log(&self) -> Result<MutexGuard<'_, WalState>> {
+        self.check_writable()?;
+        let background = &self.inner.background;
+        background.check()?;
+        if background.is_running() && self.l0_over_stop_limit() {
+            Metrics::incr(&self.inner.metrics.write_stalls);
+            background.wait_until(|| !self.l0_over_stop_limit())?;
+        }
+        Ok(self.log())
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.inner._lock.is_none()
+    }
+
+    fn l0_over_stop_limit(&self) -> bool {
+        let options = &self.inner.options;
+        self.tables().values().any(|t| {
+            t.meta.level[0].len() >= options.l0_stop_writes_pages
+                || t.immutable.len() >= options.max_immutable_memtables
+        })
+    }
+
+    fn tables(&self) -> RwLockReadGuard<'_, BTreeMap<String, Table>> {
+        self.inner.tables.read().unwrap()
+    }
+
+    fn tables_mut(&self) -> RwLockWriteGuard<'_, BTreeMap<String, Table>> {
+        self.inner.tables.write().unwrap()
+    }
+
+    /// Create an empty named table. Its creation is logged in the shared WAL.
+    pub fn create_table(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        Table::validate_name(name)?;
+        self.logged(self.log(), |log| self.create_table_locked(log, name))
+    }
+
+    /// Caller holds the log.
+    fn create_table_locked(&self, log: &mut WalState, name: &str) -> Result<()> {
+        if self.tables().contains_key(name) {
+            return Err(Error::InvalidArgument(format!("table {:?} already exists", name)));
+        }
+        Metrics::incr(&self.inner.metrics.wal_appends);
+        let (created_seqno, synced) = self.inner.writer.log_table_op(&mut log.wal, WalOp::CreateTable, name)?;
+        log.defer_sync(synced);
+        let table = Table::create(&self.inner.data_dir, name, created_seqno)?;
+        self.tables_mut().insert(name.to_string(), table);
+        self.inner.visible_seqno.fetch_max(created_seqno, Ordering::SeqCst);
+        self.notify(|| vec![WalEntry::new(WalOp::CreateTable, name, "", created_seqno, None)]);
+        Ok(())
+    }
+
+    /// Drop a named table and delete its pages.
+    pub fn drop_table(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        if name == DEFAULT_TABLE {
+            return Err(Error::InvalidArgument("the default table can't be dropped".to_string()));
+        }
+        Table::validate_name(name)?;
+        self.logged(self.log(), |log| self.drop_table_locked(log, name))
+    }
+
+    /// Caller holds the log.
+    fn drop_table_locked(&self, log: &mut WalState, name: &str) -> Result<()> {
+        // Wait out a background flush or compaction that may be writing into the table's directory
+        let _maintenance = self.inner.maintenance.lock().unwrap();
+        if !self.tables().contains_key(name) {
+            return Err(Error::NotFound(name.to_string()));
+        }
+        Metrics::incr(&self.inner.metrics.wal_appends);
+        let (seqno, synced) = self.inner.writer.log_table_op(&mut log.wal, WalOp::DropTable, name)?;
+        log.defer_sync(synced);
+        self.tables_mut().remove(name);
+        self.inner.visible_seqno.fetch_max(seqno, Ordering::SeqCst);
+        self.notify(|| vec![WalEntry::new(WalOp::DropTable, name, "", seqno, None)]);
+        Table::remove_files(&self.inner.data_dir, name)?;
+        Ok(())
+    }
+
+    /// Names of the named tables, sorted. The default table and index tables are not listed.
+    pub fn list_tables(&self) -> Vec<String> {
+        self.tables()
+            .keys()
+            .filter(|name| name.as_str() != DEFAULT_TABLE && !is_index_table(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Check every later write to `table` against `schema`. Records already stored are not
+    /// checked. If the table has a schema, `schema` must be a compatible evolution of it
+    /// (see `Schema::check_evolution`), so records written under the old one stay valid.
+    pub fn set_schema(&self, table: &str, schema: Schema) -> Result<()> {
+        self.check_writable()?;
+        if is_index_table(table) {
+            return Err(Error::InvalidArgument(format!("index table {:?} can't have a schema", table)));
+        }
+        schema.validate().map_err(Error::InvalidArgument)?;
+        // Hold the log so no write is checked against the old schema after this returns
+        let _log = self.log();
+        let mut tables = self.tables_mut();
+        let entry = table_mut(&mut tables, table)?;
+        if let Some(old) = &entry.meta.schema {
+            old.check_evolution(&schema).map_err(Error::InvalidArgument)?;
+        }
+        entry.meta.schema = Some(schema);
+        entry.persist_meta()?;
+        Ok(())
+    }
+
+    /// Stop checking writes to `table`.
+    pub fn drop_schema(&self, table: &str) -> Result<()> {
+        self.check_writable()?;
+        let _log = self.log();
+        let mut tables = self.tables_mut();
+        let entry = table_mut(&mut tables, table)?;
+        if entry.meta.schema.take().is_none() {
+            return Err(Error::NotFound(format!("schema of table {:?}", table)));
+        }
+        entry.persist_meta()?;
+        Ok(())
+    }
+
+    /// The schema writes to `table` are checked against, if any.
+    pub fn schema(&self, table: &str) -> Result<Option<Schema>> {
+        let tables = self.tables();
+        Ok(table_ref(&tables, table)?.meta.schema.clone())
+    }
+
+    pub fn put(&self, id: String, value: BTreeMap<String, FieldValue>) -> Result<()> {
+        self.logged(self.write_log()?, |log| self.put_record(log, DEFAULT_TABLE, id, value, None))?;
+        Ok(())
+    }
+
+    pub fn put_in(&self, table: &str, id: String, value: BTreeMap<String, FieldValue>) -> Result<()> {
+        self.logged(self.write_log()?, |log| self.put_record(log, table, id, value, None))?;
+        Ok(())
+    }
+
+    /// Store `value` as the record `id`, its fields converted with serde (see `storage::typed`).
+    /// `value` must serialize as a struct or a map with string keys.
+    pub fn put_typed<T: Serialize + ?Sized>(&self, id: String, value: &T) -> Result<()> {
+        self.put(id, typed::to_data(value)?)
+    }
+
+    /// Insert `id` so that it reads as deleted once `ttl` has passed.
+    /// Expired records are dropped for good by the next compaction that covers them.
+    pub fn put_with_ttl(&self, id: String, value: BTreeMap<String, FieldValue>, ttl: Duration) -> Result<()> {
+        let expires_at = now_millis().saturating_add(ttl.as_millis() as u64);
+        self.logged(self.write_log()?, |log| self.put_record(log, DEFAULT_TABLE, id, value, Some(expires_at)))?;
+        Ok(())
+    }
+
+    pub fn delete(&self, id: String) -> Result<()> {
+        self.logged(self.write_log()?, |log| self.delete_record(log, DEFAULT_TABLE, id))?;
+        Ok(())
+    }
+
+    pub fn delete_in(&self, table: &str, id: String) -> Result<()> {
+        self.logged(self.write_log()?, |log| self.delete_record(log, table, id))?;
+        Ok(())
+    }
+
+    /// Apply a field-level patch to `id` without reading it first.
+    /// Patching a missing or deleted key starts from an empty record.
+    pub fn update(&self, id: String, patch: Patch) -> Result<()> {
+        patch.validate().map_err(Error::InvalidArgument)?;
+        if patch.is_empty() {
+            return Ok(());
+        }
+        let record = Record::new_patch(id, 0, patch);
+        self.logged(self.write_log()?, |log| self.apply(log, vec![pending(WalOp::Update, DEFAULT_TABLE, record)]))?;
+        Ok(())
+    }
+
+    /// Insert `id` only if it has no live version. Returns the seqno of the write.
+    pub fn put_if_absent(&self, id: String, value: BTreeMap<String, FieldValue>) -> Result<u64> {
+        self.logged(self.write_log()?, |log| {
+            self.check_version(&id, None)?;
+            self.put_record(log, DEFAULT_TABLE, id, value, None)
+        })
+    }
+
+    /// Replace `id` only if its live version has seqno `expected_seqno`. Returns the seqno of the write.
+    pub fn put_if_version(&self, id: String, expected_seqno: u64, value: BTreeMap<String, FieldValue>) -> Result<u64> {
+        self.logged(self.write_log()?, |log| {
+            self.check_version(&id, Some(expected_seqno))?;
+            self.put_record(log, DEFAULT_TABLE, id, value, None)
+        })
+    }
+
+    /// Delete `id` only if its live version has seqno `expected_seqno`. Returns the seqno of the tombstone.
+    pub fn delete_if_version(&self, id: String, expected_seqno: u64) -> Result<u64> {
+        self.logged(self.write_log()?, |log| {
+            self.check_version(&id, Some(expected_seqno))?;
+            self.delete_record(log, DEFAULT_TABLE, id)
+        })
+    }
+
+    /// Caller holds the log, so no other write can land between the check and its own write.
+    fn check_version(&self, id: &str, expected: Option<u64>) -> Result<()> {
+        let actual = self.get(id, u64::MAX)?.map(|r| r.seqno);
+        if actual != expected {
+            return Err(Error::ConditionFailed(ConditionFailed {
+                id: id.to_string(),
+                expected,
+                actual,
+            }));
+        }
+        Ok(())
+    }
+
+    fn put_record(&self, log: &mut WalState, table: &str, id: String, value: BTreeMap<String, FieldValue>, expires_at: Option<u64>) -> Result<u64> {
+        let mut record = Record::new(id, 0, value);
+        record.expires_at = expires_at;
+        self.apply(log, vec![pending(WalOp::Insert, table, record)])
+    }
+
+    fn delete_record(&self, log: &mut WalState, table: &str, id: String) -> Result<u64> {
+        self.apply(log, vec![pending(WalOp::Delete, table, Record::new_tombstone(id, 0))])
+    }
+
+    /// Log `entries` as one atomic WAL unit together with the index entries they imply, and
+    /// publish them. Caller holds the log. Returns the seqno of the last of `entries`.
+    fn apply(&self, log: &mut WalState, mut entries: Vec<WalEntry>) -> Result<u64> {
+        if let Some(entry) = entries.iter().find(|e| is_index_table(&e.table)) {
+            return Err(Error::InvalidArgument(format!("index table {:?} can't be written to", entry.table)));
+        }
+        self.conform(&mut entries)?;
+        let count = entries.len();
+        entries.extend(self.index_entries(&entries)?);
+        Metrics::add(&self.inner.metrics.writes, count as u64);
+        let logged = self.log_entries(log, entries)?;
+        Ok(logged[count - 1].seqno)
+    }
+
+    /// Log `entries` as one WAL unit and make them visible, to readers first and then to
+    /// change subscribers. Caller holds the log.
+    fn log_entries(&self, log: &mut WalState, entries: Vec<WalEntry>) -> Result<Vec<WalEntry>> {
+        let tables: BTreeSet<String> = entries.iter().map(|e| e.table.clone()).collect();
+        for table in &tables {
+            self.prepare_write(log, table)?;
+        }
+        Metrics::incr(&self.inner.metrics.wal_appends);
+        let (entries, synced) = self.inner.writer.log(&mut log.wal, entries)?;
+        log.defer_sync(synced);
+
+        // Publish the whole unit at once so no reader sees half of it
+        {
+            let mut tables = self.tables_mut();
+            for entry in &entries {
+                if let Some(record) = &entry.record {
+                    table_mut(&mut tables, &entry.table)?.memtable.put(record.clone());
+                }
+            }
+        }
+        if let Some(last) = entries.last() {
+            self.inner.visible_seqno.fetch_max(last.seqno, Ordering::SeqCst);
+        }
+        self.notify(|| entries.clone());
+        Ok(entries)
+    }
+
+    /// Check `entries` against the schemas of their tables, filling in defaults and widening
+    /// values. Nothing is logged if any of them fails.
+    fn conform(&self, entries: &mut [WalEntry]) -> Result<()> {
+        let tables = self.tables();
+        for entry in entries.iter_mut() {
+            let Some(schema) = tables.get(&entry.table).and_then(|t| t.meta.schema.as_ref()) else {
+                continue;
+            };
+            let Some(record) = entry.record.as_mut().filter(|r| !r.is_tombstone) else {
+                continue;
+            };
+            let conformed = match &record.patch {
+                Some(patch) => schema.conform_patch(patch).map(|patch| record.patch = Some(patch)),
+                None => schema.conform(&mut record.data),
+            };
+            conformed.map_err(|reason| {
+                Error::InvalidArgument(format!("{:?} does not match the schema of table {:?}: {}", record.id, entry.table, reason))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Index entries implied by writes to the default table: each write drops the old index
+    /// keys of its id and adds the new ones. A write sees the effect of earlier ones in `entries`.
+    fn index_entries(&self, entries: &[WalEntry]) -> Result<Vec<WalEntry>> {
+        let fields = self.index_seqnos();
+        if fields.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut latest: BTreeMap<&str, Option<BTreeMap<String, FieldValue>>> = BTreeMap::new();
+        let mut out = Vec::new();
+        for entry in entries.iter().filter(|e| e.table == DEFAULT_TABLE) {
+            let Some(record) = &entry.record else {
+                continue;
+            };
+            let old = match latest.get(entry.record_id.as_str()) {
+                Some(data) => data.clone(),
+                None => self.latest_data(&entry.record_id)?,
+            };
+            let new = if record.is_tombstone {
+                None
+            } else if let Some(patch) = &record.patch {
+                let mut data = old.clone().unwrap_or_default();
+                patch.apply(&mut data);
+                Some(data)
+            } else {
+                Some(record.data.clone())
+            };
+            for field in fields.keys() {
+                out.extend(simple_index::updates(&index_table(field), field, &entry.record_id, old.as_ref(), new.as_ref()));
+            }
+            latest.insert(&entry.record_id, new);
+        }
+        Ok(out)
+    }
+
+    /// Newest value of `id` in the default table, expired or not: what its index keys were built from.
+    fn latest_data(&self, id: &str) -> Result<Option<BTreeMap<String, FieldValue>>> {
+        let tables = self.tables();
+        let table = table_ref(&tables, DEFAULT_TABLE)?;
+        let record = table.reader.latest(&table.meta, &table.memtables(), id, &self.inner.page_cache, &self.inner.metrics)?;
+        Ok(record.map(|r| r.data))
+    }
+
+    /// Make room in `table` before a write: compact and flush if over the limits,
+    /// or leave that to the background worker if there is one.
+    fn prepare_write(&self, log: &mut WalState, table: &str) -> Result<()> {
+        let background = &self.inner.background;
+        if background.is_running() {
+            let rotated = self.maybe_rotate(table)?;
+            if rotated || self.needs_compaction(table)? {
+                background.schedule();
+            }
+            return Ok(());
+        }
+        self.maybe_compact_table(table)?;
+        self.maybe_flush_table(log, table)
+    }
+
+    fn needs_compaction(&self, name: &str) -> Result<bool> {
+        let tables = self.tables();
+        let table = table_ref(&tables, name)?;
+        Ok(!table.immutable.is_empty() || plan_l0_to_l1(&table.meta, &self.inner.options).is_some())
+    }
+
+    /// One pass of the background worker over every table.
+    /// Neither flushing nor compaction holds the log, so writes carry on meanwhile.
+    fn background_round(&self) -> Result<()> {
+        for name in self.table_names() {
+            let flushed = self.flush_immutable(&name).and_then(|_| self.maybe_checkpoint_wal());
+            match flushed.and_then(|_| self.maybe_compact_table(&name)) {
+                // Dropped since the round started
+                Err(Error::NotFound(_)) => continue,
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Send committed entries to change subscribers, leaving out index maintenance.
+    /// Caller holds the log, which keeps them in seqno order.
+    fn notify(&self, entries: impl FnOnce() -> Vec<WalEntry>) {
+        if self.inner.changes.has_subscribers() {
+            let changes: Vec<WalEntry> = entries().into_iter().filter(|e| !is_index_table(&e.table)).collect();
+            self.inner.changes.publish(&changes);
+        }
+    }
+
+    fn record_syncs(&self) {
+        self.inner.metrics.wal_syncs.store(self.inner.wal_sync.sync_count(), Ordering::Relaxed);
+    }
+
+    /// Run `write` holding `log`, then release it and wait for the fsync its appends need.
+    /// Writers that append while one of them fsyncs share the next fsync (group commit), so
+    /// a write may be visible to readers shortly before it is durable, but is never
+    /// acknowledged before.
+    fn logged<T>(&self, mut log: MutexGuard<'_, WalState>, write: impl FnOnce(&mut WalState) -> Result<T>) -> Result<T> {
+        let result = write(&mut log);
+        let unsynced = log.unsynced.take();
+        drop(log);
+        let synced = unsynced.map_or(Ok(()), |unsynced| unsynced.wait());
+        self.record_syncs();
+        let value = result?;
+        synced?;
+        Ok(value)
+    }
+
+    /// Apply every operation in `batch` atomically: after a crash either all of them or none are recovered.
+    /// This holds across tables too.
+    pub fn write(&self, batch: &WriteBatch) -> Result<()> {
+        self.logged(self.write_log()?, |log| self.write_locked(log, batch))
+    }
+
+    /// Run `check` and then write `batch`, with no other write in between.
+    pub(crate) fn write_if(&self, batch: &WriteBatch, check: impl FnOnce(&Engine) -> Result<()>) -> Result<()> {
+        self.logged(self.write_log()?, |log| {
+            check(self)?;
+            self.write_locked(log, batch)
+        })
+    }
+
+    fn write_locked(&self, log: &mut WalState, batch: &WriteBatch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let entries = batch
+            .ops()
+            .iter()
+            .map(|op| match op {
+                BatchOp::Put { table, id, value } => pending(WalOp::Insert, table, Record::new(id.clone(), 0, value.clone())),
+                BatchOp::Delete { table, id } => pending(WalOp::Delete, table, Record::new_tombstone(id.clone(), 0)),
+            })
+            .collect();
+        self.apply(log, entries)?;
+        Ok(())
+    }
+
+    /// Make every acknowledged write durable, regardless of the sync mode.
+    pub fn sync(&self) -> Result<()> {
+        self.inner.wal_sync.sync()?;
+        self.record_syncs();
+        Ok(())
+    }
+
+    pub fn set_sync_mode(&self, mode: SyncMode) {
+        self.log().wal.set_sync_mode(mode);
+    }
+
+    /// Appends written since the last WAL fsync.
+    pub fn pending_wal_appends(&self) -> usize {
+        self.inner.wal_sync.pending_appends()
+    }
+
+    pub fn options(&self) -> EngineOptions {
+        let mut options = self.inner.options.clone();
+        options.sync_mode = self.inner.wal_sync.sync_mode();
+        options
+    }
+
+    /// Pin the current state. Reads at `snapshot.seqno()` stay valid across compactions
+    /// until the guard is dropped.
+    pub fn snapshot(&self) -> Snapshot {
+        self.inner.snapshots.acquire_current(&self.inner.visible_seqno)
+    }
+
+    /// Start an optimistic transaction reading at the current seqno.
+    pub fn begin_transaction(&self) -> Transaction {
+        Transaction::new(self.snapshot())
+    }
+
+    /// Whether `id` was written (or deleted) after `seqno`.
+    pub fn modified_since(&self, id: &str, seqno: u64) -> Result<bool> {
+        self.read(|| {
+            let tables = self.tables();
+            let table = table_ref(&tables, DEFAULT_TABLE)?;
+            table.reader.modified_since(&table.meta, &table.memtables(), id, seqno, &self.inner.page_cache, &self.inner.metrics)
         })
     }
 
-    pub fn put(&mut self, id: String, value: BTreeMap<String, FieldValue>) -> Result<()> {
-        self.maybe_compact()?;
-        self.maybe_flush()?;
-        self.metrics.writes += 1;
-        self.metrics.wal_appends += 1;
-        self.writer.put(&mut self.memtable, &mut self.wal, id, value)
+    /// The live version of `id` as of `snapshot`, or `None` if it has none.
+    /// Fails if a page that may hold it can't be read, rather than reporting it missing.
+    pub fn get(&self, id: &str, snapshot: u64) -> Result<Option<Record>> {
+        self.get_in(DEFAULT_TABLE, id, snapshot)
     }
 
-    pub fn delete(&mut self, id: String) -> Result<()> {
-        self.maybe_compact()?;
-        self.maybe_flush()?;
-        self.metrics.writes += 1;
-        self.metrics.wal_appends += 1;
-        self.writer.delete(&mut self.memtable, &mut self.wal, id)
+    /// Read `id` as a `T`, the inverse of `put_typed`. Fails with `Serde` if the record's
+    /// fields don't fit `T`.
+    pub fn get_typed<T: DeserializeOwned>(&self, id: &str, snapshot: u64) -> Result<Option<T>> {
+        self.get(id, snapshot)?.map(|record| typed::from_data(record.data)).transpose()
     }
 
-    pub fn get(&mut self, id: &str, snapshot: u64) -> Option<Record> {
-        self.metrics.reads += 1;
-        self.reader.get(&self.meta, &self.memtable, id, snapshot, &mut self.page_cache, &mut self.metrics)
+    pub fn get_in(&self, table: &str, id: &str, snapshot: u64) -> Result<Option<Record>> {
+        Metrics::incr(&self.inner.metrics.reads);
+        self.read(|| {
+            let tables = self.tables();
+            let table = table_ref(&tables, table)?;
+            table.reader.get(&table.meta, &table.memtables(), id, snapshot, &self.inner.page_cache, &self.inner.metrics)
+        })
+    }
+
+    /// Every version of `id` still stored with a seqno in `from_seqno..=to_seqno`, oldest first.
+    /// Tombstones are included, and so are expired records. How far back it reaches depends on
+    /// `EngineOptions::version_retention`; compaction stores kept partial updates as full values.
+    pub fn history(&self, id: &str, from_seqno: u64, to_seqno: u64) -> Result<Vec<Record>> {
+        self.history_in(DEFAULT_TABLE, id, from_seqno, to_seqno)
     }
 
-    pub fn maybe_flush(&mut self) -> Result<()> {
-        let approx = self.memtable.approx_size_bytes();
-        if approx > MEMTABLE_FLUSH_BYTES {
-            self.flush()?;
+    pub fn history_in(&self, table: &str, id: &str, from_seqno: u64, to_seqno: u64) -> Result<Vec<Record>> {
+        Metrics::incr(&self.inner.metrics.reads);
+        self.read(|| {
+            let tables = self.tables();
+            let table = table_ref(&tables, table)?;
+            table.reader.history(&table.meta, &table.memtables(), id, from_seqno, to_seqno, &self.inner.page_cache, &self.inner.metrics)
+        })
+    }
+
+    /// Iterate every live record with an id in `range`, as of `snapshot`, in ascending id order.
+    ///
+    /// The scan is not streamed from disk: every version in `range` held by a memtable or an
+    /// overlapping page is copied into memory before this returns, so memory use grows with
+    /// the size of the range, not with how much of it is consumed. It also means a scan never
+    /// fails part-way or misses pages a later compaction removes. Keep ranges bounded (or use
+    /// `scan_prefix`) on large tables.
+    pub fn scan<R: RangeBounds<String>>(&self, range: R, snapshot: u64) -> Result<MergeIterator> {
+        self.scan_with(KeyRange::new(&range), snapshot, Direction::Forward)
+    }
+
+    /// Same as `scan`, newest-id-first.
+    pub fn scan_reverse<R: RangeBounds<String>>(&self, range: R, snapshot: u64) -> Result<MergeIterator> {
+        self.scan_with(KeyRange::new(&range), snapshot, Direction::Reverse)
+    }
+
+    /// Iterate every live record whose id starts with `prefix`, as of `snapshot`.
+    pub fn scan_prefix(&self, prefix: &str, snapshot: u64) -> Result<MergeIterator> {
+        self.scan_with(KeyRange::prefix(prefix), snapshot, Direction::Forward)
+    }
+
+    /// Scan the default table in either direction. Loads the whole range up front, as `scan` does.
+    pub fn scan_with(&self, range: KeyRange, snapshot: u64, direction: Direction) -> Result<MergeIterator> {
+        self.scan_table(DEFAULT_TABLE, range, snapshot, direction)
+    }
+
+    /// Iterate every live record of `table` with an id in `range`, as of `snapshot`.
+    pub fn scan_in<R: RangeBounds<String>>(&self, table: &str, range: R, snapshot: u64) -> Result<MergeIterator> {
+        self.scan_table(table, KeyRange::new(&range), snapshot, Direction::Forward)
+    }
+
+    fn scan_table(&self, table: &str, range: KeyRange, snapshot: u64, direction: Direction) -> Result<MergeIterator> {
+        Metrics::incr(&self.inner.metrics.reads);
+        self.read(|| {
+            let tables = self.tables();
+            let table = table_ref(&tables, table)?;
+            table.reader.scan(&table.meta, &table.memtables(), &range, snapshot, direction, &self.inner.page_cache, &self.inner.metrics)
+        })
+    }
+
+    /// Run `read`. In a read-only engine, a page it needs may have been deleted by the
+    /// writer's compaction since the view was loaded; then reload the view and try again.
+    fn read<T>(&self, read: impl Fn() -> Result<T>) -> Result<T> {
+        if !self.is_read_only() {
+            return read();
         }
+        retry_missing_files(&read, || self.reload_read_only())
+    }
+
+    /// Replace a read-only engine's view with the database as it is on disk now, loaded the
+    /// way `open_read_only` loads it.
+    fn reload_read_only(&self) -> Result<()> {
+        let path = &self.inner.data_dir;
+        let writer = &self.inner.writer;
+        let mut log = self.log();
+        let mut wal = Wal::open_read_only(path.join("wal.log"))?;
+        let mut tables = load_tables(path, writer, true)?;
+        recover_read_only(&mut wal, &mut tables, writer, path)?;
+
+        let checkpoint = tables[DEFAULT_TABLE].meta.wal_checkpoint;
+        *self.tables_mut() = tables;
+        log.wal = wal;
+        log.checkpoint = checkpoint;
+        self.inner.visible_seqno.fetch_max(writer.seqno().current(), Ordering::SeqCst);
         Ok(())
     }
 
-    pub fn flush(&mut self) -> Result<()> {
-        self.metrics.flushes += 1;
-        let current_page_id = self.meta.current_page_id;
-        let (next_page_id, pages_meta) = self.writer.flush(&mut self.memtable, &self.data_dir, &current_page_id)?;
-        self.meta.add_pages(pages_meta);
-        self.meta.current_page_id = next_page_id;
-        self.maybe_checkpoint_wal()?;
-        self.meta.persist(self.data_dir.join("meta.json"))?;
+    /// Flush every table whose memtable is over the size limit.
+    pub fn maybe_flush(&self) -> Result<()> {
+        self.check_writable()?;
+        let mut log = self.log();
+        for name in self.table_names() {
+            self.maybe_flush_table(&mut log, &name)?;
+        }
         Ok(())
     }
 
-    pub fn maybe_compact(&mut self) -> Result<()> {
-        if let Some(plan) = plan_l0_to_l1(&self.meta) {
-            self.metrics.compactions += 1;
+    /// Caller holds the log.
+    fn maybe_flush_table(&self, log: &mut WalState, name: &str) -> Result<()> {
+        if self.maybe_rotate(name)? {
+            self.flush_immutable(name)?;
+            self.checkpoint_wal(log)?;
+        }
+        Ok(())
+    }
+
+    /// Rotate the table's memtable if it is over the size limit. Caller holds the log,
+    /// so no write is half-way between the WAL and the memtable.
+    fn maybe_rotate(&self, name: &str) -> Result<bool> {
+        let approx = table_ref(&self.tables(), name)?.memtable.approx_size_bytes();
+        if approx <= self.inner.options.memtable_flush_bytes {
+            return Ok(false);
+        }
+        Ok(table_mut(&mut self.tables_mut(), name)?.rotate_memtable())
+    }
+
+    /// Flush every table's memtable. Writes made while pages are built go to fresh memtables.
+    pub fn flush(&self) -> Result<()> {
+        self.check_writable()?;
+        {
+            let _log = self.log();
+            let mut tables = self.tables_mut();
+            for table in tables.values_mut() {
+                table.rotate_memtable();
+            }
+        }
+        for name in self.table_names() {
+            self.flush_immutable(&name)?;
+        }
+        self.maybe_checkpoint_wal()
+    }
+
+    /// Write the table's immutable memtables out as L0 pages, oldest first.
+    /// Each one is dropped from the queue in the same step that registers its pages, so readers
+    /// always find its records in one place or the other.
+    fn flush_immutable(&self, name: &str) -> Result<()> {
+        let _maintenance = self.inner.maintenance.lock().unwrap();
+        loop {
+            let (memtable, dir, current_page_id) = {
+                let tables = self.tables();
+                let table = table_ref(&tables, name)?;
+                let Some(oldest) = table.immutable.first() else {
+                    return Ok(());
+                };
+                (oldest.clone(), table.dir.clone(), table.meta.current_page_id)
+            };
+
+            Metrics::incr(&self.inner.metrics.flushes);
+            let (next_page_id, pages_meta) = self.inner.writer.flush(&memtable, &dir, &current_page_id)?;
+
+            let mut tables = self.tables_mut();
+            let table = table_mut(&mut tables, name)?;
+            table.immutable.remove(0);
+            table.meta.add_pages(pages_meta);
+            table.meta.current_page_id = next_page_id;
+            // Newer writes of this table all went into later memtables
+            if let Some(max_seqno) = memtable.max_seqno() {
+                table.meta.checkpoint_seqno = table.meta.checkpoint_seqno.max(max_seqno);
+            }
+            table.persist_meta()?;
+        }
+    }
+
+    /// Compact L0 into L1 in every table that is over its L0 limits.
+    pub fn maybe_compact(&self) -> Result<()> {
+        self.check_writable()?;
+        for name in self.table_names() {
+            self.maybe_compact_table(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Input pages are merged without blocking readers or writers; the new page set is swapped in at the end.
+    fn maybe_compact_table(&self, name: &str) -> Result<()> {
+        let _maintenance = self.inner.maintenance.lock().unwrap();
+        let (plan, dir) = {
+            let tables = self.tables();
+            let table = table_ref(&tables, name)?;
+            (plan_l0_to_l1(&table.meta, &self.inner.options), table.dir.clone())
+        };
+
+        if let Some(plan) = plan {
+            Metrics::incr(&self.inner.metrics.compactions);
             let obsolete_pages: Vec<PageMeta> = plan.input_l0_pages
                                                     .iter()
                                                     .chain(plan.input_l1_pages.iter())
                                                     .cloned()
                                                     .collect();
 
-            let (current_page_id,new_pages) = execute_l0_to_l1(plan, &self.data_dir)?;
-            
-            self.meta.level[0].clear();
-            self.meta.level[1].retain(|p| {
-                !new_pages.iter().any(|np| np.overlaps(p))
-            });
+            let live_snapshots = self.inner.snapshots.live();
+            let (current_page_id,new_pages) = execute_l0_to_l1(plan, &dir, &live_snapshots, self.inner.options.version_retention)?;
 
-            for p in new_pages {
-                self.meta.level[1].push(p);
-            }
+            {
+                let mut tables = self.tables_mut();
+                let table = table_mut(&mut tables, name)?;
+                table.meta.level[0].clear();
+                // Drop every input page, even one whose keys all compacted away
+                table.meta.level[1].retain(|p| {
+                    !obsolete_pages.iter().any(|op| op.page_id == p.page_id)
+                });
+
+                for p in new_pages {
+                    table.meta.level[1].push(p);
+                }
 
-            self.meta.current_page_id = current_page_id;
-            self.maybe_checkpoint_wal()?;
-            self.meta.persist(self.data_dir.join("meta.json"))?;
-            delete_older_pages(&self.data_dir, obsolete_pages)?;
+                table.meta.current_page_id = current_page_id;
+                table.persist_meta()?;
+            }
+            delete_older_pages(&dir, obsolete_pages)?;
         }
         Ok(())
     }
 
-    
-    pub fn maybe_checkpoint_wal(&mut self) -> Result<()> {
+    pub fn maybe_checkpoint_wal(&self) -> Result<()> {
+        self.check_writable()?;
+        self.checkpoint_wal(&mut self.log())
+    }
+
+    fn checkpoint_wal(&self, log: &mut WalState) -> Result<()> {
         let checkpoint_number = self.compute_checkpoint_seqno()?;
-        if checkpoint_number <= self.meta.checkpoint_seqno {
+        if checkpoint_number <= log.checkpoint {
             return Ok(());
         }
-        self.metrics.wal_rewrites += 1;
-        self.wal.rewrite_to(checkpoint_number)?;
-        self.meta.checkpoint_seqno = checkpoint_number;
+        // Once the WAL is cut, the meta may be the only record of how far seqnos have gone
+        {
+            let mut tables = self.tables_mut();
+            let table = table_mut(&mut tables, DEFAULT_TABLE)?;
+            table.meta.last_seqno = self.inner.writer.seqno().current();
+            table.meta.wal_checkpoint = checkpoint_number;
+            table.persist_meta()?;
+        }
+        Metrics::incr(&self.inner.metrics.wal_rewrites);
+        log.wal.rewrite_to(checkpoint_number)?;
+        log.checkpoint = checkpoint_number;
         Ok(())
     }
 
-    pub fn compute_checkpoint_seqno(&mut self) -> Result<u64> {
-        let checkpoint = self.meta.level
-            .iter()
-            .flat_map(|lvl| lvl.iter())
-            .map(|p| p.max_seqno)
-            .min()
-            .unwrap_or(0);
-        Ok(checkpoint)
+    /// Highest seqno the shared WAL can drop: every table has flushed its writes up to it,
+    /// and every change consumer has acknowledged it.
+    pub fn compute_checkpoint_seqno(&self) -> Result<u64> {
+        let tables = self.tables();
+        let oldest_unflushed = tables
+            .values()
+            .filter_map(|t| t.min_unflushed_seqno())
+            .min();
+        let flushed = match oldest_unflushed {
+            Some(seqno) => seqno - 1,
+            None => self.inner.writer.seqno().current(),
+        };
+        let consumers = &table_ref(&tables, DEFAULT_TABLE)?.meta.consumers;
+        Ok(consumers.values().copied().fold(flushed, u64::min))
+    }
+
+    /// Every committed change after `seqno` still in the WAL, in seqno order: writes, deletes,
+    /// partial updates and table creates and drops, one entry each; batches are not marked.
+    /// Fails with `ChangesTruncated` if the WAL was checkpointed past `seqno`; register a
+    /// consumer to keep the changes it has not acknowledged yet.
+    pub fn changes_since(&self, seqno: u64) -> Result<impl Iterator<Item = WalEntry> + use<>> {
+        if self.is_read_only() {
+            // No writer to hold up, and the file on disk may have been checkpointed since we opened
+            let mut log = self.log();
+            let checkpoint = log.checkpoint;
+            return Ok(read_changes(&mut log.wal, checkpoint, seqno, u64::MAX)?.into_iter());
+        }
+        // Replay through a handle of our own so writers carry on meanwhile. Entries up to
+        // `visible` are in whichever file the handle opens: a WAL checkpoint renames a new file
+        // into place, so an older handle keeps every entry, and one opened after it is caught
+        // by the checkpoint check below.
+        let visible = self.inner.visible_seqno.load(Ordering::SeqCst);
+        let mut wal = Wal::open_read_only(self.inner.data_dir.join("wal.log"))?;
+        let checkpoint = self.log().checkpoint;
+        Ok(read_changes(&mut wal, checkpoint, seqno, visible)?.into_iter())
+    }
+
+    /// Changes after `seqno` as in `changes_since`, followed by every later commit as it happens.
+    pub fn subscribe(&self, seqno: u64) -> Result<Subscription> {
+        // Holding the log, no commit can fall between the backlog and the live feed
+        let mut log = self.log();
+        let checkpoint = log.checkpoint;
+        let backlog = read_changes(&mut log.wal, checkpoint, seqno, u64::MAX)?;
+        Ok(self.inner.changes.subscribe(backlog))
+    }
+
+    /// Register a durable change consumer that has seen every change up to `seqno`.
+    /// From now on WAL checkpoints keep every change it has not acknowledged, across restarts.
+    /// Registering an existing name moves it to `seqno`.
+    pub fn register_consumer(&self, name: &str, seqno: u64) -> Result<()> {
+        self.check_writable()?;
+        let log = self.log();
+        if seqno < log.checkpoint {
+            return Err(Error::ChangesTruncated { since: seqno, checkpoint: log.checkpoint });
+        }
+        let mut tables = self.tables_mut();
+        let table = table_mut(&mut tables, DEFAULT_TABLE)?;
+        table.meta.consumers.insert(name.to_string(), seqno);
+        table.persist_meta()?;
+        Ok(())
+    }
+
+    /// Record that consumer `name` has processed every change up to `seqno`, letting the WAL drop them.
+    /// Acknowledgements are persisted with the next WAL checkpoint; after a crash the consumer
+    /// may see changes again from an earlier one, but never misses any.
+    pub fn ack_changes(&self, name: &str, seqno: u64) -> Result<()> {
+        self.check_writable()?;
+        let mut tables = self.tables_mut();
+        let table = table_mut(&mut tables, DEFAULT_TABLE)?;
+        let acked = table.meta.consumers.get_mut(name).ok_or_else(|| Error::NotFound(name.to_string()))?;
+        *acked = (*acked).max(seqno);
+        Ok(())
     }
 
-    pub fn metrics(&self) -> &EngineMetrics {
-        &self.metrics
+    /// Stop holding back WAL checkpoints for consumer `name`.
+    pub fn unregister_consumer(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        let mut tables = self.tables_mut();
+        let table = table_mut(&mut tables, DEFAULT_TABLE)?;
+        if table.meta.consumers.remove(name).is_none() {
+            return Err(Error::NotFound(name.to_string()));
+        }
+        table.persist_meta()?;
+        Ok(())
+    }
+
+    /// Registered change consumers and the seqno each has acknowledged.
+    pub fn consumers(&self) -> BTreeMap<String, u64> {
+        self.tables()[DEFAULT_TABLE].meta.consumers.clone()
+    }
+
+    /// Build a secondary index on `field` of the default table from its current records.
+    /// From then on every write keeps it up to date in the same WAL entry group. Numbers,
+    /// strings, bytes and timestamps are indexed; records without the field, or with Null,
+    /// Bool, List or Map values in it, are not.
+    /// Writes wait while the index is built. An index that was not finished is dropped on open.
+    pub fn create_index(&self, field: &str) -> Result<()> {
+        self.check_writable()?;
+        if field.is_empty() || field.len() > MAX_INDEX_FIELD_LEN {
+            return Err(Error::InvalidArgument(format!("index field must be 1 to {} bytes long", MAX_INDEX_FIELD_LEN)));
+        }
+        let mut log = self.log();
+        if self.index_seqnos().contains_key(field) {
+            return Err(Error::InvalidArgument(format!("{:?} is already indexed", field)));
+        }
+        let table = index_table(field);
+        if self.tables().contains_key(&table) {
+            self.drop_table_locked(&mut log, &table)?;
+        }
+        self.create_table_locked(&mut log, &table)?;
+
+        let records: Vec<Record> = self.scan_table(DEFAULT_TABLE, KeyRange::all(), u64::MAX, Direction::Forward)?.collect();
+        for chunk in records.chunks(INDEX_BUILD_CHUNK) {
+            let entries: Vec<WalEntry> = chunk
+                .iter()
+                .flat_map(|r| simple_index::updates(&table, field, &r.id, None, Some(&r.data)))
+                .collect();
+            if !entries.is_empty() {
+                self.log_entries(&mut log, entries)?;
+            }
+        }
+        // The index must be durable before it is marked finished
+        log.unsynced = None;
+        log.wal.sync()?;
+        self.record_syncs();
+
+        let mut tables = self.tables_mut();
+        let default = table_mut(&mut tables, DEFAULT_TABLE)?;
+        default.meta.indexes.insert(field.to_string(), self.last_seqno());
+        default.persist_meta()?;
+        Ok(())
+    }
+
+    /// Remove the index on `field` and its data.
+    pub fn drop_index(&self, field: &str) -> Result<()> {
+        self.check_writable()?;
+        self.logged(self.log(), |log| {
+            {
+                let mut tables = self.tables_mut();
+                let default = table_mut(&mut tables, DEFAULT_TABLE)?;
+                if default.meta.indexes.remove(field).is_none() {
+                    return Err(Error::NotFound(format!("index on {:?}", field)));
+                }
+                default.persist_meta()?;
+            }
+            self.drop_table_locked(log, &index_table(field))
+        })
+    }
+
+    /// Fields of the default table with a secondary index, sorted.
+    pub fn indexes(&self) -> Vec<String> {
+        self.index_seqnos().into_keys().collect()
+    }
+
+    /// Indexed fields and the seqno each index serves snapshots from.
+    fn index_seqnos(&self) -> BTreeMap<String, u64> {
+        self.tables()[DEFAULT_TABLE].meta.indexes.clone()
+    }
+
+    /// Live records of the default table whose `field` equals `value` as of `snapshot`, in id order.
+    /// Fails with `NotFound` if `field` has no index, and with `InvalidArgument` if `snapshot`
+    /// is older than the index.
+    pub fn find_by(&self, field: &str, value: &FieldValue, snapshot: u64) -> Result<Vec<Record>> {
+        self.find_range(field, (Bound::Included(value.clone()), Bound::Included(value.clone())), snapshot)
+    }
+
+    /// Live records of the default table whose `field` is in `range` as of `snapshot`,
+    /// ordered by value, then id. Both bounds must be of the same kind; a range of numbers
+    /// finds Int, UInt and Float values alike.
+    pub fn find_range(&self, field: &str, range: impl RangeBounds<FieldValue>, snapshot: u64) -> Result<Vec<Record>> {
+        match self.index_seqnos().get(field) {
+            None => return Err(Error::NotFound(format!("index on {:?}", field))),
+            Some(&built) if snapshot < built => {
+                return Err(Error::InvalidArgument(format!("the index on {:?} serves snapshots from seqno {}", field, built)));
+            }
+            Some(_) => {}
+        }
+        let keys = simple_index::key_range(&range)?;
+        let mut found = Vec::new();
+        for entry in self.scan_table(&index_table(field), keys, snapshot, Direction::Forward)? {
+            let Some((value, id)) = simple_index::split_key(&entry.id) else {
+                continue;
+            };
+            // The index ignores expiry, so check the record is still live and still has the value
+            if let Some(record) = self.get(id, snapshot)?
+                && record.data.get(field).and_then(simple_index::encode_value).as_deref() == Some(value)
+            {
+                found.push(record);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Live records of the default table matching `query` as of `snapshot`, through a
+    /// secondary index when the filter allows one and a full scan otherwise; see `explain`.
+    pub fn query(&self, query: impl Into<Query>, snapshot: u64) -> Result<Vec<Record>> {
+        let query = query.into();
+        let records = match self.explain(&query, snapshot) {
+            Plan::FullScan => {
+                // Without an order, the first matches in id order are the answer
+                let enough = if query.order_by.is_none() { query.limit } else { None };
+                let mut found = Vec::new();
+                for record in self.scan(.., snapshot)? {
+                    if enough.is_some_and(|n| found.len() >= n) {
+                        break;
+                    }
+                    if query.filter.matches(&record.data) {
+                        found.push(record);
+                    }
+                }
+                found
+            }
+            plan => {
+                let mut candidates = BTreeMap::new();
+                self.index_candidates(&plan, snapshot, &mut candidates)?;
+                candidates.into_values().filter(|r| query.filter.matches(&r.data)).collect()
+            }
+        };
+        Ok(query.finish(records))
+    }
+
+    /// The plan `query` would run with at `snapshot`, given the indexes that serve it.
+    pub fn explain(&self, query: &Query, snapshot: u64) -> Plan {
+        let indexes = self.index_seqnos().into_iter().filter(|&(_, built)| built <= snapshot).map(|(field, _)| field).collect();
+        Plan::new(&query.filter, &indexes)
+    }
+
+    fn index_candidates(&self, plan: &Plan, snapshot: u64, out: &mut BTreeMap<String, Record>) -> Result<()> {
+        match plan {
+            Plan::IndexScan { field, start, end } => {
+                for record in self.find_range(field, (start.clone(), end.clone()), snapshot)? {
+                    out.insert(record.id.clone(), record);
+                }
+            }
+            Plan::Union(plans) => {
+                for plan in plans {
+                    self.index_candidates(plan, snapshot, out)?;
+                }
+            }
+            Plan::FullScan => unreachable!("a union never holds a full scan"),
+        }
+        Ok(())
+    }
+
+    /// Drop index tables left by a `create_index` that did not finish.
+    fn drop_unfinished_indexes(&self) -> Result<()> {
+        let finished: BTreeSet<String> = self.index_seqnos().keys().map(|f| index_table(f)).collect();
+        self.logged(self.log(), |log| {
+            for name in self.table_names() {
+                if is_index_table(&name) && !finished.contains(&name) {
+                    self.drop_table_locked(log, &name)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.tables().keys().cloned().collect()
+    }
+
+    /// Metadata of the default table.
+    pub fn meta(&self) -> TableMeta {
+        self.tables()[DEFAULT_TABLE].meta.clone()
+    }
+
+    pub fn table_meta(&self, table: &str) -> Option<TableMeta> {
+        self.tables().get(table).map(|t| t.meta.clone())
+    }
+
+    /// Last seqno this engine issued.
+    pub fn last_seqno(&self) -> u64 {
+        self.inner.writer.seqno().current()
+    }
+
+    pub fn metrics(&self) -> EngineMetrics {
+        self.record_syncs();
+        self.inner.metrics.snapshot()
+    }
+}
+
+impl WalState {
+    /// Leave `synced` for `Engine::logged` to wait on once the log is released.
+    fn defer_sync(&mut self, synced: PendingSync) {
+        self.unsynced = Some(match self.unsynced.take() {
+            Some(earlier) => earlier.and(synced),
+            None => synced,
+        });
+    }
+}
+
+/// How often a read-only engine reloads its view before giving up on a read whose pages
+/// keep disappearing under it.
+const READ_ONLY_RETRIES: usize = 3;
+
+/// Run `attempt`; while it fails on a file that no longer exists, call `reload` and try again.
+fn retry_missing_files<T>(mut attempt: impl FnMut() -> Result<T>, reload: impl Fn() -> Result<()>) -> Result<T> {
+    let mut retries = 0;
+    loop {
+        match attempt() {
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound && retries < READ_ONLY_RETRIES => {
+                retries += 1;
+                reload()?;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Open the default table and every named table under `path`. Seqnos continue from the
+/// newest one on disk; replaying the WAL moves them on from there.
+fn load_tables(path: &Path, writer: &Writer, read_only: bool) -> Result<BTreeMap<String, Table>> {
+    let mut tables = BTreeMap::new();
+    tables.insert(DEFAULT_TABLE.to_string(), Table::open(path, DEFAULT_TABLE)?);
+    for name in Table::discover(path, !read_only)? {
+        let table = Table::open(path, &name)?;
+        tables.insert(name, table);
+    }
+    let last_seqno = tables.values().map(|t| t.meta.max_seqno()).max().unwrap_or(0);
+    writer.seqno().advance_to(last_seqno);
+    Ok(tables)
+}
+
+/// An entry for `Writer::log`, which assigns its seqno.
+fn pending(op: WalOp, table: &str, record: Record) -> WalEntry {
+    WalEntry::new(op, table, record.id.clone(), 0, Some(record))
+}
+
+/// Committed changes in `wal` after `seqno` and up to `until`, leaving out index maintenance.
+fn read_changes(wal: &mut Wal, checkpoint: u64, seqno: u64, until: u64) -> Result<Vec<WalEntry>> {
+    if seqno < checkpoint {
+        return Err(Error::ChangesTruncated { since: seqno, checkpoint });
+    }
+    let replay = ReplayResult::replay_wal(wal)?;
+    Ok(replay
+        .entries
+        .into_iter()
+        .filter(|e| e.seqno > seqno && e.seqno <= until && !is_index_table(&e.table))
+        .collect())
+}
+
+fn table_ref<'a>(tables: &'a BTreeMap<String, Table>, name: &str) -> Result<&'a Table> {
+    tables.get(name).ok_or_else(|| Error::NotFound(name.to_string()))
+}
+
+fn table_mut<'a>(tables: &'a mut BTreeMap<String, Table>, name: &str) -> Result<&'a mut Table> {
+    tables.get_mut(name).ok_or_else(|| Error::NotFound(name.to_string()))
+}
+
+/// Take the directory's advisory lock. It is released when the returned file is closed.
+fn lock_dir(path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(path.join(LOCK_FILE))?;
+    match file.try_lock() {
+        Err(TryLockError::WouldBlock) => Err(Error::DirectoryLocked(path.to_path_buf())),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+        _ => Ok(file),
     }
 }
@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Point-in-time copy of the engine's counters, as returned by `Engine::metrics`.
+#[derive(Debug, Default, Clone)]
+pub struct EngineMetrics {
+    // User operations
+    pub reads: u64,
+    pub writes: u64,
+
+    // WAL
+    pub wal_appends: u64,
+    pub wal_syncs: u64,
+    pub wal_rewrites: u64,
+
+    // Storage
+    pub flushes: u64,
+    pub compactions: u64,
+    pub write_stalls: u64,
+
+    // Read path
+    pub page_cache_hits: u64,
+    pub page_cache_misses: u64,
+    pub pages_read_from_disk: u64,
+
+    // Eviction
+    pub page_cache_evictions: u64,
+}
+
+/// Live counters shared by every handle of an engine. Updated without locks.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub reads: AtomicU64,
+    pub writes: AtomicU64,
+    pub wal_appends: AtomicU64,
+    pub wal_syncs: AtomicU64,
+    pub wal_rewrites: AtomicU64,
+    pub flushes: AtomicU64,
+    pub compactions: AtomicU64,
+    pub write_stalls: AtomicU64,
+    pub page_cache_hits: AtomicU64,
+    pub page_cache_misses: AtomicU64,
+    pub pages_read_from_disk: AtomicU64,
+    pub page_cache_evictions: AtomicU64,
+}
+
+impl Metrics {
+    pub fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn incr(counter: &AtomicU64) {
+        Self::add(counter, 1);
+    }
+
+    pub fn snapshot(&self) -> EngineMetrics {
+        let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+        EngineMetrics {
+            reads: load(&self.reads),
+            writes: load(&self.writes),
+            wal_appends: load(&self.wal_appends),
+            wal_syncs: load(&self.wal_syncs),
+            wal_rewrites: load(&self.wal_rewrites),
+            flushes: load(&self.flushes),
+            compactions: load(&self.compactions),
+            write_stalls: load(&self.write_stalls),
+            page_cache_hits: load(&self.page_cache_hits),
+            page_cache_misses: load(&self.page_cache_misses),
+            pages_read_from_disk: load(&self.pages_read_from_disk),
+            page_cache_evictions: load(&self.page_cache_evictions),
+        }
+    }
+}
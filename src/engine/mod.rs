@@ -1,5 +1,17 @@
+#[allow(clippy::module_inception)]
 pub mod engine;
 pub mod seqno;
+pub mod metrics;
+pub mod batch;
+pub mod options;
+pub mod snapshot;
+pub mod transaction;
 pub mod reader;
 pub mod writer;
-pub mod recovery;
\ No newline at end of file
+pub mod recovery;
+pub mod scan;
+pub mod table;
+pub mod background;
+pub mod changes;
+pub mod query;
+pub mod schema;
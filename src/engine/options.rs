@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+use crate::lsm::merge::VersionRetention;
+use crate::storage::wal::SyncMode;
+
+const DEFAULT_MEMTABLE_FLUSH_BYTES: usize = 32 * 1024; // 32 KB
+const DEFAULT_MAX_RECORDS_PER_PAGE: usize = 1024;
+const DEFAULT_L0_PAGE_BYTES: usize = 32 * 1024; // 32 KB
+const DEFAULT_L0_PAGES_LIMIT: usize = 8;
+const DEFAULT_L0_SIZE_LIMIT_BYTES: u64 = 256 * 1024; // 256 KB
+const DEFAULT_L1_PAGE_BYTES: usize = 256 * 1024; // 256 KB
+const DEFAULT_PAGE_CACHE_PAGES: usize = 128;
+const DEFAULT_L0_STOP_WRITES_PAGES: usize = 24;
+const DEFAULT_MAX_IMMUTABLE_MEMTABLES: usize = 4;
+
+/// Tuning and open behaviour for `Engine::open_with`.
+///
+/// ```no_run
+/// use shunyadb::engine::engine::Engine;
+/// use shunyadb::engine::options::EngineOptions;
+///
+/// let options = EngineOptions::new()
+///     .memtable_flush_bytes(64 * 1024)
+///     .page_cache_capacity(512)
+///     .error_if_exists(true);
+/// let engine = Engine::open_with("./data", options)?;
+/// # Ok::<(), shunyadb::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOptions {
+    /// Memtable size that triggers a flush to L0.
+    pub memtable_flush_bytes: usize,
+    /// Records per L0 page written by a flush.
+    pub max_records_per_page: usize,
+    /// Target size of an L0 page written by a flush.
+    pub l0_page_bytes: usize,
+    /// Number of L0 pages that triggers an L0 → L1 compaction.
+    pub l0_pages_limit: usize,
+    /// Total L0 size that triggers an L0 → L1 compaction.
+    pub l0_size_limit_bytes: u64,
+    /// With a background worker, writes to a table wait while its L0 has this many pages.
+    pub l0_stop_writes_pages: usize,
+    /// With a background worker, writes wait while a table has this many memtables queued for flush.
+    pub max_immutable_memtables: usize,
+    /// Target size of an L1 page written by compaction.
+    pub l1_page_bytes: usize,
+    /// Pages kept in the LRU page cache.
+    pub page_cache_capacity: usize,
+    pub sync_mode: SyncMode,
+    /// Old versions compaction keeps for `Engine::history`. By default only the newest
+    /// version of each key survives, plus those live snapshots can see.
+    pub version_retention: VersionRetention,
+    /// Run flushes and compactions on a background thread instead of on the write path.
+    /// Off by default: inline maintenance is deterministic, which tests rely on.
+    pub background_worker: bool,
+    /// Create the data directory when it does not exist.
+    pub create_if_missing: bool,
+    /// Refuse to open a directory that already holds a database.
+    pub error_if_exists: bool,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            memtable_flush_bytes: DEFAULT_MEMTABLE_FLUSH_BYTES,
+            max_records_per_page: DEFAULT_MAX_RECORDS_PER_PAGE,
+            l0_page_bytes: DEFAULT_L0_PAGE_BYTES,
+            l0_pages_limit: DEFAULT_L0_PAGES_LIMIT,
+            l0_size_limit_bytes: DEFAULT_L0_SIZE_LIMIT_BYTES,
+            l0_stop_writes_pages: DEFAULT_L0_STOP_WRITES_PAGES,
+            max_immutable_memtables: DEFAULT_MAX_IMMUTABLE_MEMTABLES,
+            l1_page_bytes: DEFAULT_L1_PAGE_BYTES,
+            page_cache_capacity: DEFAULT_PAGE_CACHE_PAGES,
+            sync_mode: SyncMode::default(),
+            version_retention: VersionRetention::default(),
+            background_worker: false,
+            create_if_missing: true,
+            error_if_exists: false,
+        }
+    }
+}
+
+impl EngineOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn memtable_flush_bytes(mut self, bytes: usize) -> Self {
+        self.memtable_flush_bytes = bytes;
+        self
+    }
+
+    pub fn max_records_per_page(mut self, records: usize) -> Self {
+        self.max_records_per_page = records;
+        self
+    }
+
+    pub fn l0_page_bytes(mut self, bytes: usize) -> Self {
+        self.l0_page_bytes = bytes;
+        self
+    }
+
+    pub fn l0_pages_limit(mut self, pages: usize) -> Self {
+        self.l0_pages_limit = pages;
+        self
+    }
+
+    pub fn l0_size_limit_bytes(mut self, bytes: u64) -> Self {
+        self.l0_size_limit_bytes = bytes;
+        self
+    }
+
+    pub fn l0_stop_writes_pages(mut self, pages: usize) -> Self {
+        self.l0_stop_writes_pages = pages;
+        self
+    }
+
+    pub fn max_immutable_memtables(mut self, count: usize) -> Self {
+        self.max_immutable_memtables = count;
+        self
+    }
+
+    pub fn l1_page_bytes(mut self, bytes: usize) -> Self {
+        self.l1_page_bytes = bytes;
+        self
+    }
+
+    pub fn page_cache_capacity(mut self, pages: usize) -> Self {
+        self.page_cache_capacity = pages;
+        self
+    }
+
+    pub fn sync_mode(mut self, mode: SyncMode) -> Self {
+        self.sync_mode = mode;
+        self
+    }
+
+    pub fn version_retention(mut self, retention: VersionRetention) -> Self {
+        self.version_retention = retention;
+        self
+    }
+
+    pub fn background_worker(mut self, enabled: bool) -> Self {
+        self.background_worker = enabled;
+        self
+    }
+
+    pub fn create_if_missing(mut self, create: bool) -> Self {
+        self.create_if_missing = create;
+        self
+    }
+
+    pub fn error_if_exists(mut self, error: bool) -> Self {
+        self.error_if_exists = error;
+        self
+    }
+
+    /// Reject combinations the engine cannot work with.
+    pub fn validate(&self) -> Result<()> {
+        if self.memtable_flush_bytes == 0 {
+            return Err(invalid("memtable_flush_bytes must be > 0"));
+        }
+        if self.max_records_per_page == 0 {
+            return Err(invalid("max_records_per_page must be > 0"));
+        }
+        if self.l0_page_bytes == 0 || self.l1_page_bytes == 0 {
+            return Err(invalid("page sizes must be > 0"));
+        }
+        if self.l0_pages_limit == 0 {
+            return Err(invalid("l0_pages_limit must be > 0"));
+        }
+        if self.l0_size_limit_bytes == 0 {
+            return Err(invalid("l0_size_limit_bytes must be > 0"));
+        }
+        if self.max_immutable_memtables == 0 {
+            return Err(invalid("max_immutable_memtables must be > 0"));
+        }
+        if self.l0_stop_writes_pages < self.l0_pages_limit {
+            return Err(invalid(format!(
+                "l0_stop_writes_pages ({}) must be >= l0_pages_limit ({})",
+                self.l0_stop_writes_pages, self.l0_pages_limit
+            )));
+        }
+        if self.l1_page_bytes < self.l0_page_bytes {
+            return Err(invalid(format!(
+                "l1_page_bytes ({}) must be >= l0_page_bytes ({})",
+                self.l1_page_bytes, self.l0_page_bytes
+            )));
+        }
+        if self.page_cache_capacity == 0 {
+            return Err(invalid("page_cache_capacity must be > 0"));
+        }
+        if self.sync_mode == SyncMode::EveryWrites(0) {
+            return Err(invalid("SyncMode::EveryWrites needs n > 0"));
+        }
+        if self.version_retention == VersionRetention::LastVersions(0) {
+            return Err(invalid("VersionRetention::LastVersions needs n > 0"));
+        }
+        if self.error_if_exists && !self.create_if_missing {
+            return Err(invalid("error_if_exists without create_if_missing can never open a database"));
+        }
+        Ok(())
+    }
+
+    /// Apply create-if-missing / error-if-exists to `path` before the engine touches it.
+    /// A directory without a WAL or metadata holds no database yet, even if it exists.
+    pub fn prepare_dir(&self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            if !self.create_if_missing {
+                return Err(Error::NotFound(path.display().to_string()));
+            }
+            std::fs::create_dir_all(path)?;
+            return Ok(());
+        }
+
+        let has_db = path.join("meta.json").exists() || path.join("wal.log").exists();
+        if !has_db && !self.create_if_missing {
+            return Err(Error::NotFound(path.display().to_string()));
+        }
+        if has_db && self.error_if_exists {
+            return Err(Error::InvalidArgument(format!("database already exists at {:?}", path)));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(reason: impl std::fmt::Display) -> Error {
+    Error::InvalidArgument(format!("invalid options: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(EngineOptions::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_nonsensical_combinations() {
+        assert!(EngineOptions::new().memtable_flush_bytes(0).validate().is_err());
+        assert!(EngineOptions::new().page_cache_capacity(0).validate().is_err());
+        assert!(EngineOptions::new().l0_page_bytes(1024).l1_page_bytes(512).validate().is_err());
+        assert!(EngineOptions::new().sync_mode(SyncMode::EveryWrites(0)).validate().is_err());
+        assert!(EngineOptions::new().create_if_missing(false).error_if_exists(true).validate().is_err());
+        assert!(EngineOptions::new().l0_pages_limit(8).l0_stop_writes_pages(4).validate().is_err());
+        assert!(EngineOptions::new().max_immutable_memtables(0).validate().is_err());
+        assert!(EngineOptions::new().version_retention(VersionRetention::LastVersions(0)).validate().is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+use crate::index::simple_index;
+use crate::storage::record::{FieldValue, Record};
+
+/// A predicate over the fields of a record.
+///
+/// Equality is exact: `Int(1)` does not equal `UInt(1)`. Ranges follow `FieldValue`'s `Ord`
+/// within the kind of their bounds, so a range of numbers matches Int, UInt and Float values
+/// in it, but never a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Every record.
+    All,
+    Eq(String, FieldValue),
+    /// The field holds a value of the bounds' kind within them. Fully unbounded means the field exists.
+    Range(String, Bound<FieldValue>, Bound<FieldValue>),
+    Exists(String),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(field: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        Filter::Eq(field.into(), value.into())
+    }
+
+    pub fn range(field: impl Into<String>, range: impl RangeBounds<FieldValue>) -> Self {
+        Filter::Range(field.into(), range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    pub fn exists(field: impl Into<String>) -> Self {
+        Filter::Exists(field.into())
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+
+    pub fn matches(&self, data: &BTreeMap<String, FieldValue>) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Eq(field, value) => data.get(field) == Some(value),
+            Filter::Range(field, start, end) => data.get(field).is_some_and(|v| in_range(v, start.as_ref(), end.as_ref())),
+            Filter::Exists(field) => data.contains_key(field),
+            Filter::And(filters) => filters.iter().all(|f| f.matches(data)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(data)),
+            Filter::Not(filter) => !filter.matches(data),
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
+
+fn in_range(value: &FieldValue, start: Bound<&FieldValue>, end: Bound<&FieldValue>) -> bool {
+    let same_kind = |bound: &FieldValue| bound.kind_rank() == value.kind_rank();
+    let above = match start {
+        Bound::Included(s) => same_kind(s) && value >= s,
+        Bound::Excluded(s) => same_kind(s) && value > s,
+        Bound::Unbounded => true,
+    };
+    let below = match end {
+        Bound::Included(e) => same_kind(e) && value <= e,
+        Bound::Excluded(e) => same_kind(e) && value < e,
+        Bound::Unbounded => true,
+    };
+    above && below
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A filter plus what to return: which fields, in which order, and how many records.
+///
+/// ```no_run
+/// use shunyadb::engine::engine::Engine;
+/// use shunyadb::engine::query::{Filter, Order, Query};
+/// use shunyadb::storage::record::FieldValue;
+///
+/// let engine = Engine::open("./data")?;
+/// let query = Query::new(Filter::eq("city", "Oslo").and(Filter::range("age", FieldValue::Int(18)..)))
+///     .select(["name"])
+///     .order_by("age", Order::Desc)
+///     .limit(10);
+/// let snapshot = engine.snapshot();
+/// println!("{}", engine.explain(&query, snapshot.seqno()));
+/// let adults = engine.query(query, snapshot.seqno())?;
+/// # Ok::<(), shunyadb::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub filter: Filter,
+    /// Fields to keep in each result; all of them when `None`.
+    pub fields: Option<Vec<String>>,
+    /// Results are sorted by this field in `FieldValue` order, then by id. A missing field
+    /// sorts as the smallest value: first for `Asc`, last for `Desc`. Without it they come in
+    /// id order.
+    pub order_by: Option<(String, Order)>,
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            fields: None,
+            order_by: None,
+            limit: None,
+        }
+    }
+
+    pub fn select<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn order_by(mut self, field: impl Into<String>, order: Order) -> Self {
+        self.order_by = Some((field.into(), order));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sort, cut and project matching records, given in id order.
+    pub(crate) fn finish(&self, mut records: Vec<Record>) -> Vec<Record> {
+        if let Some((field, order)) = &self.order_by {
+            // Stable, so ties stay in id order
+            records.sort_by(|a, b| {
+                let ordering = a.data.get(field).cmp(&b.data.get(field));
+                match order {
+                    Order::Asc => ordering,
+                    Order::Desc => ordering.reverse(),
+                }
+            });
+        }
+        if let Some(limit) = self.limit {
+            records.truncate(limit);
+        }
+        if let Some(fields) = &self.fields {
+            for record in &mut records {
+                record.data.retain(|name, _| fields.contains(name));
+            }
+        }
+        records
+    }
+}
+
+impl From<Filter> for Query {
+    fn from(filter: Filter) -> Self {
+        Query::new(filter)
+    }
+}
+
+/// How a query finds its candidate records. The filter is checked again on every candidate,
+/// so a plan only has to find a superset of the matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Plan {
+    /// Every live record, through a merged scan of the table.
+    FullScan,
+    /// Records whose `field` is within the bounds, through its secondary index.
+    IndexScan {
+        field: String,
+        start: Bound<FieldValue>,
+        end: Bound<FieldValue>,
+    },
+    /// Records found by any of the plans.
+    Union(Vec<Plan>),
+}
+
+impl Plan {
+    /// Plan `filter` given the indexed fields. An AND uses the most selective index among its
+    /// parts, equality before ranges; an OR uses indexes only if every branch can.
+    pub fn new(filter: &Filter, indexes: &BTreeSet<String>) -> Plan {
+        match filter {
+            Filter::Eq(field, value) => Self::index_scan(field, Bound::Included(value.clone()), Bound::Included(value.clone()), indexes),
+            Filter::Range(field, start, end) => Self::index_scan(field, start.clone(), end.clone(), indexes),
+            Filter::And(filters) => filters
+                .iter()
+                .map(|f| Plan::new(f, indexes))
+                .min_by_key(Plan::cost)
+                .unwrap_or(Plan::FullScan),
+            Filter::Or(filters) if !filters.is_empty() => {
+                let plans: Vec<Plan> = filters.iter().map(|f| Plan::new(f, indexes)).collect();
+                if plans.contains(&Plan::FullScan) {
+                    return Plan::FullScan;
+                }
+                Plan::Union(plans)
+            }
+            _ => Plan::FullScan,
+        }
+    }
+
+    fn index_scan(field: &str, start: Bound<FieldValue>, end: Bound<FieldValue>, indexes: &BTreeSet<String>) -> Plan {
+        // A fully open range also matches values the index does not hold
+        let bounded = !matches!((&start, &end), (Bound::Unbounded, Bound::Unbounded));
+        if !indexes.contains(field) || !bounded || simple_index::key_range(&(start.clone(), end.clone())).is_err() {
+            return Plan::FullScan;
+        }
+        Plan::IndexScan { field: field.to_string(), start, end }
+    }
+
+    /// Rough rank for choosing between plans; lower is better.
+    fn cost(&self) -> usize {
+        match self {
+            Plan::IndexScan { start: Bound::Included(s), end: Bound::Included(e), .. } if s == e => 1,
+            Plan::IndexScan { .. } => 2,
+            Plan::Union(plans) => 2 * plans.len(),
+            Plan::FullScan => usize::MAX,
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Plan::FullScan => write!(f, "full scan"),
+            Plan::IndexScan { field, start: Bound::Included(s), end: Bound::Included(e) } if s == e => {
+                write!(f, "index scan on {:?} = {:?}", field, s)
+            }
+            Plan::IndexScan { field, start, end } => {
+                write!(f, "index scan on {:?} ", field)?;
+                match start {
+                    Bound::Included(s) => write!(f, "[{:?}", s)?,
+                    Bound::Excluded(s) => write!(f, "({:?}", s)?,
+                    Bound::Unbounded => write!(f, "(-inf")?,
+                }
+                match end {
+                    Bound::Included(e) => write!(f, ", {:?}]", e),
+                    Bound::Excluded(e) => write!(f, ", {:?})", e),
+                    Bound::Unbounded => write!(f, ", +inf)"),
+                }
+            }
+            Plan::Union(plans) => {
+                write!(f, "union of (")?;
+                for (i, plan) in plans.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", plan)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexes(fields: &[&str]) -> BTreeSet<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn ranges_compare_values_of_the_same_kind() {
+        let data = BTreeMap::from([("age".to_string(), FieldValue::Int(30)), ("city".to_string(), "Oslo".into())]);
+
+        assert!(Filter::eq("age", 30i64).matches(&data));
+        assert!(!Filter::eq("age", 30u64).matches(&data));
+        assert!(Filter::range("age", FieldValue::Int(18)..FieldValue::Int(31)).matches(&data));
+        assert!(Filter::range("age", FieldValue::UInt(18)..).matches(&data));
+        assert!(Filter::range("age", FieldValue::try_from(29.5).unwrap()..FieldValue::UInt(31)).matches(&data));
+        assert!(!Filter::range("age", ..FieldValue::from("z")).matches(&data));
+        assert!(!Filter::range("city", FieldValue::Int(0)..).matches(&data));
+        assert!(Filter::range("city", ..).matches(&data));
+        assert!((!Filter::exists("name")).and(Filter::eq("city", "Oslo")).matches(&data));
+        assert!(Filter::eq("city", "Pune").or(Filter::exists("age")).matches(&data));
+        assert!(!Filter::Or(Vec::new()).matches(&data));
+    }
+
+    #[test]
+    fn planner_prefers_equality_indexes_and_falls_back_to_a_scan() {
+        let indexes = indexes(&["age", "city"]);
+        let adults = Filter::range("age", FieldValue::Int(18)..);
+        let oslo = Filter::eq("city", "Oslo");
+
+        assert_eq!(Plan::new(&adults.clone().and(oslo.clone()), &indexes), Plan::new(&oslo, &indexes));
+        assert!(matches!(Plan::new(&adults, &indexes), Plan::IndexScan { .. }));
+        assert_eq!(Plan::new(&Filter::eq("name", "ann"), &indexes), Plan::FullScan);
+        assert_eq!(Plan::new(&Filter::eq("age", FieldValue::Null), &indexes), Plan::FullScan);
+        assert_eq!(Plan::new(&Filter::range("age", ..), &indexes), Plan::FullScan);
+        assert_eq!(Plan::new(&!oslo.clone(), &indexes), Plan::FullScan);
+
+        assert!(matches!(Plan::new(&oslo.clone().or(adults), &indexes), Plan::Union(plans) if plans.len() == 2));
+        assert_eq!(Plan::new(&oslo.or(Filter::exists("age")), &indexes), Plan::FullScan);
+    }
+
+    #[test]
+    fn finish_orders_limits_and_projects() {
+        let record = |id: &str, age: Option<i64>| {
+            let mut data = BTreeMap::from([("name".to_string(), FieldValue::from(id))]);
+            if let Some(age) = age {
+                data.insert("age".to_string(), FieldValue::Int(age));
+            }
+            Record::new(id, 1, data)
+        };
+        let records = vec![record("a", Some(40)), record("b", None), record("c", Some(20)), record("d", Some(40))];
+
+        let query = Query::new(Filter::All).order_by("age", Order::Desc).limit(3).select(["name"]);
+        let found = query.finish(records.clone());
+        assert_eq!(found.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "d", "c"]);
+        assert!(found.iter().all(|r| r.data.len() == 1));
+
+        let ascending = Query::new(Filter::All).order_by("age", Order::Asc).finish(records);
+        assert_eq!(ascending.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["b", "c", "a", "d"]);
+    }
+}
@@ -1,36 +1,85 @@
 use crate::cache::lru::LruCache;
+use crate::engine::scan::{Direction, KeyRange};
+use crate::lsm::merge::{MergeIterator, PageIterator};
 use crate::storage::memtable::MemTable;
 use crate::storage::page::builder::Page;
 use crate::storage::record::Record;
-use crate::meta::TableMeta;
+use crate::meta::{PageMeta, TableMeta};
 use crate::storage::page::io::read_page_from_disk;
-use crate::engine::engine::EngineMetrics;
+use crate::engine::metrics::Metrics;
+use crate::error::Result;
+use crate::util::now_millis;
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 pub struct Reader {
     data_dir: PathBuf,
+    cache_id: u64, // distinguishes this table's pages in the shared page cache
 }
 
 impl Reader {
-    pub fn new(dir: PathBuf) -> Self {
+    pub fn new(dir: PathBuf, cache_id: u64) -> Self {
         Self {
             data_dir: dir,
+            cache_id,
         }
     }
 
     pub fn get(
         &self,
         meta: &TableMeta,
-        memtable: &MemTable,
+        memtables: &[&MemTable],
         id: &str,
         snapshot: u64,
-        page_cache: &mut LruCache<u64, Page>,
-        metrics: &mut EngineMetrics,
-    ) -> Option<Record> {
-        // Memtable first
-        if let Some(rec) = memtable.get(id, snapshot) {
-            return Some(rec.clone());
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<Option<Record>> {
+        self.get_at(meta, memtables, id, snapshot, now_millis(), page_cache, metrics)
+    }
+
+    /// Newest value of `id`, whether it has expired or not.
+    pub fn latest(
+        &self,
+        meta: &TableMeta,
+        memtables: &[&MemTable],
+        id: &str,
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<Option<Record>> {
+        self.get_at(meta, memtables, id, u64::MAX, 0, page_cache, metrics)
+    }
+
+    /// Resolve `id` as of `snapshot`, treating records that expire after `now` as live.
+    #[allow(clippy::too_many_arguments)]
+    fn get_at(
+        &self,
+        meta: &TableMeta,
+        memtables: &[&MemTable],
+        id: &str,
+        snapshot: u64,
+        now: u64,
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<Option<Record>> {
+        // Visible versions, newest first, down to the first full value or tombstone.
+        // Partial updates above it are stacked and resolved at the end.
+        let mut versions: Vec<Record> = Vec::new();
+        let reached_base = |versions: &Vec<Record>| versions.last().is_some_and(|r| !r.is_patch());
+
+        // Memtables first, newest first
+        for memtable in memtables {
+            let Some(mem_versions) = memtable.data.get(id) else {
+                continue;
+            };
+            for rec in mem_versions.iter().rev() {
+                if rec.seqno <= snapshot {
+                    versions.push(rec.clone());
+                    if reached_base(&versions) {
+                        return Ok(Record::resolve(&versions, now));
+                    }
+                }
+            }
         }
 
         // Immutable pages (newest → oldest)
@@ -40,30 +89,153 @@ impl Reader {
                     continue;
                 }
 
-                let page = if let Some(p) = page_cache.get(&page_info.page_id) {
-                    metrics.page_cache_hits += 1;
-                    p.clone()
-                } else {
-                    metrics.page_cache_misses += 1;
-                    metrics.pages_read_from_disk += 1;
-                    let path = self.data_dir.join(&page_info.file_name);
-                    let p = read_page_from_disk(&path).ok()?;
-                    page_cache.put(page_info.page_id, p.clone(), metrics);
-                    p
-                };
+                let page = self.load_page(page_info, page_cache, metrics)?;
 
                 for rec in page.records.iter().rev() {
                     if rec.id == id && rec.seqno <= snapshot {
-                        return if rec.is_tombstone {
-                            None
-                        } else {
-                            Some(rec.clone())
-                        };
+                        versions.push(rec.clone());
+                        if reached_base(&versions) {
+                            return Ok(Record::resolve(&versions, now));
+                        }
                     }
                 }
             }
         }
 
-        None
+        Ok(Record::resolve(&versions, now))
+    }
+
+    /// Whether `id` has any version (tombstones included) with a seqno above `seqno`.
+    /// Pages whose `max_seqno` is not above it are never read.
+    pub fn modified_since(
+        &self,
+        meta: &TableMeta,
+        memtables: &[&MemTable],
+        id: &str,
+        seqno: u64,
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<bool> {
+        if memtables.iter().any(|m| m.data.get(id).is_some_and(|v| v.iter().any(|r| r.seqno > seqno))) {
+            return Ok(true);
+        }
+
+        for page_info in meta.level.iter().flatten() {
+            if page_info.max_seqno <= seqno
+                || id < page_info.min_id.as_str()
+                || id > page_info.max_id.as_str()
+            {
+                continue;
+            }
+
+            let page = self.load_page(page_info, page_cache, metrics)?;
+            if page.records.iter().any(|r| r.id == id && r.seqno > seqno) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Every stored version of `id` with a seqno in `from..=to`, tombstones and partial updates
+    /// included, oldest first. Pages with nothing newer than `from` are never read.
+    #[allow(clippy::too_many_arguments)]
+    pub fn history(
+        &self,
+        meta: &TableMeta,
+        memtables: &[&MemTable],
+        id: &str,
+        from: u64,
+        to: u64,
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<Vec<Record>> {
+        let in_range = |r: &&Record| r.id == id && (from..=to).contains(&r.seqno);
+        let mut versions: Vec<Record> = Vec::new();
+
+        for memtable in memtables {
+            if let Some(mem_versions) = memtable.data.get(id) {
+                versions.extend(mem_versions.iter().filter(in_range).cloned());
+            }
+        }
+
+        for page_info in meta.level.iter().flatten() {
+            if page_info.max_seqno < from
+                || id < page_info.min_id.as_str()
+                || id > page_info.max_id.as_str()
+            {
+                continue;
+            }
+
+            let page = self.load_page(page_info, page_cache, metrics)?;
+            versions.extend(page.records.iter().filter(in_range).cloned());
+        }
+
+        versions.sort_by_key(|r| r.seqno);
+        versions.dedup_by_key(|r| r.seqno);
+        Ok(versions)
+    }
+
+    /// Merged view of every key in `range` as of `snapshot`, across memtables, L0 and L1.
+    /// Matching records are copied out of the memtables and pages before this returns, so the
+    /// iterator owns everything it yields and memory grows with the range.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan(
+        &self,
+        meta: &TableMeta,
+        memtables: &[&MemTable],
+        range: &KeyRange,
+        snapshot: u64,
+        direction: Direction,
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<MergeIterator> {
+        let mut sources = Vec::new();
+
+        for memtable in memtables {
+            let mem_records: Vec<Record> = memtable
+                .range(range)
+                .flat_map(|(_, versions)| versions.iter().cloned())
+                .collect();
+            sources.push((PageIterator::from_records(mem_records), 0));
+        }
+
+        for (level, pages_at_level) in meta.level.iter().enumerate() {
+            for page_info in pages_at_level.iter() {
+                if !range.overlaps(page_info) {
+                    continue;
+                }
+
+                let page = self.load_page(page_info, page_cache, metrics)?;
+                let records: Vec<Record> = page
+                    .records
+                    .iter()
+                    .filter(|r| range.contains(&r.id))
+                    .cloned()
+                    .collect();
+                sources.push((PageIterator::from_records(records), level as u32));
+            }
+        }
+
+        Ok(MergeIterator::with_direction(sources, snapshot, direction))
+    }
+
+    fn load_page(
+        &self,
+        page_info: &PageMeta,
+        page_cache: &LruCache<(u64, u64), Arc<Page>>,
+        metrics: &Metrics,
+    ) -> Result<Arc<Page>> {
+        if let Some(p) = page_cache.get(&(self.cache_id, page_info.page_id)) {
+            Metrics::incr(&metrics.page_cache_hits);
+            return Ok(p);
+        }
+
+        Metrics::incr(&metrics.page_cache_misses);
+        Metrics::incr(&metrics.pages_read_from_disk);
+        let path = self.data_dir.join(&page_info.file_name);
+        let p = Arc::new(read_page_from_disk(&path)?);
+        page_cache.put((self.cache_id, page_info.page_id), p.clone(), metrics);
+        Ok(p)
     }
 }
@@ -1,52 +1,108 @@
 use anyhow::Result;
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
 
+use crate::engine::table::Table;
 use crate::engine::writer::Writer;
-use crate::meta::TableMeta;
-use crate::storage::memtable::MemTable;
-use crate::storage::wal::Wal;
+use crate::storage::wal::{Wal, WalEntry, WalOp};
 use crate::storage::wal::replay::ReplayResult;
 
 pub fn recover(
     wal: &mut Wal,
-    memtable: &mut MemTable,
+    tables: &mut BTreeMap<String, Table>,
     writer: &Writer,
-    meta: &mut TableMeta,
     data_dir: &std::path::Path,
 ) -> Result<()> {
     // Replay WAL
     let replay = ReplayResult::replay_wal(wal)?;
 
-    // Re-apply WAL entries into memtable
-    for entry in replay.entries {
-        if entry.seqno <= meta.checkpoint_seqno {
-            continue;
+    // Cut off a torn tail or uncommitted batch so new appends start on a clean frame
+    if wal.len_bytes()? > replay.committed_len {
+        wal.truncate(replay.committed_len)?;
+    }
+
+    // Re-apply WAL entries into each table's memtable
+    apply(replay.entries, tables, data_dir, true)?;
+
+    // Flush recovered memtables into immutable pages
+    for table in tables.values_mut() {
+        if !table.memtable.is_empty() {
+            let (next_page_id, pages) = writer.flush(&table.memtable, &table.dir, &table.meta.current_page_id)?;
+            table.memtable.clear();
+            table.meta.add_pages(pages);
+            table.meta.current_page_id = next_page_id;
+            table.meta.checkpoint_seqno = table.meta.checkpoint_seqno.max(replay.max_seqno);
         }
+        table.persist_meta()?;
+    }
+
+    writer.seqno().advance_to(replay.max_seqno);
+    Ok(())
+}
+
+/// Replay the WAL into memtables without writing anything: no truncation, no flush, no meta.
+/// Tables created by replayed entries exist in memory only.
+pub fn recover_read_only(
+    wal: &mut Wal,
+    tables: &mut BTreeMap<String, Table>,
+    writer: &Writer,
+    data_dir: &std::path::Path,
+) -> Result<()> {
+    let replay = ReplayResult::replay_wal(wal)?;
+    apply(replay.entries, tables, data_dir, false)?;
+    writer.seqno().advance_to(replay.max_seqno);
+    Ok(())
+}
+
+fn apply(
+    entries: Vec<WalEntry>,
+    tables: &mut BTreeMap<String, Table>,
+    data_dir: &std::path::Path,
+    persist: bool,
+) -> Result<()> {
+    for entry in entries {
         match entry.op {
-            crate::storage::wal::WalOp::Insert
-            | crate::storage::wal::WalOp::Update => {
+            WalOp::Insert
+            | WalOp::Update
+            | WalOp::Delete => {
+                // Entries for a dropped table, or for an earlier table of the same name, are skipped
+                let Some(table) = tables.get_mut(&entry.table) else {
+                    continue;
+                };
+                if entry.seqno <= table.meta.checkpoint_seqno || entry.seqno <= table.meta.created_seqno {
+                    continue;
+                }
+                // delete is represented as tombstone record
                 if let Some(record) = entry.record {
-                    memtable.put(record);
+                    table.memtable.put(record);
                 }
             }
 
-            crate::storage::wal::WalOp::Delete => {
-                if let Some(record) = entry.record {
-                    // delete is represented as tombstone record
-                    memtable.put(record);
+            WalOp::CreateTable => {
+                if let Entry::Vacant(slot) = tables.entry(entry.table) {
+                    let table = if persist {
+                        Table::create(data_dir, slot.key(), entry.seqno)?
+                    } else {
+                        Table::unpersisted(data_dir, slot.key(), entry.seqno)
+                    };
+                    slot.insert(table);
                 }
             }
-        }
-    }
-
-    // Flush recovered memtable into immutable pages
-    if !memtable.is_empty() {
-        let (next_page_id,pages) = writer.flush(memtable, data_dir, &meta.current_page_id)?;
-        meta.add_pages(pages);
-        meta.current_page_id = next_page_id;
-    }
 
-    meta.persist(data_dir.join("meta.json"))?;
+            // Finish a drop that crashed before its files were removed
+            WalOp::DropTable => {
+                if tables.get(&entry.table).is_some_and(|t| t.meta.created_seqno < entry.seqno) {
+                    tables.remove(&entry.table);
+                    if persist {
+                        Table::remove_files(data_dir, &entry.table)?;
+                    }
+                }
+            }
 
-    crate::engine::seqno::advance_to(replay.max_seqno + 1);
+            // Batch markers are consumed by replay
+            WalOp::BatchBegin
+            | WalOp::BatchCommit => {}
+        }
+    }
     Ok(())
 }
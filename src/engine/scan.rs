@@ -0,0 +1,138 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::meta::PageMeta;
+
+/// Order in which a scan yields keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+}
+
+/// Owned key range used by scans to prune the memtable and pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRange {
+    pub start: Bound<String>,
+    pub end: Bound<String>,
+}
+
+impl KeyRange {
+    pub fn new<R: RangeBounds<String>>(range: &R) -> Self {
+        Self {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+        }
+    }
+
+    pub fn all() -> Self {
+        Self {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+        }
+    }
+
+    /// Every key starting with `prefix`.
+    pub fn prefix(prefix: &str) -> Self {
+        let end = match prefix_successor(prefix) {
+            Some(s) => Bound::Excluded(s),
+            None => Bound::Unbounded,
+        };
+        Self {
+            start: Bound::Included(prefix.to_string()),
+            end,
+        }
+    }
+
+    /// True when no key can satisfy both bounds.
+    pub fn is_empty(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Bound::Included(s), Bound::Included(e)) => s > e,
+            (Bound::Included(s), Bound::Excluded(e))
+            | (Bound::Excluded(s), Bound::Included(e))
+            | (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        let after_start = match &self.start {
+            Bound::Included(s) => id >= s.as_str(),
+            Bound::Excluded(s) => id > s.as_str(),
+            Bound::Unbounded => true,
+        };
+        let before_end = match &self.end {
+            Bound::Included(e) => id <= e.as_str(),
+            Bound::Excluded(e) => id < e.as_str(),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+
+    /// Whether a page with `[min_id, max_id]` may hold keys in this range.
+    pub fn overlaps(&self, page: &PageMeta) -> bool {
+        let below_start = match &self.start {
+            Bound::Included(s) => page.max_id.as_str() < s.as_str(),
+            Bound::Excluded(s) => page.max_id.as_str() <= s.as_str(),
+            Bound::Unbounded => false,
+        };
+        let above_end = match &self.end {
+            Bound::Included(e) => page.min_id.as_str() > e.as_str(),
+            Bound::Excluded(e) => page.min_id.as_str() >= e.as_str(),
+            Bound::Unbounded => false,
+        };
+        !(below_start || above_end)
+    }
+
+    pub fn as_bounds(&self) -> (Bound<&str>, Bound<&str>) {
+        (
+            self.start.as_ref().map(|s| s.as_str()),
+            self.end.as_ref().map(|s| s.as_str()),
+        )
+    }
+}
+
+/// Smallest string greater than every string starting with `prefix`.
+/// `None` when no such bound exists (empty prefix or all chars at `char::MAX`).
+fn prefix_successor(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        let next = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+        if let Some(c) = next {
+            chars.push(c);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_range_covers_only_prefixed_keys() {
+        let range = KeyRange::prefix("user:42:");
+        assert!(range.contains("user:42:"));
+        assert!(range.contains("user:42:order:7"));
+        assert!(!range.contains("user:42"));
+        assert!(!range.contains("user:43:"));
+        assert!(!range.contains("user:4"));
+    }
+
+    #[test]
+    fn prefix_successor_handles_edge_chars() {
+        assert_eq!(prefix_successor("ab"), Some("ac".to_string()));
+        assert_eq!(prefix_successor(""), None);
+        assert_eq!(prefix_successor("a\u{10FFFF}"), Some("b".to_string()));
+        assert_eq!(prefix_successor("\u{D7FF}"), Some("\u{E000}".to_string()));
+    }
+
+    #[test]
+    fn empty_prefix_is_unbounded() {
+        assert_eq!(KeyRange::prefix(""), KeyRange {
+            start: Bound::Included(String::new()),
+            end: Bound::Unbounded,
+        });
+    }
+}
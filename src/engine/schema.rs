@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::storage::patch::{Patch, PatchOp};
+use crate::storage::record::FieldValue;
+
+/// The `FieldValue` variant a schema field holds. Null is not a type: an optional field
+/// may be Null or missing, a required one neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    Str,
+    Bytes,
+    Timestamp,
+    List,
+    Map,
+}
+
+impl FieldType {
+    pub fn of(value: &FieldValue) -> Option<FieldType> {
+        match value {
+            FieldValue::Null => None,
+            FieldValue::Bool(_) => Some(FieldType::Bool),
+            FieldValue::Int(_) => Some(FieldType::Int),
+            FieldValue::UInt(_) => Some(FieldType::UInt),
+            FieldValue::Float(_) => Some(FieldType::Float),
+            FieldValue::Str(_) => Some(FieldType::Str),
+            FieldValue::Bytes(_) => Some(FieldType::Bytes),
+            FieldValue::Timestamp(_) => Some(FieldType::Timestamp),
+            FieldValue::List(_) => Some(FieldType::List),
+            FieldValue::Map(_) => Some(FieldType::Map),
+        }
+    }
+
+    /// `value` as this type, or `None` if it isn't one. A Float field takes Int and UInt
+    /// values that convert exactly, stored as Float.
+    fn coerce(self, value: &FieldValue) -> Option<FieldValue> {
+        let exact = |i: i128| {
+            let f = i as f64;
+            (f as i128 == i).then(|| FieldValue::try_from(f).ok()).flatten()
+        };
+        match (self, value) {
+            (FieldType::Float, FieldValue::Int(i)) => exact(*i as i128),
+            (FieldType::Float, FieldValue::UInt(u)) => exact(*u as i128),
+            _ if FieldType::of(value) == Some(self) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether values of `self` are also valid for `wider`: the same type, or Int and UInt
+    /// widened to Float.
+    fn widens_to(self, wider: FieldType) -> bool {
+        self == wider || (matches!(self, FieldType::Int | FieldType::UInt) && wider == FieldType::Float)
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// One field of a `Schema`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldDef {
+    pub field_type: FieldType,
+    pub required: bool,
+    /// Filled in when a put leaves the field missing or Null.
+    pub default: Option<FieldValue>,
+}
+
+impl FieldDef {
+    pub fn required(field_type: FieldType) -> Self {
+        Self { field_type, required: true, default: None }
+    }
+
+    pub fn optional(field_type: FieldType) -> Self {
+        Self { field_type, required: false, default: None }
+    }
+
+    pub fn default(mut self, value: impl Into<FieldValue>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+}
+
+/// Fields a table's records must have, checked on every write before it is logged.
+///
+/// A put is checked as a whole: defaults are filled in, then every required field must be
+/// present and every field must hold its type. A partial update is checked op by op, since
+/// the record it applies to is not read: sets must hold the field's type, required fields
+/// can't be removed, and increments need a numeric field. Fields not in the schema are
+/// rejected unless `allow_unknown` is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schema {
+    fields: BTreeMap<String, FieldDef>,
+    allow_unknown: bool,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, name: impl Into<String>, def: FieldDef) -> Self {
+        self.fields.insert(name.into(), def);
+        self
+    }
+
+    pub fn allow_unknown(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, FieldDef> {
+        &self.fields
+    }
+
+    pub fn allows_unknown(&self) -> bool {
+        self.allow_unknown
+    }
+
+    /// Check that every field has a name and every default fits its field.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, def) in &self.fields {
+            if name.is_empty() {
+                return Err("schema fields need a name".to_string());
+            }
+            if let Some(default) = &def.default
+                && def.field_type.coerce(default).is_none() {
+                return Err(format!("default of field {:?} must be {}, got {:?}", name, def.field_type, default));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that records valid under `self` stay valid under `next`, so a table's schema
+    /// can be replaced by it. Allowed: adding optional fields, making required fields
+    /// optional, widening Int or UInt to Float, changing defaults and allowing unknown fields.
+    pub fn check_evolution(&self, next: &Schema) -> Result<(), String> {
+        if self.allow_unknown && !next.allow_unknown {
+            return Err("unknown fields can't be disallowed once allowed".to_string());
+        }
+        for (name, def) in &self.fields {
+            let Some(new) = next.fields.get(name) else {
+                return Err(format!("field {:?} can't be removed", name));
+            };
+            if !def.field_type.widens_to(new.field_type) {
+                return Err(format!("field {:?} can't change from {} to {}", name, def.field_type, new.field_type));
+            }
+            if new.required && !def.required {
+                return Err(format!("optional field {:?} can't become required", name));
+            }
+        }
+        if let Some((name, _)) = next.fields.iter().find(|(name, def)| def.required && !self.fields.contains_key(*name)) {
+            return Err(format!("new field {:?} must be optional", name));
+        }
+        Ok(())
+    }
+
+    /// Fill in defaults and check a put's fields, widening values where the schema allows.
+    pub(crate) fn conform(&self, data: &mut BTreeMap<String, FieldValue>) -> Result<(), String> {
+        if !self.allow_unknown
+            && let Some(name) = data.keys().find(|name| !self.fields.contains_key(*name)) {
+            return Err(format!("unknown field {:?}", name));
+        }
+        for (name, def) in &self.fields {
+            let value = match data.get(name) {
+                None | Some(FieldValue::Null) => match &def.default {
+                    Some(default) => default,
+                    None if def.required => return Err(format!("missing required field {:?}", name)),
+                    None => continue,
+                },
+                Some(value) => value,
+            };
+            let value = def.field_type.coerce(value).ok_or_else(|| mismatch(name, def, value))?;
+            data.insert(name.clone(), value);
+        }
+        Ok(())
+    }
+
+    /// Check a partial update's ops, returning the patch with values widened where the schema allows.
+    pub(crate) fn conform_patch(&self, patch: &Patch) -> Result<Patch, String> {
+        let mut out = Patch::new();
+        for op in patch.ops() {
+            let name = match op {
+                PatchOp::Set(name, _) | PatchOp::Remove(name) | PatchOp::Increment(name, _) => name,
+            };
+            let Some(def) = self.fields.get(name) else {
+                if !self.allow_unknown {
+                    return Err(format!("unknown field {:?}", name));
+                }
+                out = push(out, op.clone());
+                continue;
+            };
+            out = match op {
+                PatchOp::Set(_, FieldValue::Null) | PatchOp::Remove(_) if def.required => {
+                    return Err(format!("required field {:?} can't be removed", name));
+                }
+                PatchOp::Set(_, FieldValue::Null) | PatchOp::Remove(_) => push(out, op.clone()),
+                PatchOp::Set(_, value) => {
+                    out.set(name.clone(), def.field_type.coerce(value).ok_or_else(|| mismatch(name, def, value))?)
+                }
+                PatchOp::Increment(_, delta) => {
+                    // An Int or UInt field must keep its variant, so only a Float field takes any delta
+                    let widened = match def.field_type {
+                        FieldType::Int | FieldType::UInt | FieldType::Float => def.field_type.coerce(delta),
+                        _ => None,
+                    };
+                    match widened {
+                        Some(widened) => out.increment(name.clone(), widened),
+                        None => return Err(format!("field {:?} of type {} can't be incremented by {:?}", name, def.field_type, delta)),
+                    }
+                }
+            };
+        }
+        Ok(out)
+    }
+}
+
+fn push(patch: Patch, op: PatchOp) -> Patch {
+    match op {
+        PatchOp::Set(name, value) => patch.set(name, value),
+        PatchOp::Remove(name) => patch.remove(name),
+        PatchOp::Increment(name, delta) => patch.increment(name, delta),
+    }
+}
+
+fn mismatch(name: &str, def: &FieldDef, value: &FieldValue) -> String {
+    format!("field {:?} must be {}, got {:?}", name, def.field_type, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Schema {
+        Schema::new()
+            .field("name", FieldDef::required(FieldType::Str))
+            .field("age", FieldDef::optional(FieldType::Int))
+            .field("score", FieldDef::optional(FieldType::Float))
+            .field("city", FieldDef::optional(FieldType::Str).default("Oslo"))
+    }
+
+    fn data(fields: &[(&str, FieldValue)]) -> BTreeMap<String, FieldValue> {
+        fields.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn puts_get_defaults_and_widened_values() {
+        let mut ann = data(&[("name", "ann".into()), ("score", FieldValue::Int(3))]);
+        people().conform(&mut ann).unwrap();
+        assert_eq!(ann, data(&[("name", "ann".into()), ("score", FieldValue::try_from(3.0).unwrap()), ("city", "Oslo".into())]));
+
+        assert!(people().conform(&mut data(&[("age", FieldValue::Int(3))])).unwrap_err().contains("missing required field \"name\""));
+        assert!(people().conform(&mut data(&[("name", FieldValue::Int(1))])).unwrap_err().contains("must be Str"));
+        assert!(people().conform(&mut data(&[("name", "x".into()), ("extra", FieldValue::Null)])).unwrap_err().contains("unknown field"));
+        assert!(people().allow_unknown(true).conform(&mut data(&[("name", "x".into()), ("extra", FieldValue::Null)])).is_ok());
+        // Only exact conversions widen
+        assert!(people().conform(&mut data(&[("name", "x".into()), ("score", FieldValue::Int(i64::MAX))])).is_err());
+    }
+
+    #[test]
+    fn patches_are_checked_op_by_op() {
+        let patch = Patch::new().set("score", 2i64).increment("age", 1i64).remove("city");
+        assert_eq!(
+            people().conform_patch(&patch).unwrap(),
+            Patch::new().set("score", FieldValue::try_from(2.0).unwrap()).increment("age", 1i64).remove("city")
+        );
+        assert!(people().conform_patch(&Patch::new().remove("name")).is_err());
+        assert!(people().conform_patch(&Patch::new().increment("age", FieldValue::try_from(0.5).unwrap())).is_err());
+        assert!(people().conform_patch(&Patch::new().increment("name", 1i64)).is_err());
+        assert!(people().conform_patch(&Patch::new().set("nick", "x")).is_err());
+    }
+
+    #[test]
+    fn evolution_keeps_old_records_valid() {
+        let old = people();
+        let added = old.clone().field("email", FieldDef::optional(FieldType::Str));
+        let widened = old.clone().field("age", FieldDef::optional(FieldType::Float));
+        let relaxed = old.clone().field("name", FieldDef::optional(FieldType::Str)).allow_unknown(true);
+        for next in [&added, &widened, &relaxed] {
+            assert_eq!(old.check_evolution(next), Ok(()));
+        }
+
+        let required = old.clone().field("email", FieldDef::required(FieldType::Str).default("-"));
+        let narrowed = old.clone().field("score", FieldDef::optional(FieldType::Int));
+        let mut removed = old.clone();
+        removed.fields.remove("age");
+        for next in [&required, &narrowed, &removed, &old.clone().field("age", FieldDef::required(FieldType::Int))] {
+            assert!(old.check_evolution(next).is_err(), "{:?}", next);
+        }
+        assert!(relaxed.check_evolution(&relaxed.clone().allow_unknown(false)).is_err());
+        assert!(Schema::new().field("a", FieldDef::optional(FieldType::Int).default("x")).validate().is_err());
+    }
+}
@@ -1,39 +1,47 @@
-use once_cell::sync::Lazy;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-/// Global seqno counter (in-memory)
-/// Starts at 0 internally; first issued seqno is 1
-static GLOBAL_SEQNO: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::new(0));
-
-/// Allocate the next sequence number (monotonic, unique).
-/// Return the new seqno (value >= 1).
-pub fn allocate() -> u64 {
-  let prev = GLOBAL_SEQNO.fetch_add(1, Ordering::SeqCst);
-  prev + 1
+/// Sequence number allocator owned by one engine.
+/// Holds the last issued seqno; starts at 0, so the first issued seqno is 1.
+#[derive(Debug, Default)]
+pub struct SeqnoAllocator {
+  last: AtomicU64,
 }
 
-pub fn current() -> u64 {
-  GLOBAL_SEQNO.load(Ordering::SeqCst)
-}
+impl SeqnoAllocator {
+  /// Allocator that continues after `last`.
+  pub fn new(last: u64) -> Self {
+    Self {
+      last: AtomicU64::new(last),
+    }
+  }
 
-/// Ensure global seqno is at least `min`. If current < min, advance it to `min`.
-/// Returns the resulting current seqno after the operation (>= min).
-pub fn advance_to(min: u64) -> u64 {
-  let cur = GLOBAL_SEQNO.load(Ordering::SeqCst);
-  if cur >= min {
-    return cur;
+  /// Allocate the next sequence number (monotonic, unique).
+  /// Return the new seqno (value >= 1).
+  pub fn allocate(&self) -> u64 {
+    let prev = self.last.fetch_add(1, Ordering::SeqCst);
+    prev + 1
   }
-  let _ = GLOBAL_SEQNO.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |old| {
-    if old >= min {
-      None
-    } else {
-      Some(min)
-    }
-  });
 
-  GLOBAL_SEQNO.load(Ordering::SeqCst)
+  /// Allocate `n` contiguous sequence numbers.
+  /// Returns the first one; the range is `first..first + n`.
+  pub fn allocate_range(&self, n: u64) -> u64 {
+    let prev = self.last.fetch_add(n, Ordering::SeqCst);
+    prev + 1
+  }
+
+  /// Last issued seqno.
+  pub fn current(&self) -> u64 {
+    self.last.load(Ordering::SeqCst)
+  }
+
+  /// Ensure the last issued seqno is at least `min`. If current < min, advance it to `min`.
+  /// Returns the resulting current seqno after the operation (>= min).
+  pub fn advance_to(&self, min: u64) -> u64 {
+    let prev = self.last.fetch_max(min, Ordering::SeqCst);
+    prev.max(min)
+  }
 }
 
 
 #[cfg(test)]
-mod tests;
\ No newline at end of file
+mod tests;
@@ -1,65 +1,75 @@
 use super::*;
-use serial_test::serial;
 use std::collections::HashSet;
+use std::sync::Arc;
 use std::thread;
 
-// Test-only helper to reset global seqno for clean test runs.
-// This is only compiled in test builds.
-fn reset_for_tests() {
-  GLOBAL_SEQNO.store(0, Ordering::SeqCst);
-}
-
 #[test]
-#[serial]
 fn allocate_increments() {
-  reset_for_tests();
-  let a = allocate();
-  let b = allocate();
-  let c = allocate();
+  let seqno = SeqnoAllocator::default();
+  let a = seqno.allocate();
+  let b = seqno.allocate();
+  let c = seqno.allocate();
   assert_eq!(a + 1, b);
   assert_eq!(b + 1, c);
-  assert_eq!(current(), c);
+  assert_eq!(seqno.current(), c);
   assert!(a >= 1);
 }
 
 #[test]
-#[serial]
+fn allocate_range_reserves_contiguous_block() {
+  let seqno = SeqnoAllocator::default();
+  let a = seqno.allocate();
+  let first = seqno.allocate_range(5);
+  assert_eq!(first, a + 1);
+  assert_eq!(seqno.current(), first + 4);
+  assert_eq!(seqno.allocate(), first + 5);
+}
+
+#[test]
 fn advance_to_sets_value_when_lower() {
-  reset_for_tests();
-  assert_eq!(current(), 0);
-  let after = advance_to(100);
+  let seqno = SeqnoAllocator::default();
+  assert_eq!(seqno.current(), 0);
+  let after = seqno.advance_to(100);
   assert_eq!(after, 100);
-  assert_eq!(current(), 100);
+  assert_eq!(seqno.current(), 100);
   // Allocate should return 101 next
-  let next = allocate();
+  let next = seqno.allocate();
   assert_eq!(next, 101);
 }
 
 #[test]
-#[serial]
 fn advance_to_noop_when_higher() {
-  reset_for_tests();
+  let seqno = SeqnoAllocator::default();
   // allocate a few
-  let _ = allocate(); // 1
-  let _ = allocate(); // 2
-  let cur = current();
-  let after = advance_to(cur - 1); // smaller than current
+  let _ = seqno.allocate(); // 1
+  let _ = seqno.allocate(); // 2
+  let cur = seqno.current();
+  let after = seqno.advance_to(cur - 1); // smaller than current
   assert_eq!(after, cur);
 }
 
 #[test]
-#[serial]
+fn allocators_are_independent() {
+  let a = SeqnoAllocator::default();
+  let b = SeqnoAllocator::new(41);
+  assert_eq!(a.allocate(), 1);
+  assert_eq!(b.allocate(), 42);
+  assert_eq!(a.allocate(), 2);
+}
+
+#[test]
 fn concurrent_allocations_are_unique_and_monotonic() {
-  reset_for_tests();
+  let seqno = Arc::new(SeqnoAllocator::default());
   let threads = 16;
   let per_thread = 5_000; // total 80k allocations
   let mut handles = Vec::with_capacity(threads);
 
   for _ in 0..threads {
+    let seqno = seqno.clone();
     handles.push(thread::spawn(move || {
     let mut local = Vec::with_capacity(per_thread);
     for _ in 0..per_thread {
-      local.push(crate::engine::seqno::allocate());
+      local.push(seqno.allocate());
     }
     local
     }));
@@ -78,7 +88,7 @@ fn concurrent_allocations_are_unique_and_monotonic() {
   let set: HashSet<u64> = all.iter().copied().collect();
   assert_eq!(set.len(), all.len(), "seqnos must be unique");
 
-  // check range continuity: since we reset to 0, we expect sequence 1..=N
+  // check range continuity: a fresh allocator hands out 1..=N
   let mut all_sorted = all;
   all_sorted.sort_unstable();
   let n = threads * per_thread;
@@ -88,5 +98,5 @@ fn concurrent_allocations_are_unique_and_monotonic() {
   }
 
   // current should be n
-  assert_eq!(current(), n as u64);
-}
\ No newline at end of file
+  assert_eq!(seqno.current(), n as u64);
+}
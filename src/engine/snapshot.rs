@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Registry of live snapshots, shared between the engine and every `Snapshot` guard.
+/// Compaction asks it which seqnos still need their versions.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotList {
+    live: Arc<Mutex<BTreeMap<u64, usize>>>,
+}
+
+impl SnapshotList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin `seqno` until the returned guard (and all its clones) are dropped.
+    pub fn acquire(&self, seqno: u64) -> Snapshot {
+        *self.live.lock().unwrap().entry(seqno).or_insert(0) += 1;
+        Snapshot {
+            seqno,
+            list: self.clone(),
+        }
+    }
+
+    /// Pin the current value of `seqno`, read under the registry's lock. A compaction that
+    /// reads `live()` either sees the new snapshot or ran before it was taken, and so only
+    /// dropped versions older than anything it can read.
+    pub fn acquire_current(&self, seqno: &AtomicU64) -> Snapshot {
+        let mut live = self.live.lock().unwrap();
+        let seqno = seqno.load(Ordering::SeqCst);
+        *live.entry(seqno).or_insert(0) += 1;
+        Snapshot {
+            seqno,
+            list: self.clone(),
+        }
+    }
+
+    /// Distinct pinned seqnos, ascending.
+    pub fn live(&self) -> Vec<u64> {
+        self.live.lock().unwrap().keys().copied().collect()
+    }
+
+    pub fn oldest(&self) -> Option<u64> {
+        self.live.lock().unwrap().keys().next().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live.lock().unwrap().is_empty()
+    }
+
+    fn release(&self, seqno: u64) {
+        let mut live = self.live.lock().unwrap();
+        if let Some(count) = live.get_mut(&seqno) {
+            *count -= 1;
+            if *count == 0 {
+                live.remove(&seqno);
+            }
+        }
+    }
+}
+
+/// A consistent read point. While it is alive, compaction keeps every version it can see.
+#[derive(Debug)]
+pub struct Snapshot {
+    seqno: u64,
+    list: SnapshotList,
+}
+
+impl Snapshot {
+    pub fn seqno(&self) -> u64 {
+        self.seqno
+    }
+}
+
+impl Clone for Snapshot {
+    fn clone(&self) -> Self {
+        self.list.acquire(self.seqno)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.list.release(self.seqno);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guards_pin_until_last_clone_is_dropped() {
+        let list = SnapshotList::new();
+        let a = list.acquire(10);
+        let b = a.clone();
+        let c = list.acquire(5);
+
+        assert_eq!(list.live(), vec![5, 10]);
+        assert_eq!(list.oldest(), Some(5));
+
+        drop(a);
+        assert_eq!(list.live(), vec![5, 10]);
+        drop(b);
+        assert_eq!(list.live(), vec![5]);
+        drop(c);
+        assert!(list.is_empty());
+    }
+}
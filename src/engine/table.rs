@@ -0,0 +1,203 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::engine::reader::Reader;
+use crate::error::Error;
+use crate::meta::TableMeta;
+use crate::storage::memtable::MemTable;
+
+/// Name of the table every un-scoped engine call uses. It lives in the data dir itself,
+/// so directories written before named tables existed open unchanged.
+pub const DEFAULT_TABLE: &str = "";
+
+/// Named tables live in `<data dir>/tables/<name>/`.
+const TABLES_DIR: &str = "tables";
+
+/// Cache ids are never reused, so a dropped table's pages can't be served to a new table of the same name.
+static NEXT_CACHE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// One table (column family): its own memtable, levels and page files.
+/// All tables share the engine's WAL and seqno space.
+pub struct Table {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Takes new writes.
+    pub memtable: MemTable,
+    /// Full memtables waiting to be flushed, oldest first. Read-only; each keeps serving reads
+    /// until its pages are registered in `meta`.
+    pub immutable: Vec<Arc<MemTable>>,
+    pub meta: TableMeta,
+    pub reader: Reader,
+}
+
+impl Table {
+    /// Open a table whose directory already exists (or the default table, which always does).
+    pub fn open(root: &Path, name: &str) -> Result<Self> {
+        let dir = Self::dir_for(root, name);
+        let meta = TableMeta::load(dir.join("meta.json"))?;
+        Ok(Self::with_meta(name, dir, meta))
+    }
+
+    /// Create the directory and metadata of a new table, created by the WAL entry at `created_seqno`.
+    pub fn create(root: &Path, name: &str, created_seqno: u64) -> Result<Self> {
+        let dir = Self::dir_for(root, name);
+        std::fs::create_dir_all(&dir)?;
+        let meta = TableMeta {
+            created_seqno,
+            checkpoint_seqno: created_seqno,
+            ..TableMeta::default()
+        };
+        let table = Self::with_meta(name, dir, meta);
+        table.persist_meta()?;
+        Ok(table)
+    }
+
+    /// A table known only in memory, for replaying a `CreateTable` entry in a read-only engine.
+    pub fn unpersisted(root: &Path, name: &str, created_seqno: u64) -> Self {
+        let meta = TableMeta {
+            created_seqno,
+            checkpoint_seqno: created_seqno,
+            ..TableMeta::default()
+        };
+        Self::with_meta(name, Self::dir_for(root, name), meta)
+    }
+
+    fn with_meta(name: &str, dir: PathBuf, meta: TableMeta) -> Self {
+        let cache_id = NEXT_CACHE_ID.fetch_add(1, Ordering::SeqCst);
+        Self {
+            name: name.to_string(),
+            reader: Reader::new(dir.clone(), cache_id),
+            dir,
+            memtable: MemTable::new(),
+            immutable: Vec::new(),
+            meta,
+        }
+    }
+
+    pub fn dir_for(root: &Path, name: &str) -> PathBuf {
+        if name == DEFAULT_TABLE {
+            root.to_path_buf()
+        } else {
+            root.join(TABLES_DIR).join(name)
+        }
+    }
+
+    /// Every memtable, newest first: the active one, then the immutable queue.
+    pub fn memtables(&self) -> Vec<&MemTable> {
+        std::iter::once(&self.memtable)
+            .chain(self.immutable.iter().rev().map(|m| m.as_ref()))
+            .collect()
+    }
+
+    /// Turn the active memtable into the newest immutable one. Returns false if it was empty.
+    pub fn rotate_memtable(&mut self) -> bool {
+        if self.memtable.is_empty() {
+            return false;
+        }
+        let full = std::mem::take(&mut self.memtable);
+        self.immutable.push(Arc::new(full));
+        true
+    }
+
+    /// Oldest seqno held only in memory.
+    pub fn min_unflushed_seqno(&self) -> Option<u64> {
+        self.memtables().into_iter().filter_map(|m| m.min_seqno()).min()
+    }
+
+    pub fn persist_meta(&self) -> Result<()> {
+        self.meta.persist(self.dir.join("meta.json"))
+    }
+
+    /// Delete the table's pages and metadata.
+    pub fn remove_files(root: &Path, name: &str) -> Result<()> {
+        let dir = Self::dir_for(root, name);
+        match std::fs::remove_dir_all(&dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Names of the named tables on disk. Directories left without metadata by a crash
+    /// half-way through a create or drop are skipped, and removed if `remove_orphans` is set.
+    pub fn discover(root: &Path, remove_orphans: bool) -> Result<Vec<String>> {
+        let tables_dir = root.join(TABLES_DIR);
+        if !tables_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&tables_dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if path.join("meta.json").exists() {
+                names.push(name);
+            } else if remove_orphans && path.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Table names become directory names: ASCII letters, digits, `_` and `-` only.
+    pub fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty() || name.len() > 128 {
+            return Err(Error::InvalidArgument("table name must be 1 to 128 characters long".to_string()).into());
+        }
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(Error::InvalidArgument(format!(
+                "table name {:?} may only contain ASCII letters, digits, '_' and '-'",
+                name
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::lru::LruCache;
+    use crate::engine::metrics::Metrics;
+    use crate::engine::scan::{Direction, KeyRange};
+    use crate::storage::record::{FieldValue, Record};
+
+    fn record(id: &str, seqno: u64, v: i64) -> Record {
+        Record::from_pairs(id, seqno, vec![("v", FieldValue::Int(v))])
+    }
+
+    #[test]
+    fn immutable_memtables_keep_serving_reads() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut table = Table::create(dir.path(), "t", 0)?;
+        let cache = LruCache::new(4);
+        let metrics = Metrics::default();
+
+        table.memtable.put(record("a", 1, 1));
+        table.memtable.put(record("b", 2, 1));
+        assert!(table.rotate_memtable());
+        assert!(!table.rotate_memtable(), "an empty memtable is not queued");
+        table.memtable.put(record("a", 3, 2));
+
+        let memtables = table.memtables();
+        let get = |id: &str, snapshot: u64| {
+            table.reader.get(&table.meta, &memtables, id, snapshot, &cache, &metrics).unwrap().map(|r| r.data["v"].clone())
+        };
+        assert_eq!(get("a", u64::MAX), Some(FieldValue::Int(2)));
+        assert_eq!(get("a", 2), Some(FieldValue::Int(1)));
+        assert_eq!(get("b", u64::MAX), Some(FieldValue::Int(1)));
+
+        let scanned: Vec<(String, u64)> = table.reader
+            .scan(&table.meta, &memtables, &KeyRange::all(), u64::MAX, Direction::Forward, &cache, &metrics)?
+            .map(|r| (r.id, r.seqno))
+            .collect();
+        assert_eq!(scanned, vec![("a".to_string(), 3), ("b".to_string(), 2)]);
+        assert_eq!(table.min_unflushed_seqno(), Some(1));
+        Ok(())
+    }
+}
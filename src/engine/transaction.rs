@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::engine::batch::WriteBatch;
+use crate::engine::engine::Engine;
+use crate::engine::snapshot::Snapshot;
+use crate::error::{Error, Result};
+use crate::storage::record::{FieldValue, Record};
+
+/// Carried by `Error::Conflict` when `Transaction::commit` finds that a key it read was written
+/// by someone else after the transaction started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionConflict {
+    pub id: String,
+    pub start_seqno: u64,
+}
+
+impl std::fmt::Display for TransactionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TransactionConflict: key {:?} was modified after seqno {}",
+            self.id, self.start_seqno
+        )
+    }
+}
+
+impl std::error::Error for TransactionConflict {}
+
+/// Optimistic transaction.
+///
+/// Reads see the engine as of the start seqno plus the transaction's own buffered writes.
+/// Writes are buffered until `commit`, which fails with `Error::Conflict` if any key
+/// read through the transaction got a newer version in the meantime.
+pub struct Transaction {
+    snapshot: Snapshot,
+    writes: WriteBatch,
+    /// Buffered value per key; `None` is a buffered delete.
+    pending: BTreeMap<String, Option<BTreeMap<String, FieldValue>>>,
+    read_set: BTreeSet<String>,
+}
+
+impl Transaction {
+    pub(crate) fn new(snapshot: Snapshot) -> Self {
+        Self {
+            snapshot,
+            writes: WriteBatch::new(),
+            pending: BTreeMap::new(),
+            read_set: BTreeSet::new(),
+        }
+    }
+
+    pub fn start_seqno(&self) -> u64 {
+        self.snapshot.seqno()
+    }
+
+    /// Read `id` at the start seqno. Keys written earlier in this transaction are served
+    /// from the buffer, with `seqno` 0 since they have none until commit.
+    pub fn get(&mut self, engine: &Engine, id: &str) -> Result<Option<Record>> {
+        if let Some(buffered) = self.pending.get(id) {
+            return Ok(buffered.as_ref().map(|value| Record::new(id, 0, value.clone())));
+        }
+        self.read_set.insert(id.to_string());
+        engine.get(id, self.start_seqno())
+    }
+
+    pub fn put(&mut self, id: impl Into<String>, value: BTreeMap<String, FieldValue>) {
+        let id = id.into();
+        self.pending.insert(id.clone(), Some(value.clone()));
+        self.writes.put(id, value);
+    }
+
+    pub fn delete(&mut self, id: impl Into<String>) {
+        let id = id.into();
+        self.pending.insert(id.clone(), None);
+        self.writes.delete(id);
+    }
+
+    pub fn read_set(&self) -> impl Iterator<Item = &str> {
+        self.read_set.iter().map(|s| s.as_str())
+    }
+
+    /// Validate the read set and apply the buffered writes as one atomic batch.
+    /// No other write can land between the validation and the batch.
+    pub fn commit(self, engine: &Engine) -> Result<()> {
+        let start = self.start_seqno();
+        engine.write_if(&self.writes, |engine| {
+            for id in &self.read_set {
+                if engine.modified_since(id, start)? {
+                    return Err(Error::Conflict(TransactionConflict {
+                        id: id.clone(),
+                        start_seqno: start,
+                    }));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Discard buffered writes. Equivalent to dropping the transaction.
+    pub fn rollback(self) {}
+}
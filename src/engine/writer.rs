@@ -1,63 +1,82 @@
 use anyhow::Result;
-use std::collections::BTreeMap;
 use std::path::Path;
 
-use crate::engine::seqno::allocate;
+use crate::engine::options::EngineOptions;
+use crate::engine::seqno::SeqnoAllocator;
 use crate::meta::PageMeta;
 use crate::storage::memtable::MemTable;
 use crate::storage::page::builder::{PageBuilder, Page};
 use crate::storage::page::io::write_page;
-use crate::storage::record::{FieldValue, Record};
+use crate::storage::wal::group::PendingSync;
 use crate::storage::wal::{Wal, WalEntry, WalOp};
 
-const MAX_RECORDS_PER_PAGE: usize = 1024;
-const MAX_PER_PAGE_SIZE: usize = 32 * 1024; // 32 KB for L0
+pub struct Writer {
+    max_records_per_page: usize,
+    max_page_bytes: usize, // L0 page size
+    seqno: SeqnoAllocator,
+}
 
-pub struct Writer;
+impl Default for Writer {
+    fn default() -> Self {
+        Self::new(&EngineOptions::default())
+    }
+}
 
 impl Writer {
-    pub fn new() -> Self {
-        Self
+    pub fn new(options: &EngineOptions) -> Self {
+        Self {
+            max_records_per_page: options.max_records_per_page,
+            max_page_bytes: options.l0_page_bytes,
+            seqno: SeqnoAllocator::default(),
+        }
     }
 
-    pub fn put(
-        &self,
-        memtable: &mut MemTable,
-        wal: &mut Wal,
-        id: String,
-        value: BTreeMap<String, FieldValue>,
-    ) -> Result<()> {
-        let seqno = allocate();
-        let record = Record::new(id, seqno, value);
-        let wal_entry = WalEntry::new(WalOp::Insert, String::new(), record.id.clone(), seqno, Some(record.clone()));
-        wal.append(&wal_entry)?;
-        memtable.put(record);
-
-        Ok(())
+    /// The engine's seqno allocator; every logged entry takes its seqno from here.
+    pub fn seqno(&self) -> &SeqnoAllocator {
+        &self.seqno
     }
 
-    pub fn delete(
-        &self,
-        memtable: &mut MemTable,
-        wal: &mut Wal,
-        id: String,
-    ) -> Result<()> {
-        let seqno = allocate();
-        let record = Record::new_tombstone(id, seqno);
-        let wal_entry = WalEntry::new(WalOp::Delete, String::new(), record.id.clone(), seqno, Some(record.clone()));
-        wal.append(&wal_entry)?;
-        memtable.put(record);
-
-        Ok(())
+    /// Log `entries` as one WAL unit with consecutive seqnos: a single entry on its own,
+    /// several as an atomic batch. Seqnos already set on the entries are overwritten.
+    /// Returns the logged entries for the caller to apply to each table's memtable, and the
+    /// fsync to wait for before acknowledging them.
+    pub fn log(&self, wal: &mut Wal, mut entries: Vec<WalEntry>) -> Result<(Vec<WalEntry>, PendingSync)> {
+        if entries.is_empty() {
+            return Ok((entries, wal.append_batch_nowait(&[])?));
+        }
+
+        let first = self.seqno.allocate_range(entries.len() as u64);
+        for (seqno, entry) in (first..).zip(entries.iter_mut()) {
+            entry.seqno = seqno;
+            if let Some(record) = entry.record.as_mut() {
+                record.seqno = seqno;
+            }
+        }
+
+        let synced = match entries.as_slice() {
+            [entry] => wal.append_nowait(entry)?,
+            _ => wal.append_batch_nowait(&entries)?,
+        };
+        Ok((entries, synced))
+    }
+
+    /// Log the creation or drop of `table`. Returns the seqno of the entry and the fsync to
+    /// wait for.
+    pub fn log_table_op(&self, wal: &mut Wal, op: WalOp, table: &str) -> Result<(u64, PendingSync)> {
+        let seqno = self.seqno.allocate();
+        let synced = wal.append_nowait(&WalEntry::new(op, table, "", seqno, None))?;
+        Ok((seqno, synced))
     }
 
+    /// Write the memtable out as L0 pages. The memtable is left as is; the caller retires it
+    /// once the new pages are registered, so readers never miss a record in between.
     pub fn flush(
         &self,
-        memtable: &mut MemTable,
+        memtable: &MemTable,
         dir: &Path,
         next_page_id: &u64,
     ) -> Result<(u64, Vec<PageMeta>)> {
-        let mut next_page_id = next_page_id.clone();
+        let mut next_page_id = *next_page_id;
         let mut builder = PageBuilder::new();
         let mut count = 0;
         let mut pagesmeta = Vec::new();
@@ -66,35 +85,34 @@ impl Writer {
             for record in versions {
                 let estimated_size = builder.estimate_size_with(record);
 
-                if estimated_size > MAX_PER_PAGE_SIZE {
+                if estimated_size > self.max_page_bytes && !builder.is_empty() {
                     let page = builder.build();
                     pagesmeta.push(self.flush_one(&page, dir, &next_page_id)?);
                     builder = PageBuilder::new();
                     count = 0;
-                    next_page_id = next_page_id + 1;
+                    next_page_id += 1;
                 }
 
                 builder.add(record.clone());
-                builder.update_size(&record);
+                builder.update_size(record);
                 count += 1;
 
-                if count >= MAX_RECORDS_PER_PAGE {
+                if count >= self.max_records_per_page {
                     let page = builder.build();
                     pagesmeta.push(self.flush_one(&page, dir, &next_page_id)?);
                     builder = PageBuilder::new();
                     count = 0;
-                    next_page_id = next_page_id + 1;
+                    next_page_id += 1;
                 }
             }
         }
 
         if count > 0 {
             let page = builder.build();
-            pagesmeta.push(self.flush_one(&page, dir, &mut &next_page_id)?);
-            next_page_id = next_page_id + 1;
+            pagesmeta.push(self.flush_one(&page, dir, &next_page_id)?);
+            next_page_id += 1;
         }
 
-        memtable.clear();
         Ok((next_page_id, pagesmeta))
     }
 
@@ -105,7 +123,7 @@ impl Writer {
         page_id: &u64
     ) -> Result<PageMeta> {
         let path = dir.join(format!("page_{}.db", page_id));
-        let page_size = write_page(&path, &page)?;
+        let page_size = write_page(&path, page)?;
 
         Ok(PageMeta::new(
             *page_id,
@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+
+use crate::engine::engine::ConditionFailed;
+use crate::engine::transaction::TransactionConflict;
+
+/// Everything an engine call can fail with.
+/// A missing key is not an error: reads return `Ok(None)` for it, and `Err` only when the
+/// data could not be read, so lost data never looks like absent data.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The OS failed a read, write or sync.
+    Io(std::io::Error),
+    /// A page, WAL entry or metadata file could not be decoded.
+    Corruption { file: PathBuf, reason: String },
+    /// A page's payload does not match the checksum in its header.
+    ChecksumMismatch { file: PathBuf, expected: u32, actual: u32 },
+    /// A transaction read a key that a later commit changed.
+    Conflict(TransactionConflict),
+    /// A conditional write found another version than it expected.
+    ConditionFailed(ConditionFailed),
+    /// The named table, or the database directory, does not exist.
+    NotFound(String),
+    /// The call itself is invalid: bad options, table name, patch or similar.
+    InvalidArgument(String),
+    /// Another engine, in this process or another, has the data directory open.
+    DirectoryLocked(PathBuf),
+    /// Changes after `since` were requested, but the WAL was already checkpointed up to `checkpoint`.
+    ChangesTruncated { since: u64, checkpoint: u64 },
+    /// The engine was opened with `Engine::open_read_only`.
+    ReadOnly,
+    /// The background worker stopped on this error; writes report it from then on.
+    Background(String),
+    /// A value could not be converted to record fields, or fields to a value, by
+    /// `put_typed` or `get_typed`.
+    Serde(String),
+    /// Any other failure, such as a value that can't be encoded.
+    Internal(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Name the file a decoding error was found in.
+    pub(crate) fn in_file(self, path: &Path) -> Self {
+        let file = path.to_path_buf();
+        match self {
+            Error::Corruption { reason, .. } => Error::Corruption { file, reason },
+            Error::ChecksumMismatch { expected, actual, .. } => Error::ChecksumMismatch { file, expected, actual },
+            other => other,
+        }
+    }
+
+    pub(crate) fn corruption(file: impl Into<PathBuf>, reason: impl std::fmt::Display) -> Self {
+        Error::Corruption {
+            file: file.into(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Whether the error means data on disk is damaged, as opposed to a failed or invalid call.
+    pub fn is_corruption(&self) -> bool {
+        matches!(self, Error::Corruption { .. } | Error::ChecksumMismatch { .. })
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "Io: {}", e),
+            Error::Corruption { file, reason } => write!(f, "Corruption: {:?}: {}", file, reason),
+            Error::ChecksumMismatch { file, expected, actual } => write!(
+                f,
+                "ChecksumMismatch: {:?}: expected {:#010x}, found {:#010x}",
+                file, expected, actual
+            ),
+            Error::Conflict(conflict) => conflict.fmt(f),
+            Error::ConditionFailed(condition) => condition.fmt(f),
+            Error::NotFound(name) => write!(f, "NotFound: {:?} does not exist", name),
+            Error::InvalidArgument(reason) => write!(f, "InvalidArgument: {}", reason),
+            Error::DirectoryLocked(dir) => write!(f, "DirectoryLocked: {:?} is already open by another engine", dir),
+            Error::ChangesTruncated { since, checkpoint } => write!(
+                f,
+                "ChangesTruncated: changes after seqno {} were requested, but the WAL only holds those after {}",
+                since, checkpoint
+            ),
+            Error::ReadOnly => write!(f, "ReadOnly: the engine was opened read-only"),
+            Error::Background(reason) => write!(f, "Background: maintenance failed: {}", reason),
+            Error::Serde(reason) => write!(f, "Serde: {}", reason),
+            Error::Internal(reason) => write!(f, "Internal: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Internal modules work with `anyhow`; typed errors raised inside them travel through
+/// unchanged, and I/O errors keep their kind.
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<Error>() {
+            Ok(typed) => return typed,
+            Err(e) => e,
+        };
+        match e.downcast::<std::io::Error>() {
+            Ok(io) => Error::Io(io),
+            Err(e) => Error::Internal(format!("{:#}", e)),
+        }
+    }
+}
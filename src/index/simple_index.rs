@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+
+use crate::engine::scan::KeyRange;
+use crate::error::{Error, Result};
+use crate::storage::ordered;
+use crate::storage::record::{FieldValue, Record};
+use crate::storage::wal::{WalEntry, WalOp};
+
+/// Index tables are named `.index.<field as hex>`. Table names can't contain `.`,
+/// so they never clash with user tables.
+const INDEX_TABLE_PREFIX: &str = ".index.";
+
+/// Separates the encoded value from the record id in an index key.
+/// Sorts below every character an encoded value can contain.
+const SEPARATOR: char = '\u{1}';
+/// Sorts right after `SEPARATOR`, so `value + AFTER_SEPARATOR` is above every key of `value`.
+const AFTER_SEPARATOR: char = '\u{2}';
+
+/// Name of the hidden table holding the index on `field`.
+pub fn index_table(field: &str) -> String {
+    format!("{}{}", INDEX_TABLE_PREFIX, hex(field.as_bytes()))
+}
+
+pub fn is_index_table(name: &str) -> bool {
+    name.starts_with(INDEX_TABLE_PREFIX)
+}
+
+/// Encode an indexable value so that string order matches `FieldValue` order: the hex of
+/// its order-preserving encoding. Returns `None` for values that are not indexed
+/// (Null, Bool, List, Map).
+pub fn encode_value(value: &FieldValue) -> Option<String> {
+    match value {
+        FieldValue::Null | FieldValue::Bool(_) | FieldValue::List(_) | FieldValue::Map(_) => None,
+        _ => Some(hex(&ordered::encode(value))),
+    }
+}
+
+/// Key of the index entry saying that record `id` has the value encoded as `value`.
+pub fn index_key(value: &str, id: &str) -> String {
+    format!("{}{}{}", value, SEPARATOR, id)
+}
+
+/// Split an index key into the encoded value and the record id.
+pub fn split_key(key: &str) -> Option<(&str, &str)> {
+    key.split_once(SEPARATOR)
+}
+
+/// Index keys of every value in `range`. Both bounds must be of the same kind (numbers
+/// count as one); a half-open range stays within the kind of its bound.
+pub fn key_range(range: &impl RangeBounds<FieldValue>) -> Result<KeyRange> {
+    let encode = |bound: Bound<&FieldValue>| -> Result<Bound<String>> {
+        match bound {
+            Bound::Unbounded => Ok(Bound::Unbounded),
+            Bound::Included(v) | Bound::Excluded(v) => {
+                let key = encode_value(v).ok_or_else(|| Error::InvalidArgument(format!("{:?} can't be indexed", v)))?;
+                Ok(match bound {
+                    Bound::Included(_) => Bound::Included(key),
+                    _ => Bound::Excluded(key),
+                })
+            }
+        }
+    };
+    let start = encode(range.start_bound())?;
+    let end = encode(range.end_bound())?;
+
+    let tag = |bound: Bound<&FieldValue>| match bound {
+        Bound::Included(v) | Bound::Excluded(v) => Some(ordered::kind_tag(v)),
+        Bound::Unbounded => None,
+    };
+    let (start_tag, end_tag) = (tag(range.start_bound()), tag(range.end_bound()));
+    if let (Some(s), Some(e)) = (start_tag, end_tag)
+        && s != e
+    {
+        return Err(Error::InvalidArgument("range bounds must be values of the same kind".to_string()));
+    }
+
+    Ok(KeyRange {
+        start: match start {
+            Bound::Included(v) => Bound::Included(format!("{}{}", v, SEPARATOR)),
+            Bound::Excluded(v) => Bound::Included(format!("{}{}", v, AFTER_SEPARATOR)),
+            Bound::Unbounded => match end_tag {
+                Some(tag) => Bound::Included(hex(&[tag])),
+                None => Bound::Unbounded,
+            },
+        },
+        end: match end {
+            Bound::Included(v) => Bound::Excluded(format!("{}{}", v, AFTER_SEPARATOR)),
+            Bound::Excluded(v) => Bound::Excluded(format!("{}{}", v, SEPARATOR)),
+            // Every value of a kind encodes below the next kind's tag
+            Bound::Unbounded => match start_tag {
+                Some(tag) => Bound::Excluded(hex(&[tag + 1])),
+                None => Bound::Unbounded,
+            },
+        },
+    })
+}
+
+/// Index entries for the index on `field` in `table` when record `id` goes from `old` to `new`
+/// (`None` = absent): a tombstone for the old key and an entry for the new one, or nothing
+/// if the field's value did not change. Seqnos are left for the writer to assign.
+pub fn updates(
+    table: &str,
+    field: &str,
+    id: &str,
+    old: Option<&BTreeMap<String, FieldValue>>,
+    new: Option<&BTreeMap<String, FieldValue>>,
+) -> Vec<WalEntry> {
+    let key = |data: Option<&BTreeMap<String, FieldValue>>| {
+        data.and_then(|d| d.get(field)).and_then(encode_value).map(|v| index_key(&v, id))
+    };
+    let (old, new) = (key(old), key(new));
+    if old == new {
+        return Vec::new();
+    }
+
+    let mut entries = Vec::new();
+    if let Some(old) = old {
+        entries.push(WalEntry::new(WalOp::Delete, table, old.clone(), 0, Some(Record::new_tombstone(old, 0))));
+    }
+    if let Some(new) = new {
+        entries.push(WalEntry::new(WalOp::Insert, table, new.clone(), 0, Some(Record::new(new, 0, BTreeMap::new()))));
+    }
+    entries
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn float(f: f64) -> FieldValue {
+        FieldValue::try_from(f).unwrap()
+    }
+
+    fn keys(values: &[FieldValue]) -> Vec<String> {
+        values.iter().map(|v| encode_value(v).unwrap()).collect()
+    }
+
+    #[test]
+    fn encoding_preserves_value_order() {
+        let numbers = keys(&[float(f64::NEG_INFINITY), FieldValue::Int(i64::MIN), float(-2.5), FieldValue::Int(0), FieldValue::UInt(0), float(1e-9), FieldValue::Int(7), FieldValue::UInt(u64::MAX), float(f64::INFINITY)]);
+        assert!(numbers.is_sorted());
+        assert_eq!(encode_value(&float(-0.0)), encode_value(&float(0.0)));
+
+        let strs = keys(&["".into(), "a".into(), "ab".into(), "b".into(), "é".into()]);
+        assert!(strs.is_sorted());
+        assert!(numbers.last() < strs.first());
+        assert!(encode_value(&FieldValue::Null).is_none());
+    }
+
+    #[test]
+    fn keys_of_a_prefix_value_sort_before_longer_values() {
+        let short = index_key(&encode_value(&"ab".into()).unwrap(), "zzz");
+        let long = index_key(&encode_value(&"abc".into()).unwrap(), "a");
+        assert!(short < long);
+        assert_eq!(split_key(&short), Some((encode_value(&"ab".into()).unwrap().as_str(), "zzz")));
+    }
+
+    #[test]
+    fn range_keys_bound_values_and_kinds() {
+        let key = |v: i64, id: &str| index_key(&encode_value(&FieldValue::Int(v)).unwrap(), id);
+
+        let range = key_range(&(FieldValue::Int(2)..FieldValue::Int(5))).unwrap();
+        assert!(!range.contains(&key(1, "x")));
+        assert!(range.contains(&key(2, "")));
+        assert!(range.contains(&key(4, "zz")));
+        assert!(!range.contains(&key(5, "a")));
+
+        let open = key_range(&(FieldValue::Int(2)..)).unwrap();
+        assert!(open.contains(&key(i64::MAX, "a")));
+        assert!(!open.contains(&index_key(&encode_value(&"s".into()).unwrap(), "a")));
+        assert!(open.contains(&index_key(&encode_value(&FieldValue::UInt(9)).unwrap(), "a")));
+        assert!(!open.contains(&index_key(&encode_value(&float(1.5)).unwrap(), "a")));
+
+        let mixed = key_range(&(FieldValue::Int(1)..FieldValue::UInt(2))).unwrap();
+        assert!(mixed.contains(&index_key(&encode_value(&float(1.5)).unwrap(), "a")));
+        assert!(key_range(&(FieldValue::Int(1)..FieldValue::from("2"))).is_err());
+        assert!(key_range(&(FieldValue::Null..)).is_err());
+    }
+
+    #[test]
+    fn updates_replace_the_old_key() {
+        let data = |v: i64| BTreeMap::from([("age".to_string(), FieldValue::Int(v))]);
+
+        assert!(updates("t", "age", "u1", Some(&data(3)), Some(&data(3))).is_empty());
+
+        let changed = updates("t", "age", "u1", Some(&data(3)), Some(&data(4)));
+        let ops: Vec<_> = changed.iter().map(|e| e.op.clone()).collect();
+        assert_eq!(ops, vec![WalOp::Delete, WalOp::Insert]);
+
+        assert_eq!(updates("t", "age", "u1", None, Some(&data(4))).len(), 1);
+        assert_eq!(updates("t", "age", "u1", Some(&data(4)), None)[0].op, WalOp::Delete);
+        assert!(updates("t", "age", "u1", None, Some(&BTreeMap::new())).is_empty());
+    }
+}
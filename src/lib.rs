@@ -1,7 +1,10 @@
 pub mod engine;
+pub mod error;
 pub mod storage;
 pub mod index;
 pub mod meta;
 pub mod util;
 pub mod lsm;
-pub mod cache;
\ No newline at end of file
+pub mod cache;
+
+pub use error::{Error, Result};
@@ -1,12 +1,20 @@
 use std::path::Path;
 
 use crate::lsm::compaction_plan::CompactionPlan;
-use crate::lsm::merge::{MergeIterator, PageIterator};
+use crate::lsm::merge::{MergeIterator, PageIterator, VersionRetention};
 use crate::storage::page::builder::PageBuilder;
 use crate::storage::page::io::write_page;
 use crate::meta::PageMeta;
 
-pub fn execute_l0_to_l1(plan: CompactionPlan, data_dir: &Path) -> anyhow::Result<(u64, Vec<PageMeta>)> {
+/// Merge the plan's inputs into new L1 pages.
+/// `snapshots` are the live snapshot seqnos; versions they can still see are carried over,
+/// along with the older versions `retention` keeps.
+pub fn execute_l0_to_l1(
+  plan: CompactionPlan,
+  data_dir: &Path,
+  snapshots: &[u64],
+  retention: VersionRetention,
+) -> anyhow::Result<(u64, Vec<PageMeta>)> {
   let mut sources = Vec::new();
 
   for p in &plan.input_l0_pages {
@@ -24,13 +32,17 @@ pub fn execute_l0_to_l1(plan: CompactionPlan, data_dir: &Path) -> anyhow::Result
   let mut builder = PageBuilder::new();
   let mut pages = Vec::new();
 
-  while let Some(record) = merge.next() {
-    if builder.estimate_size_with(&record) > plan.target_page_size_bytes {
+  // Versions of one key always land in the same page, so L1 ranges never overlap
+  while let Some(versions) = merge.next_retained(snapshots, retention) {
+    let group_size: usize = versions.iter().map(|r| builder.estimate_size(r)).sum();
+    if builder.current_size() + group_size > plan.target_page_size_bytes && !builder.is_empty() {
       pages.push(builder.build());
       builder = PageBuilder::new();
     }
-    builder.add(record.clone());
-    builder.update_size(&record);
+    for record in versions {
+      builder.update_size(&record);
+      builder.add(record);
+    }
   }
 
   if !builder.is_empty() {
@@ -50,7 +62,7 @@ pub fn execute_l0_to_l1(plan: CompactionPlan, data_dir: &Path) -> anyhow::Result
       size,
       page.header.page_seqno,
     ));
-    current_page_id = current_page_id + 1;
+    current_page_id += 1;
   }
 
   Ok((current_page_id, metas))
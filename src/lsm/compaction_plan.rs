@@ -1,3 +1,4 @@
+use crate::engine::options::EngineOptions;
 use crate::meta::{PageMeta, TableMeta};
 
 #[derive(Debug)]
@@ -15,18 +16,14 @@ pub struct CompactionPlan {
   pub target_page_id_start: u64,
 }
 
-const L0_LEVEL_PAGES_LIMIT: usize = 8;
-const L0_LEVEL_SIZE_LIMIT_BYTES: u64 = 256 * 1024; // 256 KB
-const L1_PAGE_BYTES: usize = 256 * 1024; // 256 KB
-
-pub fn plan_l0_to_l1(meta: &TableMeta) -> Option<CompactionPlan> {
+pub fn plan_l0_to_l1(meta: &TableMeta, options: &EngineOptions) -> Option<CompactionPlan> {
   let l0 = &meta.level[0];
   if l0.is_empty() {
     return None;
   }
 
   let l0_bytes: u64 = l0.iter().map(|p| p.size_bytes).sum();
-  if l0.len() < L0_LEVEL_PAGES_LIMIT && l0_bytes < L0_LEVEL_SIZE_LIMIT_BYTES {
+  if l0.len() < options.l0_pages_limit && l0_bytes < options.l0_size_limit_bytes {
     return None;
   }
 
@@ -50,7 +47,7 @@ pub fn plan_l0_to_l1(meta: &TableMeta) -> Option<CompactionPlan> {
     input_l1_pages,
     min_key,
     max_key,
-    target_page_size_bytes: L1_PAGE_BYTES,
+    target_page_size_bytes: options.l1_page_bytes,
     target_page_id_start: meta.current_page_id,
   })
 }
\ No newline at end of file
@@ -2,9 +2,36 @@ use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::path::Path;
 
+use crate::engine::scan::Direction;
 use crate::storage::record::Record;
 use crate::storage::page::io::read_page_from_disk;
+use crate::util::now_millis;
 
+/// Which old versions of a key compaction keeps, on top of the newest one and those
+/// live snapshots can still see. Kept versions are what `Engine::history` can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionRetention {
+  /// Nothing more: older versions are dropped once no snapshot needs them.
+  #[default]
+  Latest,
+  /// The newest `n` versions of every key, tombstones included.
+  LastVersions(usize),
+  /// Every version with a seqno above this one, tombstones included.
+  NewerThan(u64),
+}
+
+impl VersionRetention {
+  /// Whether the version `rank` places from the newest (0 = newest) with `seqno` is kept.
+  fn keeps(&self, rank: usize, seqno: u64) -> bool {
+    match *self {
+      VersionRetention::Latest => false,
+      VersionRetention::LastVersions(n) => rank < n,
+      VersionRetention::NewerThan(s) => seqno > s,
+    }
+  }
+}
+
+/// Sorted run of records (by id, versions in ascending seqno) fed into a merge.
 pub struct PageIterator {
   records: Vec<Record>,
   index: usize,
@@ -13,10 +40,15 @@ pub struct PageIterator {
 impl PageIterator {
   pub fn open(path: &Path) -> anyhow::Result<Self> {
     let page = read_page_from_disk(path)?;
-    Ok(Self {
-      records: page.records,
+    Ok(Self::from_records(page.records))
+  }
+
+  /// Build a source from records already sorted by id (memtable, cached page).
+  pub fn from_records(records: Vec<Record>) -> Self {
+    Self {
+      records,
       index: 0,
-    })
+    }
   }
 
   fn peek(&self) -> Option<&Record> {
@@ -34,16 +66,19 @@ impl PageIterator {
 struct HeapItem {
   key: String,
   seqno: u64,
-  level: u32, // 0 = L0, 1 = L1
+  level: u32, // 0 = memtable / L0, 1 = L1
   iter_id: usize,
+  direction: Direction,
 }
 
 
 impl Ord for HeapItem {
   fn cmp(&self, other: &Self) -> Ordering {
-    self.key
-      .cmp(&other.key)
-      .reverse()
+    let by_key = match self.direction {
+      Direction::Forward => self.key.cmp(&other.key).reverse(),
+      Direction::Reverse => self.key.cmp(&other.key),
+    };
+    by_key
       .then_with(|| self.seqno.cmp(&other.seqno))
       .then_with(|| other.level.cmp(&self.level))
   }
@@ -63,44 +98,72 @@ impl PartialEq for HeapItem {
 
 impl Eq for HeapItem {}
 
+/// K-way merge over sorted sources.
+/// Yields the newest version of each key visible at `snapshot`; tombstones and records
+/// expired when the merge was created hide the key.
 pub struct MergeIterator {
   iters: Vec<PageIterator>,
   heap: BinaryHeap<HeapItem>,
+  snapshot: u64,
+  direction: Direction,
+  now: u64,
 }
 
 impl MergeIterator {
-  pub fn new(mut sources: Vec<(PageIterator, u32)>) -> Self {
+  /// Merge used by compaction: every version is visible.
+  pub fn new(sources: Vec<(PageIterator, u32)>) -> Self {
+    Self::with_snapshot(sources, u64::MAX)
+  }
+
+  /// Merge used by the read path: versions newer than `snapshot` are ignored.
+  pub fn with_snapshot(sources: Vec<(PageIterator, u32)>, snapshot: u64) -> Self {
+    Self::with_direction(sources, snapshot, Direction::Forward)
+  }
+
+  /// Merge yielding keys in `direction` order. Sources must be sorted ascending.
+  pub fn with_direction(mut sources: Vec<(PageIterator, u32)>, snapshot: u64, direction: Direction) -> Self {
     let mut heap = BinaryHeap::new();
     let mut iters = Vec::new();
 
     for (i, (iter, level)) in sources.iter_mut().enumerate() {
+      if direction == Direction::Reverse {
+        iter.records[iter.index..].reverse();
+      }
       if let Some(rec) = iter.peek() {
         heap.push(HeapItem {
           key: rec.id.clone(),
           seqno: rec.seqno,
           level: *level,
           iter_id: i,
+          direction,
         });
       }
-      iters.push(std::mem::replace(iter, PageIterator { records: vec![], index: 0}));
+      iters.push(std::mem::replace(iter, PageIterator::from_records(vec![])));
     }
 
-    Self { iters, heap }
+    Self { iters, heap, snapshot, direction, now: now_millis() }
   }
 
-  pub fn next(&mut self) -> Option<Record> {
-    let first = self.heap.pop()?;
-    let mut best = self.iters[first.iter_id].next()?;
-    let key = best.id.clone();
+  fn advance(&mut self, item: &HeapItem) -> Option<Record> {
+    let rec = self.iters[item.iter_id].next()?;
 
-    if let Some(next) = self.iters[first.iter_id].peek() {
+    if let Some(next) = self.iters[item.iter_id].peek() {
       self.heap.push(HeapItem {
         key: next.id.clone(),
         seqno: next.seqno,
-        level: first.level,
-        iter_id: first.iter_id,
+        level: item.level,
+        iter_id: item.iter_id,
+        direction: self.direction,
       });
     }
+    Some(rec)
+  }
+
+  /// Every version of the next key across all sources, newest first.
+  fn next_versions(&mut self) -> Option<Vec<Record>> {
+    let first = self.heap.pop()?;
+    let key = first.key.clone();
+    let mut versions: Vec<Record> = self.advance(&first).into_iter().collect();
 
     while let Some(top) = self.heap.peek() {
       if top.key != key {
@@ -108,26 +171,203 @@ impl MergeIterator {
       }
 
       let dup = self.heap.pop().unwrap();
-      let rec = self.iters[dup.iter_id].next().unwrap();
+      versions.extend(self.advance(&dup));
+    }
+
+    versions.sort_by_key(|r| std::cmp::Reverse(r.seqno));
+    Some(versions)
+  }
 
-      if rec.seqno > best.seqno {
-        best = rec;
+  /// Versions of the next key that compaction must keep, oldest first.
+  /// Partial updates are folded into full values and expired records become tombstones.
+  /// Keys with nothing left to keep are skipped.
+  pub fn next_retained(&mut self, snapshots: &[u64], retention: VersionRetention) -> Option<Vec<Record>> {
+    loop {
+      let versions = materialize(self.next_versions()?, self.now);
+      let kept = retain_versions(versions, snapshots, retention);
+      if !kept.is_empty() {
+        return Some(kept);
       }
+    }
+  }
+}
+
+impl Iterator for MergeIterator {
+  type Item = Record;
 
-      if let Some(next) = self.iters[dup.iter_id].peek() {
-        self.heap.push(HeapItem {
-          key: next.id.clone(),
-          seqno: next.seqno,
-          level: dup.level,
-          iter_id: dup.iter_id,
-        })
+  fn next(&mut self) -> Option<Record> {
+    loop {
+      let versions = self.next_versions()?;
+      let visible = versions.iter().filter(|r| r.seqno <= self.snapshot);
+      if let Some(rec) = Record::resolve(visible, self.now) {
+        return Some(rec);
       }
     }
+  }
+}
+
+/// Turn every partial update of one key (versions newest first) into the full value it
+/// produces, so compaction output never depends on versions it may drop.
+/// Records expired by `now` are replaced by tombstones; no reader can see them any more.
+/// Requires all versions of the key, which holds for L0 → L1 since L1 is the last level.
+pub fn materialize(versions: Vec<Record>, now: u64) -> Vec<Record> {
+  let mut out = Vec::with_capacity(versions.len());
+  let mut below: Option<Record> = None; // next older version
 
-    if best.is_tombstone {
-      None
+  for rec in versions.into_iter().rev() {
+    let rec = if rec.is_patch() {
+      Record::resolve(std::iter::once(&rec).chain(below.as_ref()), now).expect("a patch always resolves to a value")
+    } else if rec.is_expired(now) {
+      Record::new_tombstone(rec.id, rec.seqno)
     } else {
-      Some(best)
+      rec
+    };
+    below = Some(rec.clone());
+    out.push(rec);
+  }
+
+  out.reverse();
+  out
+}
+
+/// Pick the versions of one key (newest first) that a bottommost compaction keeps:
+/// the newest one, the newest one visible to each live snapshot, and those `retention` asks for.
+/// Tombstones with nothing older left to hide are dropped, unless `retention` keeps them.
+/// Returns oldest first.
+pub fn retain_versions(versions: Vec<Record>, snapshots: &[u64], retention: VersionRetention) -> Vec<Record> {
+  let mut kept: Vec<(Record, bool)> = Vec::new();
+  let mut newer: Option<u64> = None;
+
+  for (rank, rec) in versions.into_iter().enumerate() {
+    // `rec` is the newest version visible at `s` iff the next newer version is above `s`
+    let needed = match newer {
+      None => true,
+      Some(n) => snapshots.iter().any(|&s| rec.seqno <= s && s < n),
+    };
+    let retained = retention.keeps(rank, rec.seqno);
+    newer = Some(rec.seqno);
+    if needed || retained {
+      kept.push((rec, retained));
     }
   }
-}
\ No newline at end of file
+
+  while kept.last().is_some_and(|(r, retained)| r.is_tombstone && !retained) {
+    kept.pop();
+  }
+  kept.into_iter().rev().map(|(r, _)| r).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::record::FieldValue;
+
+  fn rec(id: &str, seqno: u64, v: i64) -> Record {
+    Record::from_pairs(id, seqno, vec![("v", FieldValue::Int(v))])
+  }
+
+  #[test]
+  fn merge_keeps_newest_version_per_key() {
+    let newer = PageIterator::from_records(vec![rec("a", 5, 50), rec("c", 6, 60)]);
+    let older = PageIterator::from_records(vec![rec("a", 1, 10), rec("b", 2, 20)]);
+
+    let out: Vec<_> = MergeIterator::new(vec![(newer, 0), (older, 1)]).collect();
+    let ids: Vec<_> = out.iter().map(|r| (r.id.as_str(), r.seqno)).collect();
+    assert_eq!(ids, vec![("a", 5), ("b", 2), ("c", 6)]);
+  }
+
+  #[test]
+  fn merge_skips_tombstones_and_continues() {
+    let l0 = PageIterator::from_records(vec![Record::new_tombstone("a", 3)]);
+    let l1 = PageIterator::from_records(vec![rec("a", 1, 1), rec("b", 2, 2)]);
+
+    let out: Vec<_> = MergeIterator::new(vec![(l0, 0), (l1, 1)]).collect();
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].id, "b");
+  }
+
+  #[test]
+  fn merge_reverse_yields_descending_keys() {
+    let l0 = PageIterator::from_records(vec![rec("b", 4, 40), rec("d", 5, 50)]);
+    let l1 = PageIterator::from_records(vec![rec("a", 1, 1), rec("b", 2, 2), rec("c", 3, 3)]);
+
+    let out: Vec<_> = MergeIterator::with_direction(vec![(l0, 0), (l1, 1)], u64::MAX, Direction::Reverse)
+      .map(|r| (r.id, r.seqno))
+      .collect();
+    assert_eq!(out, vec![
+      ("d".to_string(), 5),
+      ("c".to_string(), 3),
+      ("b".to_string(), 4),
+      ("a".to_string(), 1),
+    ]);
+  }
+
+  #[test]
+  fn retain_keeps_versions_pinned_by_snapshots() {
+    let versions = vec![rec("a", 9, 9), rec("a", 7, 7), rec("a", 5, 5), rec("a", 2, 2)];
+
+    let kept: Vec<_> = retain_versions(versions.clone(), &[], VersionRetention::Latest).iter().map(|r| r.seqno).collect();
+    assert_eq!(kept, vec![9]);
+
+    let kept: Vec<_> = retain_versions(versions, &[3, 6], VersionRetention::Latest).iter().map(|r| r.seqno).collect();
+    assert_eq!(kept, vec![2, 5, 9]);
+  }
+
+  #[test]
+  fn retain_drops_tombstones_with_nothing_below() {
+    let versions = vec![Record::new_tombstone("a", 8), rec("a", 4, 4), Record::new_tombstone("a", 2)];
+
+    assert!(retain_versions(versions.clone(), &[], VersionRetention::Latest).is_empty());
+
+    // Snapshot at 5 still needs the value under the newest tombstone
+    let kept: Vec<_> = retain_versions(versions, &[5], VersionRetention::Latest).iter().map(|r| (r.seqno, r.is_tombstone)).collect();
+    assert_eq!(kept, vec![(4, false), (8, true)]);
+  }
+
+  #[test]
+  fn retention_keeps_old_versions_and_tombstones() {
+    let versions = vec![rec("a", 9, 9), Record::new_tombstone("a", 7), rec("a", 5, 5), rec("a", 2, 2)];
+    let seqnos = |retention| -> Vec<u64> {
+      retain_versions(versions.clone(), &[], retention).iter().map(|r| r.seqno).collect()
+    };
+
+    assert_eq!(seqnos(VersionRetention::LastVersions(3)), vec![5, 7, 9]);
+    assert_eq!(seqnos(VersionRetention::LastVersions(10)), vec![2, 5, 7, 9]);
+    assert_eq!(seqnos(VersionRetention::NewerThan(5)), vec![7, 9]);
+    // The newest version is kept whatever the retention says
+    assert_eq!(seqnos(VersionRetention::NewerThan(20)), vec![9]);
+    // Snapshots still pin versions retention would drop
+    let kept: Vec<_> = retain_versions(versions.clone(), &[3], VersionRetention::LastVersions(2)).iter().map(|r| r.seqno).collect();
+    assert_eq!(kept, vec![2, 7, 9]);
+  }
+
+  #[test]
+  fn merge_respects_snapshot() {
+    let src = PageIterator::from_records(vec![rec("a", 1, 1), rec("a", 4, 4), rec("b", 5, 5)]);
+
+    let out: Vec<_> = MergeIterator::with_snapshot(vec![(src, 0)], 3).collect();
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].seqno, 1);
+  }
+
+  #[test]
+  fn expired_records_are_hidden_and_dropped() {
+    let src = || PageIterator::from_records(vec![
+      rec("a", 1, 1).with_expiry(1),
+      rec("b", 2, 2),
+      rec("b", 3, 3).with_expiry(1),
+      rec("c", 4, 4).with_expiry(u64::MAX),
+    ]);
+
+    let out: Vec<_> = MergeIterator::new(vec![(src(), 0)]).map(|r| r.id).collect();
+    assert_eq!(out, vec!["c".to_string()]);
+
+    let mut merge = MergeIterator::new(vec![(src(), 0)]);
+    let mut kept = Vec::new();
+    while let Some(versions) = merge.next_retained(&[], VersionRetention::Latest) {
+      kept.extend(versions.into_iter().map(|r| (r.id, r.is_tombstone)));
+    }
+    // The newest "b" expired, so with no snapshots nothing of "b" survives
+    assert_eq!(kept, vec![("c".to_string(), false)]);
+  }
+}
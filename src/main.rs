@@ -12,14 +12,14 @@ fn main() -> anyhow::Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
     let base = std::path::Path::new("./data");
     fs::create_dir_all(base)?;
-    let mut engine = Engine::open(base)?;
+    let engine = Engine::open(base)?;
 
     match args[1].as_str() {
         "put" => {
             engine.put(args[2].clone(), parse_value(&args[3]))?;
         }
         "get" => {
-            let rec = engine.get(&args[2], u64::MAX);
+            let rec = engine.get(&args[2], u64::MAX)?;
             println!("{:?}", rec);
         }
         "flush" => {
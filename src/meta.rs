@@ -1,5 +1,8 @@
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
+use crate::error::Error;
+use crate::engine::schema::Schema;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -39,8 +42,30 @@ impl PageMeta {
 pub struct TableMeta {
     pub version: u32,
     pub level: Vec<Vec<PageMeta>>,
+    /// Every write to this table up to here is in its pages; WAL replay skips it.
     pub checkpoint_seqno: u64,
     pub current_page_id: u64,
+    /// Seqno of the `CreateTable` entry; 0 for the default table.
+    #[serde(default)]
+    pub created_seqno: u64,
+    /// Last seqno the engine had issued when the WAL was last checkpointed.
+    /// Kept in the default table's meta, so seqnos never go backwards after the WAL is emptied.
+    #[serde(default)]
+    pub last_seqno: u64,
+    /// Every WAL entry up to here was dropped by the last WAL checkpoint. Default table only.
+    #[serde(default)]
+    pub wal_checkpoint: u64,
+    /// Change consumers and the seqno each has acknowledged; the WAL keeps every entry
+    /// after the lowest of them. Default table only.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub consumers: BTreeMap<String, u64>,
+    /// Fields with a finished secondary index, and the seqno it was finished at: it serves
+    /// snapshots from there on. Default table only.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub indexes: BTreeMap<String, u64>,
+    /// Checked on every write to this table, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Schema>,
 }
 
 impl Default for TableMeta {
@@ -53,6 +78,12 @@ impl Default for TableMeta {
             ],
             checkpoint_seqno: 0,
             current_page_id: 0,
+            created_seqno: 0,
+            last_seqno: 0,
+            wal_checkpoint: 0,
+            consumers: BTreeMap::new(),
+            indexes: BTreeMap::new(),
+            schema: None,
         }
     }
 }
@@ -64,7 +95,7 @@ impl TableMeta {
             return Ok(Self::default());
         }
         let data = fs::read(path)?;
-        Ok(serde_json::from_slice(&data)?)
+        serde_json::from_slice(&data).map_err(|e| Error::corruption(path, e).into())
     }
 
     pub fn persist(&self, path: impl AsRef<Path>) -> Result<()> {
@@ -76,4 +107,15 @@ impl TableMeta {
     pub fn add_pages(&mut self, new_pages: Vec<PageMeta>) {
         self.level[0].extend(new_pages);
     }
+
+    /// Highest seqno this table is known to have seen issued, by any record of it.
+    pub fn max_seqno(&self) -> u64 {
+        self.level
+            .iter()
+            .flatten()
+            .map(|p| p.max_seqno)
+            .chain([self.last_seqno, self.checkpoint_seqno, self.created_seqno])
+            .max()
+            .unwrap_or(0)
+    }
 }
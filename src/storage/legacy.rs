@@ -0,0 +1,77 @@
+//! On-disk formats written by earlier versions.
+//! They are decoded on read and upgraded in memory; new files always use the current format.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::storage::patch::Patch;
+use crate::storage::record::{FieldValue, Record};
+use crate::storage::wal::{WalEntry, WalOp};
+
+/// Record layout of page format v1 (before partial updates).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordV1 {
+  pub id: String,
+  pub seqno: u64,
+  pub is_tombstone: bool,
+  pub data: BTreeMap<String, FieldValue>,
+}
+
+impl From<RecordV1> for Record {
+  fn from(r: RecordV1) -> Self {
+    let mut rec = Record::new(r.id, r.seqno, r.data);
+    rec.is_tombstone = r.is_tombstone;
+    rec
+  }
+}
+
+/// WAL entry layout holding a `RecordV1`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalEntryV1 {
+  pub seqno: u64,
+  pub op: WalOp,
+  pub table: String,
+  pub record_id: String,
+  pub record: Option<RecordV1>,
+}
+
+impl From<WalEntryV1> for WalEntry {
+  fn from(e: WalEntryV1) -> Self {
+    WalEntry::new(e.op, e.table, e.record_id, e.seqno, e.record.map(Record::from))
+  }
+}
+
+/// Record layout of page format v2 (before per-record TTL).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordV2 {
+  pub id: String,
+  pub seqno: u64,
+  pub is_tombstone: bool,
+  pub data: BTreeMap<String, FieldValue>,
+  pub patch: Option<Patch>,
+}
+
+impl From<RecordV2> for Record {
+  fn from(r: RecordV2) -> Self {
+    let mut rec = Record::new(r.id, r.seqno, r.data);
+    rec.is_tombstone = r.is_tombstone;
+    rec.patch = r.patch;
+    rec
+  }
+}
+
+/// WAL entry layout holding a `RecordV2`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalEntryV2 {
+  pub seqno: u64,
+  pub op: WalOp,
+  pub table: String,
+  pub record_id: String,
+  pub record: Option<RecordV2>,
+}
+
+impl From<WalEntryV2> for WalEntry {
+  fn from(e: WalEntryV2) -> Self {
+    WalEntry::new(e.op, e.table, e.record_id, e.seqno, e.record.map(Record::from))
+  }
+}
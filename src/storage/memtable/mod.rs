@@ -1,4 +1,6 @@
 use std::collections::BTreeMap;
+use crate::engine::scan::KeyRange;
+use crate::storage::patch::PatchOp;
 use crate::storage::record::{FieldValue, Record};
 
 #[derive(Debug)]
@@ -6,6 +8,12 @@ pub struct MemTable {
   pub data: BTreeMap<String, Vec<Record>>,
 }
 
+impl Default for MemTable {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl MemTable {
   pub fn new() -> Self {
     Self {
@@ -45,10 +53,25 @@ impl MemTable {
     self.data.iter()
   }
 
+  /// Keys (with all their versions) inside `range`, in ascending id order.
+  pub fn range<'a>(&'a self, range: &'a KeyRange) -> impl Iterator<Item = (&'a String, &'a Vec<Record>)> {
+    let bounds = if range.is_empty() { None } else { Some(range.as_bounds()) };
+    bounds.into_iter().flat_map(move |b| self.data.range::<str, _>(b))
+  }
+
   pub fn clear(&mut self) {
     self.data.clear();
   }
 
+  /// Oldest seqno not yet flushed, if any.
+  pub fn min_seqno(&self) -> Option<u64> {
+    self.data.values().flatten().map(|r| r.seqno).min()
+  }
+
+  pub fn max_seqno(&self) -> Option<u64> {
+    self.data.values().flatten().map(|r| r.seqno).max()
+  }
+
   pub fn approx_size_bytes(&self) -> usize {
     let mut size = 0;
 
@@ -66,15 +89,16 @@ impl MemTable {
 
         for (field, value) in &record.data {
           size += field.len();
+          size += value_size(value);
+        }
 
-          size += match value {
-            FieldValue::Str(s) => s.len(),
-            FieldValue::Int(_) => std::mem::size_of::<i64>(),
-            FieldValue::Bool(_) => std::mem::size_of::<bool>(),
-            FieldValue::Float(_) => std::mem::size_of::<f64>(),
-            FieldValue::Null => 0,
-            FieldValue::UInt(_) => std::mem::size_of::<u64>(),
-          };
+        if let Some(patch) = &record.patch {
+          for op in patch.ops() {
+            size += match op {
+              PatchOp::Set(field, value) | PatchOp::Increment(field, value) => field.len() + value_size(value),
+              PatchOp::Remove(field) => field.len(),
+            };
+          }
         }
       }
     }
@@ -83,5 +107,20 @@ impl MemTable {
 
 }
 
+fn value_size(value: &FieldValue) -> usize {
+  match value {
+    FieldValue::Str(s) => s.len(),
+    FieldValue::Int(_) => std::mem::size_of::<i64>(),
+    FieldValue::Bool(_) => std::mem::size_of::<bool>(),
+    FieldValue::Float(_) => std::mem::size_of::<f64>(),
+    FieldValue::Null => 0,
+    FieldValue::UInt(_) => std::mem::size_of::<u64>(),
+    FieldValue::Bytes(b) => b.len(),
+    FieldValue::Timestamp(_) => std::mem::size_of::<i64>(),
+    FieldValue::List(items) => items.iter().map(|v| std::mem::size_of::<FieldValue>() + value_size(v)).sum(),
+    FieldValue::Map(fields) => fields.iter().map(|(k, v)| k.len() + std::mem::size_of::<FieldValue>() + value_size(v)).sum(),
+  }
+}
+
 #[cfg(test)]
 mod tests;
\ No newline at end of file
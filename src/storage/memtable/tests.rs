@@ -36,3 +36,19 @@ fn memtable_delete_creates_tombstone() {
     let rec = mem.get("k", 10);
     assert_eq!(rec, None);
 }
+
+#[test]
+fn memtable_size_counts_nested_values() {
+    let flat = |v: FieldValue| {
+        let mut mem = MemTable::new();
+        mem.put(Record::from_pairs("k", 1, vec![("v", v)]));
+        mem.approx_size_bytes()
+    };
+    let empty = flat(FieldValue::Null);
+
+    assert_eq!(flat(FieldValue::Bytes(vec![0; 100])), empty + 100);
+    let list = FieldValue::List(vec![FieldValue::Bytes(vec![0; 100]), FieldValue::from("abc")]);
+    assert!(flat(list) > empty + 103);
+    let map = FieldValue::Map([("inner".to_string(), FieldValue::Bytes(vec![0; 100]))].into());
+    assert!(flat(map) > empty + 105);
+}
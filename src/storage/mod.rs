@@ -1,4 +1,8 @@
 pub mod record;
 pub mod wal;
 pub mod page;
-pub mod memtable;
\ No newline at end of file
+pub mod memtable;
+pub mod patch;
+pub mod legacy;
+pub mod ordered;
+pub mod typed;
@@ -0,0 +1,304 @@
+//! Order-preserving binary encoding of `FieldValue`, for keys on disk.
+//!
+//! For any values `a` and `b`, `encode(a) < encode(b)` bytewise exactly when `a < b` in
+//! `FieldValue`'s `Ord`, numbers of different variants included. Encodings are self-delimiting:
+//! no encoding is a prefix of another, so more key bytes (a record id, another value) can
+//! follow one without disturbing the order.
+//!
+//! Layout, after a one-byte kind tag (`FieldValue::kind_rank`, which never changes for a kind):
+//! - Null: nothing
+//! - Bool: one byte, 0 or 1
+//! - Number: the largest f64 not above the value, as 8 bytes ordered like the floats; the
+//!   remainder the f64 could not hold (non-zero only for large Int and UInt values), as a
+//!   big-endian u64; then the variant, Int < UInt < Float
+//! - Str, Bytes: the bytes with each 0x00 escaped as 0x00 0xFF, ended by 0x00 0x00
+//! - Timestamp: the millis with the sign bit flipped, big-endian
+//! - List: each element as 0x01 then its encoding, ended by 0x00
+//! - Map: each entry in key order as 0x01, the key encoded as a Str, then the value; ended by 0x00
+
+use ordered_float::NotNan;
+use std::collections::BTreeMap;
+
+use crate::storage::record::FieldValue;
+
+const NULL: u8 = 0x00;
+const BOOL: u8 = 0x01;
+const NUMBER: u8 = 0x02;
+const STR: u8 = 0x03;
+const BYTES: u8 = 0x04;
+const TIMESTAMP: u8 = 0x05;
+const LIST: u8 = 0x06;
+const MAP: u8 = 0x07;
+
+/// Precedes each element of a list or entry of a map; `END` follows the last one.
+const MORE: u8 = 0x01;
+const END: u8 = 0x00;
+
+const INT: u8 = 0;
+const UINT: u8 = 1;
+const FLOAT: u8 = 2;
+
+/// Kind tag an encoding starts with. Every value of a kind encodes within
+/// `[kind_tag(v)]..[kind_tag(v) + 1]`.
+pub fn kind_tag(value: &FieldValue) -> u8 {
+  value.kind_rank()
+}
+
+pub fn encode(value: &FieldValue) -> Vec<u8> {
+  let mut out = Vec::new();
+  encode_into(value, &mut out);
+  out
+}
+
+pub fn encode_into(value: &FieldValue, out: &mut Vec<u8>) {
+  out.push(kind_tag(value));
+  match value {
+    FieldValue::Null => {}
+    FieldValue::Bool(b) => out.push(*b as u8),
+    FieldValue::Int(i) => encode_int(*i as i128, INT, out),
+    FieldValue::UInt(u) => encode_int(*u as i128, UINT, out),
+    FieldValue::Float(f) => {
+      // -0.0 and 0.0 are equal values, so they must encode the same
+      let f = if f.into_inner() == 0.0 { 0.0 } else { f.into_inner() };
+      out.extend_from_slice(&ordered_bits(f).to_be_bytes());
+      out.extend_from_slice(&0u64.to_be_bytes());
+      out.push(FLOAT);
+    }
+    FieldValue::Str(s) => encode_bytes(s.as_bytes(), out),
+    FieldValue::Bytes(b) => encode_bytes(b, out),
+    FieldValue::Timestamp(t) => out.extend_from_slice(&((*t as u64) ^ (1 << 63)).to_be_bytes()),
+    FieldValue::List(items) => {
+      for item in items {
+        out.push(MORE);
+        encode_into(item, out);
+      }
+      out.push(END);
+    }
+    FieldValue::Map(fields) => {
+      for (key, value) in fields {
+        out.push(MORE);
+        out.push(STR);
+        encode_bytes(key.as_bytes(), out);
+        encode_into(value, out);
+      }
+      out.push(END);
+    }
+  }
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+  for &b in bytes {
+    out.push(b);
+    if b == 0 {
+      out.push(0xFF);
+    }
+  }
+  out.extend_from_slice(&[0, 0]);
+}
+
+fn encode_int(i: i128, variant: u8, out: &mut Vec<u8>) {
+  // Round down to an f64, then keep what rounding lost
+  let mut floor = i as f64;
+  if floor as i128 > i {
+    floor = floor.next_down();
+  }
+  let remainder = (i - floor as i128) as u64;
+  out.extend_from_slice(&ordered_bits(floor).to_be_bytes());
+  out.extend_from_slice(&remainder.to_be_bytes());
+  out.push(variant);
+}
+
+/// f64 bits reordered so that unsigned comparison matches float comparison.
+fn ordered_bits(f: f64) -> u64 {
+  let bits = f.to_bits();
+  if bits >> 63 == 1 { !bits } else { bits | (1 << 63) }
+}
+
+fn from_ordered_bits(bits: u64) -> f64 {
+  f64::from_bits(if bits >> 63 == 1 { bits & !(1 << 63) } else { !bits })
+}
+
+/// Decode the value at the start of `bytes`. Returns it with the number of bytes it took,
+/// or `None` if `bytes` does not start with a valid encoding.
+pub fn decode(bytes: &[u8]) -> Option<(FieldValue, usize)> {
+  let (&tag, rest) = bytes.split_first()?;
+  match tag {
+    NULL => Some((FieldValue::Null, 1)),
+    BOOL => match rest.first()? {
+      0 => Some((FieldValue::Bool(false), 2)),
+      1 => Some((FieldValue::Bool(true), 2)),
+      _ => None,
+    },
+    NUMBER => {
+      let floor = from_ordered_bits(u64::from_be_bytes(rest.get(..8)?.try_into().ok()?));
+      let remainder = u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?);
+      let value = match *rest.get(16)? {
+        INT => FieldValue::Int(i64::try_from(floor as i128 + remainder as i128).ok()?),
+        UINT => FieldValue::UInt(u64::try_from(floor as i128 + remainder as i128).ok()?),
+        FLOAT => FieldValue::Float(NotNan::new(floor).ok()?),
+        _ => return None,
+      };
+      Some((value, 18))
+    }
+    STR => {
+      let (bytes, len) = decode_bytes(rest)?;
+      Some((FieldValue::Str(String::from_utf8(bytes).ok()?), len + 1))
+    }
+    BYTES => {
+      let (bytes, len) = decode_bytes(rest)?;
+      Some((FieldValue::Bytes(bytes), len + 1))
+    }
+    TIMESTAMP => {
+      let bits = u64::from_be_bytes(rest.get(..8)?.try_into().ok()?);
+      Some((FieldValue::Timestamp((bits ^ (1 << 63)) as i64), 9))
+    }
+    LIST => {
+      let mut items = Vec::new();
+      let mut i = 0;
+      while *rest.get(i)? == MORE {
+        let (item, len) = decode(&rest[i + 1..])?;
+        items.push(item);
+        i += 1 + len;
+      }
+      (rest[i] == END).then_some((FieldValue::List(items), i + 2))
+    }
+    MAP => {
+      let mut fields = BTreeMap::new();
+      let mut i = 0;
+      while *rest.get(i)? == MORE {
+        let (FieldValue::Str(key), key_len) = decode(&rest[i + 1..])? else {
+          return None;
+        };
+        let (value, value_len) = decode(&rest[i + 1 + key_len..])?;
+        fields.insert(key, value);
+        i += 1 + key_len + value_len;
+      }
+      (rest[i] == END).then_some((FieldValue::Map(fields), i + 2))
+    }
+    _ => None,
+  }
+}
+
+/// Unescape a Str or Bytes body. Returns the bytes and the length of the body, terminator included.
+fn decode_bytes(body: &[u8]) -> Option<(Vec<u8>, usize)> {
+  let mut out = Vec::new();
+  let mut i = 0;
+  loop {
+    match (*body.get(i)?, body.get(i + 1)) {
+      (0, Some(0)) => return Some((out, i + 2)),
+      (0, Some(0xFF)) => {
+        out.push(0);
+        i += 2;
+      }
+      (0, _) => return None,
+      (b, _) => {
+        out.push(b);
+        i += 1;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn float(f: f64) -> FieldValue {
+    FieldValue::try_from(f).unwrap()
+  }
+
+  /// Values in ascending order, across kinds and number variants.
+  fn ascending() -> Vec<FieldValue> {
+    vec![
+      FieldValue::Null,
+      FieldValue::Bool(false),
+      FieldValue::Bool(true),
+      float(f64::NEG_INFINITY),
+      float(-1e300),
+      FieldValue::Int(i64::MIN),
+      FieldValue::Int(-(1 << 53) - 1),
+      float(-2.5),
+      FieldValue::Int(-1),
+      float(-0.5),
+      FieldValue::Int(0),
+      FieldValue::UInt(0),
+      float(0.0),
+      float(1e-9),
+      FieldValue::Int(1),
+      FieldValue::UInt(1),
+      float(1.0),
+      FieldValue::Int((1 << 53) + 1),
+      float(9007199254740994.0),
+      FieldValue::Int(i64::MAX),
+      FieldValue::UInt(i64::MAX as u64),
+      FieldValue::UInt(u64::MAX - 1),
+      FieldValue::UInt(u64::MAX),
+      float(18446744073709551616.0),
+      float(f64::INFINITY),
+      FieldValue::from(""),
+      FieldValue::from("a"),
+      FieldValue::from("a\0"),
+      FieldValue::from("a\0b"),
+      FieldValue::from("ab"),
+      FieldValue::from("é"),
+      FieldValue::Bytes(vec![]),
+      FieldValue::Bytes(vec![0]),
+      FieldValue::Bytes(vec![0, 0]),
+      FieldValue::Bytes(vec![1]),
+      FieldValue::Timestamp(i64::MIN),
+      FieldValue::Timestamp(-1),
+      FieldValue::Timestamp(0),
+      FieldValue::Timestamp(1_700_000_000_000),
+      FieldValue::List(vec![]),
+      FieldValue::List(vec![FieldValue::Null]),
+      FieldValue::List(vec![FieldValue::Null, FieldValue::Null]),
+      FieldValue::List(vec![FieldValue::Int(1), FieldValue::from("a")]),
+      FieldValue::List(vec![FieldValue::UInt(1)]),
+      FieldValue::List(vec![FieldValue::List(vec![])]),
+      FieldValue::Map(BTreeMap::new()),
+      FieldValue::Map(BTreeMap::from([("a".to_string(), FieldValue::Int(2))])),
+      FieldValue::Map(BTreeMap::from([("a".to_string(), FieldValue::Int(2)), ("b".to_string(), FieldValue::Null)])),
+      FieldValue::Map(BTreeMap::from([("a".to_string(), FieldValue::Int(3))])),
+      FieldValue::Map(BTreeMap::from([("b".to_string(), FieldValue::Null)])),
+    ]
+  }
+
+  #[test]
+  fn ord_follows_the_documented_rules() {
+    let values = ascending();
+    for (i, a) in values.iter().enumerate() {
+      for (j, b) in values.iter().enumerate() {
+        assert_eq!(a.cmp(b), i.cmp(&j), "{:?} vs {:?}", a, b);
+      }
+    }
+    assert_eq!(float(-0.0), float(0.0));
+    assert_eq!(float(-0.0).cmp(&float(0.0)), std::cmp::Ordering::Equal);
+  }
+
+  #[test]
+  fn encoding_preserves_order_and_round_trips() {
+    let values = ascending();
+    let encoded: Vec<Vec<u8>> = values.iter().map(encode).collect();
+    for (i, a) in encoded.iter().enumerate() {
+      for (j, b) in encoded.iter().enumerate() {
+        assert_eq!(a.cmp(b), i.cmp(&j), "{:?} vs {:?}", values[i], values[j]);
+      }
+    }
+    for (value, bytes) in values.iter().zip(&encoded) {
+      assert_eq!(decode(bytes), Some((value.clone(), bytes.len())));
+    }
+    assert_eq!(encode(&float(-0.0)), encode(&float(0.0)));
+  }
+
+  #[test]
+  fn encodings_can_be_followed_by_more_bytes() {
+    let mut key = encode(&FieldValue::from("a"));
+    key.extend_from_slice(b"\xFFrest");
+    assert!(key < encode(&FieldValue::from("a\0")));
+    assert_eq!(decode(&key), Some((FieldValue::from("a"), 4)));
+
+    assert_eq!(decode(&[STR, b'a', 0]), None);
+    assert_eq!(decode(&[NUMBER, 0]), None);
+    assert_eq!(decode(&[9]), None);
+  }
+}
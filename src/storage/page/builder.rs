@@ -17,6 +17,12 @@ pub struct PageBuilder {
   current_size_bytes: usize
 }
 
+impl Default for PageBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl PageBuilder {
   pub fn new() -> Self {
     Self {
@@ -61,14 +67,17 @@ impl PageBuilder {
   }
 
   pub fn estimate_size(&self, record: &Record) -> usize {
-    let record_size = bincode::serialized_size(record).unwrap() as usize;
-    record_size
+    bincode::serialized_size(record).unwrap() as usize
   }
 
   pub fn estimate_size_with(&self, record: &Record) -> usize {
     self.current_size_bytes + self.estimate_size(record)
   }
 
+  pub fn current_size(&self) -> usize {
+    self.current_size_bytes
+  }
+
   pub fn update_size(&mut self, record: &Record) {
     self.current_size_bytes = self.estimate_size_with(record);
   }
@@ -5,7 +5,12 @@ use crc32fast::Hasher;
 pub const PAGE_MAGIC: u32 = 0x53484442; // 'SHDB'
 
 /// Current page format version
-pub const PAGE_VERSION: u16 = 1;
+/// v1: records without `patch`
+/// v2: records carry an optional `patch` (partial updates)
+/// v3: records carry an optional `expires_at` (TTL)
+/// v4: values may be Bytes, Timestamp, List or Map. The record layout is unchanged and v3
+///     payloads decode as is; the bump makes older readers reject pages with the new values.
+pub const PAGE_VERSION: u16 = 4;
 
 /// Immutable page header.
 /// Stored at the beginning of every page file.
@@ -51,7 +56,7 @@ impl PageHeader {
         if self.magic != PAGE_MAGIC {
             return Err("Invalid page magic".into());
         }
-        if self.version != PAGE_VERSION {
+        if self.version == 0 || self.version > PAGE_VERSION {
             return Err("Unsupported page version".into());
         }
         if self.min_id > self.max_id {
@@ -43,11 +43,12 @@ pub fn write_page(path: impl AsRef<Path>, page: &Page) -> Result<u64> {
 
 /// Read page from disk and validate
 pub fn read_page_from_disk(path: impl AsRef<Path>) -> Result<Page> {
+  let path = path.as_ref();
   let mut file = File::open(path)?;
   let mut bytes = Vec::new();
   file.read_to_end(&mut bytes)?;
 
-  read_page(&bytes)
+  Ok(read_page(&bytes).map_err(|e| e.in_file(path))?)
 }
 
 
@@ -55,10 +56,9 @@ pub fn read_page_from_disk(path: impl AsRef<Path>) -> Result<Page> {
 pub fn delete_older_pages(dir: &Path, pages_to_be_removed: Vec<PageMeta>) -> Result<()>{
   for page in pages_to_be_removed {
     let path = dir.join(page.file_name);
-    if let Err(e) = fs::remove_file(&path) {
-      if e.kind() != std::io::ErrorKind::NotFound {
-          return Err(e.into());
-      }
+    if let Err(e) = fs::remove_file(&path)
+      && e.kind() != std::io::ErrorKind::NotFound {
+        return Err(e.into());
     }
   }
 
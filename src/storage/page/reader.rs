@@ -1,30 +1,47 @@
-use anyhow::{Result, bail};
 use std::io::{Cursor, Read};
+use std::path::PathBuf;
 
+use crate::error::{Error, Result};
+
+use crate::storage::legacy::{RecordV1, RecordV2};
 use crate::storage::page::header::PageHeader;
 use crate::storage::page::builder::Page;
 use crate::storage::record::Record;
 
 /// Read a page from raw bytes.
 /// Expected layout: [header][payload]
+/// Decoding errors name no file; `read_page_from_disk` fills it in.
 pub fn read_page(bytes: &[u8]) -> Result<Page> {
+  let corrupt = |reason: String| Error::corruption(PathBuf::new(), reason);
   let mut cursor = Cursor::new(bytes);
 
-  let header: PageHeader = bincode::deserialize_from(&mut cursor)?;
-  header.validate().map_err(|e| anyhow::anyhow!(e))?;
+  let header: PageHeader = bincode::deserialize_from(&mut cursor)
+    .map_err(|e| corrupt(format!("unreadable page header: {}", e)))?;
+  header.validate().map_err(corrupt)?;
 
   let mut payload = Vec::new();
   cursor.read_to_end(&mut payload)?;
 
   let checksum = PageHeader::compute_checksum(&payload);
   if checksum != header.checksum {
-    bail!("Page checksum mismatch");
+    return Err(Error::ChecksumMismatch {
+      file: PathBuf::new(),
+      expected: header.checksum,
+      actual: checksum,
+    });
   }
 
-  let records: Vec<Record> = bincode::deserialize(&payload)?;
+  let records: std::result::Result<Vec<Record>, bincode::Error> = match header.version {
+    1 => bincode::deserialize::<Vec<RecordV1>>(&payload)
+      .map(|records| records.into_iter().map(Record::from).collect()),
+    2 => bincode::deserialize::<Vec<RecordV2>>(&payload)
+      .map(|records| records.into_iter().map(Record::from).collect()),
+    _ => bincode::deserialize(&payload),
+  };
+  let records = records.map_err(|e| corrupt(format!("undecodable records: {}", e)))?;
 
   if records.len() != header.num_records as usize {
-    bail!("Number of records mismatch");
+    return Err(corrupt("Number of records mismatch".to_string()));
   }
 
   Ok(Page {
@@ -218,4 +218,103 @@ fn page_is_immutable() {
     // Writing again should fail
     let result = write_page(&page_path, &page);
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+
+#[test]
+fn reads_v1_pages_written_before_partial_updates() {
+    use crate::storage::legacy::RecordV1;
+
+    let records = vec![
+        RecordV1 { id: "a".into(), seqno: 1, is_tombstone: false, data: [("v".to_string(), FieldValue::Int(1))].into() },
+        RecordV1 { id: "b".into(), seqno: 2, is_tombstone: true, data: Default::default() },
+    ];
+    let payload = bincode::serialize(&records).unwrap();
+
+    let mut header = PageHeader::new("a".into(), "b".into(), 2, 2);
+    header.version = 1;
+    header.checksum = PageHeader::compute_checksum(&payload);
+
+    let mut bytes = bincode::serialize(&header).unwrap();
+    bytes.extend(&payload);
+
+    let page = read_page(&bytes).unwrap();
+    assert_eq!(page.records[0], Record::from_pairs("a", 1, vec![("v", FieldValue::Int(1))]));
+    assert_eq!(page.records[1], Record::new_tombstone("b", 2));
+}
+
+#[test]
+fn reads_v2_pages_written_before_ttl() {
+    use crate::storage::legacy::RecordV2;
+    use crate::storage::patch::Patch;
+
+    let records = vec![
+        RecordV2 { id: "a".into(), seqno: 1, is_tombstone: false, data: Default::default(), patch: Some(Patch::new().set("v", 1i64)) },
+    ];
+    let payload = bincode::serialize(&records).unwrap();
+
+    let mut header = PageHeader::new("a".into(), "a".into(), 1, 1);
+    header.version = 2;
+    header.checksum = PageHeader::compute_checksum(&payload);
+
+    let mut bytes = bincode::serialize(&header).unwrap();
+    bytes.extend(&payload);
+
+    let page = read_page(&bytes).unwrap();
+    assert_eq!(page.records[0], Record::new_patch("a", 1, Patch::new().set("v", 1i64)));
+    assert_eq!(page.records[0].expires_at, None);
+}
+
+#[test]
+fn page_roundtrip_keeps_nested_values() {
+    use std::collections::BTreeMap;
+
+    let nested = FieldValue::Map(BTreeMap::from([
+        ("tags".to_string(), FieldValue::List(vec!["a".into(), FieldValue::Null])),
+        ("blob".to_string(), FieldValue::Bytes(vec![0, 1, 255])),
+    ]));
+    let record = Record::from_pairs("a", 1, vec![("doc", nested), ("at", FieldValue::Timestamp(-5))]);
+    let mut pb = PageBuilder::new();
+    pb.add(record.clone());
+    let page = pb.build();
+    assert_eq!(page.header.version, 4);
+
+    let mut bytes = bincode::serialize(&page.header).unwrap();
+    bytes.extend(&page.payload);
+    assert_eq!(read_page(&bytes).unwrap().records, vec![record]);
+}
+
+#[test]
+fn reads_v3_pages_written_before_nested_values() {
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    // The value and record layout of format v3
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum FieldValueV3 { Null, Bool(bool), Int(i64), UInt(u64), Float(f64), Str(String) }
+    #[derive(Serialize)]
+    struct RecordV3 {
+        id: String,
+        seqno: u64,
+        is_tombstone: bool,
+        data: BTreeMap<String, FieldValueV3>,
+        patch: Option<()>,
+        expires_at: Option<u64>,
+    }
+
+    let data = BTreeMap::from([
+        ("f".to_string(), FieldValueV3::Float(0.5)),
+        ("s".to_string(), FieldValueV3::Str("x".into())),
+    ]);
+    let records = vec![RecordV3 { id: "a".into(), seqno: 1, is_tombstone: false, data, patch: None, expires_at: Some(9) }];
+    let payload = bincode::serialize(&records).unwrap();
+
+    let mut header = PageHeader::new("a".into(), "a".into(), 1, 1);
+    header.version = 3;
+    header.checksum = PageHeader::compute_checksum(&payload);
+    let mut bytes = bincode::serialize(&header).unwrap();
+    bytes.extend(&payload);
+
+    let expected = Record::from_pairs("a", 1, vec![("f", FieldValue::try_from(0.5).unwrap()), ("s", "x".into())]).with_expiry(9);
+    assert_eq!(read_page(&bytes).unwrap().records, vec![expected]);
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use ordered_float::NotNan;
+
+use crate::storage::record::FieldValue;
+
+/// One field-level change inside a `Patch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatchOp {
+  /// Set the field, replacing any previous value.
+  Set(String, FieldValue),
+  /// Remove the field if present.
+  Remove(String),
+  /// Add a numeric delta (`Int`, `UInt` or `Float`) to the field.
+  Increment(String, FieldValue),
+}
+
+/// Partial update of a record, applied on top of the previous version (merge-operator style).
+///
+/// Increment rules:
+/// - a missing or `Null` field starts from the delta itself
+/// - `Int`/`UInt` arithmetic saturates instead of overflowing
+/// - an `Int`/`UInt` field incremented by a `Float` becomes a `Float`
+/// - a non-numeric field is left unchanged
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Patch {
+  ops: Vec<PatchOp>,
+}
+
+impl Patch {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set(mut self, field: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+    self.ops.push(PatchOp::Set(field.into(), value.into()));
+    self
+  }
+
+  pub fn remove(mut self, field: impl Into<String>) -> Self {
+    self.ops.push(PatchOp::Remove(field.into()));
+    self
+  }
+
+  pub fn increment(mut self, field: impl Into<String>, delta: impl Into<FieldValue>) -> Self {
+    self.ops.push(PatchOp::Increment(field.into(), delta.into()));
+    self
+  }
+
+  pub fn ops(&self) -> &[PatchOp] {
+    &self.ops
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.ops.is_empty()
+  }
+
+  /// Check that every increment carries a numeric delta.
+  pub fn validate(&self) -> Result<(), String> {
+    for op in &self.ops {
+      if let PatchOp::Increment(field, delta) = op
+        && !matches!(delta, FieldValue::Int(_) | FieldValue::UInt(_) | FieldValue::Float(_)) {
+        return Err(format!("increment of field {:?} needs a numeric delta, got {:?}", field, delta));
+      }
+    }
+    Ok(())
+  }
+
+  /// Apply the ops in order to `data`.
+  pub fn apply(&self, data: &mut BTreeMap<String, FieldValue>) {
+    for op in &self.ops {
+      match op {
+        PatchOp::Set(field, value) => {
+          data.insert(field.clone(), value.clone());
+        }
+        PatchOp::Remove(field) => {
+          data.remove(field);
+        }
+        PatchOp::Increment(field, delta) => {
+          let current = data.get(field).cloned().unwrap_or(FieldValue::Null);
+          if let Some(next) = add(&current, delta) {
+            data.insert(field.clone(), next);
+          }
+        }
+      }
+    }
+  }
+}
+
+fn add(current: &FieldValue, delta: &FieldValue) -> Option<FieldValue> {
+  use FieldValue::*;
+
+  let float = |a: f64, b: f64| NotNan::new(a + b).ok().map(Float);
+
+  match (current, delta) {
+    (Null, Int(_) | UInt(_) | Float(_)) => Some(delta.clone()),
+    (Int(a), Int(b)) => Some(Int(a.saturating_add(*b))),
+    (Int(a), UInt(b)) => Some(Int(a.saturating_add(i64::try_from(*b).unwrap_or(i64::MAX)))),
+    (UInt(a), UInt(b)) => Some(UInt(a.saturating_add(*b))),
+    (UInt(a), Int(b)) => Some(UInt(a.saturating_add_signed(*b))),
+    (Int(a), Float(b)) => float(*a as f64, b.into_inner()),
+    (UInt(a), Float(b)) => float(*a as f64, b.into_inner()),
+    (Float(a), Int(b)) => float(a.into_inner(), *b as f64),
+    (Float(a), UInt(b)) => float(a.into_inner(), *b as f64),
+    (Float(a), Float(b)) => float(a.into_inner(), b.into_inner()),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn data(pairs: Vec<(&str, FieldValue)>) -> BTreeMap<String, FieldValue> {
+    pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+  }
+
+  #[test]
+  fn patch_sets_removes_and_increments() {
+    let mut d = data(vec![("name", "a".into()), ("n", FieldValue::Int(1)), ("tmp", FieldValue::Bool(true))]);
+
+    Patch::new()
+      .set("name", "b")
+      .remove("tmp")
+      .increment("n", 5i64)
+      .increment("hits", 1u64)
+      .apply(&mut d);
+
+    assert_eq!(d, data(vec![("name", "b".into()), ("n", FieldValue::Int(6)), ("hits", FieldValue::UInt(1))]));
+  }
+
+  #[test]
+  fn increment_saturates_and_widens() {
+    let mut d = data(vec![("i", FieldValue::Int(i64::MAX)), ("u", FieldValue::UInt(1)), ("f", FieldValue::Int(1))]);
+
+    Patch::new()
+      .increment("i", 1i64)
+      .increment("u", -5i64)
+      .increment("f", FieldValue::try_from(0.5).unwrap())
+      .apply(&mut d);
+
+    assert_eq!(d["i"], FieldValue::Int(i64::MAX));
+    assert_eq!(d["u"], FieldValue::UInt(0));
+    assert_eq!(d["f"], FieldValue::try_from(1.5).unwrap());
+  }
+
+  #[test]
+  fn increment_leaves_non_numeric_fields_alone() {
+    let mut d = data(vec![("s", "x".into())]);
+    Patch::new().increment("s", 1i64).apply(&mut d);
+    assert_eq!(d["s"], FieldValue::Str("x".into()));
+
+    assert!(Patch::new().increment("s", "nope").validate().is_err());
+  }
+}
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use ordered_float::NotNan;
 use std::convert::TryFrom;
 
+use crate::storage::patch::Patch;
+
 #[derive(Debug, Clone)]
 pub struct FloatConversionError(pub &'static str);
 
@@ -14,6 +17,9 @@ impl std::fmt::Display for FloatConversionError {
 
 impl std::error::Error for FloatConversionError {}
 
+/// A field value.
+/// New variants only ever go at the end: pages and WAL entries store the variant index,
+/// so older data keeps decoding as the same values.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FieldValue {
   Null,
@@ -22,6 +28,110 @@ pub enum FieldValue {
   UInt(u64),
   Float(NotNan<f64>),
   Str(String),
+  Bytes(Vec<u8>),
+  /// Milliseconds since the Unix epoch, negative before it.
+  Timestamp(i64),
+  List(Vec<FieldValue>),
+  Map(BTreeMap<String, FieldValue>),
+}
+
+impl FieldValue {
+  /// Rank of the value's kind in the total order: Null, Bool, numbers, Str, Bytes, Timestamp,
+  /// List, Map. Int, UInt and Float are one kind and compare with each other.
+  pub fn kind_rank(&self) -> u8 {
+    match self {
+      FieldValue::Null => 0,
+      FieldValue::Bool(_) => 1,
+      FieldValue::Int(_) | FieldValue::UInt(_) | FieldValue::Float(_) => 2,
+      FieldValue::Str(_) => 3,
+      FieldValue::Bytes(_) => 4,
+      FieldValue::Timestamp(_) => 5,
+      FieldValue::List(_) => 6,
+      FieldValue::Map(_) => 7,
+    }
+  }
+
+  /// The current time as a `Timestamp`.
+  pub fn now() -> Self {
+    FieldValue::from(std::time::SystemTime::now())
+  }
+
+  pub fn is_number(&self) -> bool {
+    matches!(self, FieldValue::Int(_) | FieldValue::UInt(_) | FieldValue::Float(_))
+  }
+}
+
+/// Values of every type are totally ordered, so they can be sorted and range-compared:
+/// - Kinds come in the order Null < Bool < numbers < Str < Bytes < Timestamp < List < Map.
+/// - Int, UInt and Float compare by exact numeric value: `Int(-1) < UInt(0) < Float(0.5) < Int(1)`.
+///   Large integers are not rounded through f64: `UInt(u64::MAX) < Float(2^64)`.
+/// - Numerically equal values of different variants order as Int < UInt < Float, so that only
+///   equal values compare `Equal`: `Int(1) < UInt(1) < Float(1.0)`.
+/// - `Float(-0.0)` equals `Float(0.0)`; NaN can't be stored.
+/// - Strings and bytes compare bytewise, which for UTF-8 is code point order.
+/// - Lists compare element by element, a prefix first; maps compare as lists of
+///   (key, value) pairs in key order.
+impl Ord for FieldValue {
+  fn cmp(&self, other: &Self) -> Ordering {
+    use FieldValue::*;
+    self.kind_rank().cmp(&other.kind_rank()).then_with(|| match (self, other) {
+      (Bool(a), Bool(b)) => a.cmp(b),
+      (Str(a), Str(b)) => a.cmp(b),
+      (Bytes(a), Bytes(b)) => a.cmp(b),
+      (Timestamp(a), Timestamp(b)) => a.cmp(b),
+      (List(a), List(b)) => a.cmp(b),
+      (Map(a), Map(b)) => a.cmp(b),
+      (Int(_) | UInt(_) | Float(_), Int(_) | UInt(_) | Float(_)) => {
+        compare_numbers(self, other).then_with(|| number_rank(self).cmp(&number_rank(other)))
+      }
+      _ => Ordering::Equal,
+    })
+  }
+}
+
+impl PartialOrd for FieldValue {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+fn number_rank(value: &FieldValue) -> u8 {
+  match value {
+    FieldValue::Int(_) => 0,
+    FieldValue::UInt(_) => 1,
+    _ => 2,
+  }
+}
+
+/// Exact numeric comparison of two number values.
+fn compare_numbers(a: &FieldValue, b: &FieldValue) -> Ordering {
+  match (a, b) {
+    (FieldValue::Float(a), FieldValue::Float(b)) => a.cmp(b),
+    (FieldValue::Float(a), b) => compare_int_float(as_int(b), a.into_inner()).reverse(),
+    (a, FieldValue::Float(b)) => compare_int_float(as_int(a), b.into_inner()),
+    (a, b) => as_int(a).cmp(&as_int(b)),
+  }
+}
+
+fn as_int(value: &FieldValue) -> i128 {
+  match value {
+    FieldValue::Int(i) => *i as i128,
+    FieldValue::UInt(u) => *u as i128,
+    _ => 0,
+  }
+}
+
+fn compare_int_float(i: i128, f: f64) -> Ordering {
+  // Every i64 and u64 is well inside ±2^127, where truncating `f` to i128 is exact
+  const TWO_POW_127: f64 = 170141183460469231731687303715884105728.0;
+  if f >= TWO_POW_127 {
+    return Ordering::Less;
+  }
+  if f < -TWO_POW_127 {
+    return Ordering::Greater;
+  }
+  let whole = f.trunc();
+  i.cmp(&(whole as i128)).then_with(|| 0.0.partial_cmp(&(f - whole)).unwrap_or(Ordering::Equal))
 }
 
 impl From<&str> for FieldValue {
@@ -48,7 +158,36 @@ impl From<u64> for FieldValue {
   }
 }
 
-/// Convenience (panics on NaN). Use only in tests or internal helpers.
+impl From<Vec<u8>> for FieldValue {
+  fn from(b: Vec<u8>) -> Self {
+    FieldValue::Bytes(b)
+  }
+}
+
+impl From<Vec<FieldValue>> for FieldValue {
+  fn from(l: Vec<FieldValue>) -> Self {
+    FieldValue::List(l)
+  }
+}
+
+impl From<BTreeMap<String, FieldValue>> for FieldValue {
+  fn from(m: BTreeMap<String, FieldValue>) -> Self {
+    FieldValue::Map(m)
+  }
+}
+
+/// A `Timestamp`, truncated to the millisecond.
+impl From<std::time::SystemTime> for FieldValue {
+  fn from(t: std::time::SystemTime) -> Self {
+    let millis = match t.duration_since(std::time::UNIX_EPOCH) {
+      Ok(after) => i64::try_from(after.as_millis()).unwrap_or(i64::MAX),
+      Err(before) => i64::try_from(before.duration().as_millis()).map(|m| -m).unwrap_or(i64::MIN),
+    };
+    FieldValue::Timestamp(millis)
+  }
+}
+
+// Convenience (panics on NaN). Use only in tests or internal helpers.
 // impl From<f64> for FieldValue {
 //   fn from(v: f64) -> Self {
 //     FieldValue::Float(NotNan::new(v).expect("float cannot be NaN"))
@@ -67,12 +206,16 @@ impl TryFrom<f64> for FieldValue {
 
 /// Core record type for storage layer.
 /// `id` is the primary key (string for flexibility), `seqno` is the global sequence number.
+/// A record with `patch` set is a partial update: it only means something on top of older versions.
+/// A record with `expires_at` set (unix millis) reads as deleted from that moment on.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Record {
   pub id: String,
   pub seqno: u64,
   pub is_tombstone: bool,
   pub data: BTreeMap<String, FieldValue>,
+  pub patch: Option<Patch>,
+  pub expires_at: Option<u64>,
 }
 
 impl Record {
@@ -83,6 +226,8 @@ impl Record {
       seqno,
       is_tombstone: false,
       data,
+      patch: None,
+      expires_at: None,
     }
   }
 
@@ -93,6 +238,20 @@ impl Record {
       seqno,
       is_tombstone: true,
       data: BTreeMap::new(),
+      patch: None,
+      expires_at: None,
+    }
+  }
+
+  /// Create a partial update, resolved against older versions at read and compaction time.
+  pub fn new_patch(id: impl Into<String>, seqno: u64, patch: Patch) -> Self {
+    Self {
+      id: id.into(),
+      seqno,
+      is_tombstone: false,
+      data: BTreeMap::new(),
+      patch: Some(patch),
+      expires_at: None,
     }
   }
 
@@ -112,4 +271,55 @@ impl Record {
   pub fn is_tombstone(&self) -> bool {
     self.is_tombstone
   }
+
+  pub fn is_patch(&self) -> bool {
+    self.patch.is_some()
+  }
+
+  /// Set the expiry time, in unix millis.
+  pub fn with_expiry(mut self, expires_at: u64) -> Self {
+    self.expires_at = Some(expires_at);
+    self
+  }
+
+  pub fn is_expired(&self, now: u64) -> bool {
+    self.expires_at.is_some_and(|t| t <= now)
+  }
+
+  /// Resolve the live value of a key from its versions, newest first.
+  /// Patches are stacked until a full value or tombstone is reached; patches with no base
+  /// apply to an empty record. A base that has expired by `now` counts as a tombstone, and a
+  /// live base passes its expiry on to the patched result.
+  /// Returns `None` if the key is deleted, expired or has no versions.
+  pub fn resolve<'a, I>(versions: I, now: u64) -> Option<Record>
+  where
+    I: IntoIterator<Item = &'a Record>,
+  {
+    let mut patches: Vec<&Record> = Vec::new();
+    let mut base: Option<&Record> = None;
+
+    for rec in versions {
+      if rec.is_patch() {
+        patches.push(rec);
+      } else {
+        base = Some(rec);
+        break;
+      }
+    }
+
+    let base = base.filter(|b| !b.is_tombstone && !b.is_expired(now));
+    let Some(newest) = patches.first() else {
+      return base.cloned();
+    };
+
+    let mut data = base.map(|b| b.data.clone()).unwrap_or_default();
+    for rec in patches.iter().rev() {
+      if let Some(patch) = &rec.patch {
+        patch.apply(&mut data);
+      }
+    }
+    let mut resolved = Record::new(newest.id.clone(), newest.seqno, data);
+    resolved.expires_at = base.and_then(|b| b.expires_at);
+    Some(resolved)
+  }
 }
\ No newline at end of file
@@ -0,0 +1,625 @@
+//! Serde support: any `Serialize` type converts to record fields and back.
+//!
+//! Rust values map to `FieldValue`s the way most self-describing formats map them:
+//! - bools, integers, floats, chars and strings become Bool, Int, UInt, Float and Str;
+//!   signed integers are always Int and unsigned ones UInt
+//! - `None`, `()` and unit structs become Null, and `Some(v)` becomes `v`
+//! - sequences and tuples become List, byte buffers Bytes
+//! - structs and maps with string keys become Map; the fields of a record are a Map at the top
+//! - enums are externally tagged: a unit variant is its name as a Str, any other variant a
+//!   Map from its name to its content
+//!
+//! Floats must not be NaN, map keys must be strings, and 128-bit integers must fit in 64 bits.
+//! Deserializing is lenient the same way serde's formats are: an Int field reads into any
+//! integer type it fits, a Timestamp reads as its millis.
+
+use std::collections::BTreeMap;
+
+use serde::de::value::StringDeserializer;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::ser::{self, Serialize};
+
+use crate::error::{Error, Result};
+use crate::storage::record::FieldValue;
+
+/// Convert `value` to the fields of a record. It must serialize as a struct or a map.
+pub fn to_data<T: Serialize + ?Sized>(value: &T) -> Result<BTreeMap<String, FieldValue>> {
+  match to_value(value)? {
+    FieldValue::Map(fields) => Ok(fields),
+    other => Err(Error::Serde(format!(
+      "only structs and maps can be stored as records, got {:?}",
+      other
+    ))),
+  }
+}
+
+/// Read the fields of a record as a `T`.
+pub fn from_data<T: DeserializeOwned>(data: BTreeMap<String, FieldValue>) -> Result<T> {
+  from_value(FieldValue::Map(data))
+}
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<FieldValue> {
+  value.serialize(ValueSerializer)
+}
+
+pub fn from_value<T: DeserializeOwned>(value: FieldValue) -> Result<T> {
+  T::deserialize(ValueDeserializer(value))
+}
+
+impl ser::Error for Error {
+  fn custom<M: std::fmt::Display>(msg: M) -> Self {
+    Error::Serde(msg.to_string())
+  }
+}
+
+impl de::Error for Error {
+  fn custom<M: std::fmt::Display>(msg: M) -> Self {
+    Error::Serde(msg.to_string())
+  }
+}
+
+/// Wrap the content of an enum variant in its externally tagged form.
+fn tagged(variant: Option<&'static str>, value: FieldValue) -> FieldValue {
+  match variant {
+    Some(name) => FieldValue::Map(BTreeMap::from([(name.to_string(), value)])),
+    None => value,
+  }
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+  type SerializeSeq = SeqSerializer;
+  type SerializeTuple = SeqSerializer;
+  type SerializeTupleStruct = SeqSerializer;
+  type SerializeTupleVariant = SeqSerializer;
+  type SerializeMap = MapSerializer;
+  type SerializeStruct = MapSerializer;
+  type SerializeStructVariant = MapSerializer;
+
+  fn serialize_bool(self, v: bool) -> Result<FieldValue> {
+    Ok(FieldValue::Bool(v))
+  }
+
+  fn serialize_i8(self, v: i8) -> Result<FieldValue> {
+    self.serialize_i64(v as i64)
+  }
+
+  fn serialize_i16(self, v: i16) -> Result<FieldValue> {
+    self.serialize_i64(v as i64)
+  }
+
+  fn serialize_i32(self, v: i32) -> Result<FieldValue> {
+    self.serialize_i64(v as i64)
+  }
+
+  fn serialize_i64(self, v: i64) -> Result<FieldValue> {
+    Ok(FieldValue::Int(v))
+  }
+
+  fn serialize_i128(self, v: i128) -> Result<FieldValue> {
+    i64::try_from(v)
+      .map(FieldValue::Int)
+      .map_err(|_| Error::Serde(format!("{} does not fit in an Int", v)))
+  }
+
+  fn serialize_u8(self, v: u8) -> Result<FieldValue> {
+    self.serialize_u64(v as u64)
+  }
+
+  fn serialize_u16(self, v: u16) -> Result<FieldValue> {
+    self.serialize_u64(v as u64)
+  }
+
+  fn serialize_u32(self, v: u32) -> Result<FieldValue> {
+    self.serialize_u64(v as u64)
+  }
+
+  fn serialize_u64(self, v: u64) -> Result<FieldValue> {
+    Ok(FieldValue::UInt(v))
+  }
+
+  fn serialize_u128(self, v: u128) -> Result<FieldValue> {
+    u64::try_from(v)
+      .map(FieldValue::UInt)
+      .map_err(|_| Error::Serde(format!("{} does not fit in a UInt", v)))
+  }
+
+  fn serialize_f32(self, v: f32) -> Result<FieldValue> {
+    self.serialize_f64(v as f64)
+  }
+
+  fn serialize_f64(self, v: f64) -> Result<FieldValue> {
+    FieldValue::try_from(v).map_err(|e| Error::Serde(e.to_string()))
+  }
+
+  fn serialize_char(self, v: char) -> Result<FieldValue> {
+    Ok(FieldValue::Str(v.to_string()))
+  }
+
+  fn serialize_str(self, v: &str) -> Result<FieldValue> {
+    Ok(FieldValue::Str(v.to_string()))
+  }
+
+  fn serialize_bytes(self, v: &[u8]) -> Result<FieldValue> {
+    Ok(FieldValue::Bytes(v.to_vec()))
+  }
+
+  fn serialize_none(self) -> Result<FieldValue> {
+    Ok(FieldValue::Null)
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<FieldValue> {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<FieldValue> {
+    Ok(FieldValue::Null)
+  }
+
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<FieldValue> {
+    Ok(FieldValue::Null)
+  }
+
+  fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<FieldValue> {
+    Ok(FieldValue::Str(variant.to_string()))
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<FieldValue> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<FieldValue> {
+    Ok(tagged(Some(variant), value.serialize(self)?))
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+    Ok(SeqSerializer::new(None, len))
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+    Ok(SeqSerializer::new(None, Some(len)))
+  }
+
+  fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+    Ok(SeqSerializer::new(None, Some(len)))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> Result<SeqSerializer> {
+    Ok(SeqSerializer::new(Some(variant), Some(len)))
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer> {
+    Ok(MapSerializer::new(None))
+  }
+
+  fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapSerializer> {
+    Ok(MapSerializer::new(None))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+    _len: usize,
+  ) -> Result<MapSerializer> {
+    Ok(MapSerializer::new(Some(variant)))
+  }
+}
+
+struct SeqSerializer {
+  variant: Option<&'static str>,
+  items: Vec<FieldValue>,
+}
+
+impl SeqSerializer {
+  fn new(variant: Option<&'static str>, len: Option<usize>) -> Self {
+    Self {
+      variant,
+      items: Vec::with_capacity(len.unwrap_or(0)),
+    }
+  }
+
+  fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+    self.items.push(value.serialize(ValueSerializer)?);
+    Ok(())
+  }
+
+  fn finish(self) -> Result<FieldValue> {
+    Ok(tagged(self.variant, FieldValue::List(self.items)))
+  }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+struct MapSerializer {
+  variant: Option<&'static str>,
+  fields: BTreeMap<String, FieldValue>,
+  next_key: Option<String>,
+}
+
+impl MapSerializer {
+  fn new(variant: Option<&'static str>) -> Self {
+    Self {
+      variant,
+      fields: BTreeMap::new(),
+      next_key: None,
+    }
+  }
+
+  fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+    let value = value.serialize(ValueSerializer)?;
+    self.fields.insert(key, value);
+    Ok(())
+  }
+
+  fn finish(self) -> Result<FieldValue> {
+    Ok(tagged(self.variant, FieldValue::Map(self.fields)))
+  }
+}
+
+impl ser::SerializeMap for MapSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+    match key.serialize(ValueSerializer)? {
+      FieldValue::Str(key) => {
+        self.next_key = Some(key);
+        Ok(())
+      }
+      other => Err(Error::Serde(format!("map keys must be strings, got {:?}", other))),
+    }
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+    let key = self
+      .next_key
+      .take()
+      .ok_or_else(|| Error::Serde("map value without a key".to_string()))?;
+    self.insert(key, value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+    self.insert(key.to_string(), value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+  type Ok = FieldValue;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+    self.insert(key.to_string(), value)
+  }
+
+  fn end(self) -> Result<FieldValue> {
+    self.finish()
+  }
+}
+
+struct ValueDeserializer(FieldValue);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+  type Error = Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+    match self.0 {
+      FieldValue::Null => visitor.visit_unit(),
+      FieldValue::Bool(b) => visitor.visit_bool(b),
+      FieldValue::Int(i) => visitor.visit_i64(i),
+      FieldValue::UInt(u) => visitor.visit_u64(u),
+      FieldValue::Float(f) => visitor.visit_f64(f.into_inner()),
+      FieldValue::Str(s) => visitor.visit_string(s),
+      FieldValue::Bytes(b) => visitor.visit_byte_buf(b),
+      FieldValue::Timestamp(t) => visitor.visit_i64(t),
+      FieldValue::List(items) => visitor.visit_seq(SeqDeserializer(items.into_iter())),
+      FieldValue::Map(fields) => visitor.visit_map(MapDeserializer {
+        fields: fields.into_iter(),
+        value: None,
+      }),
+    }
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+    match self.0 {
+      FieldValue::Null => visitor.visit_none(),
+      value => visitor.visit_some(ValueDeserializer(value)),
+    }
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  /// Bytes also read as a sequence, so they can fill a `Vec<u8>`.
+  fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+    match self.0 {
+      FieldValue::Bytes(b) => visitor.visit_seq(SeqDeserializer(
+        b.into_iter().map(|byte| FieldValue::UInt(byte as u64)).collect::<Vec<_>>().into_iter(),
+      )),
+      value => ValueDeserializer(value).deserialize_any(visitor),
+    }
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value> {
+    match self.0 {
+      FieldValue::Str(variant) => visitor.visit_enum(EnumDeserializer { variant, value: None }),
+      FieldValue::Map(fields) if fields.len() == 1 => {
+        let (variant, value) = fields.into_iter().next().unwrap();
+        visitor.visit_enum(EnumDeserializer { variant, value: Some(value) })
+      }
+      other => Err(Error::Serde(format!(
+        "expected a variant name or a map of one variant for enum {}, got {:?}",
+        name, other
+      ))),
+    }
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+  }
+}
+
+struct SeqDeserializer(std::vec::IntoIter<FieldValue>);
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+  type Error = Error;
+
+  fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+    self.0.next().map(|value| seed.deserialize(ValueDeserializer(value))).transpose()
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.0.len())
+  }
+}
+
+struct MapDeserializer {
+  fields: std::collections::btree_map::IntoIter<String, FieldValue>,
+  value: Option<FieldValue>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+  type Error = Error;
+
+  fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+    match self.fields.next() {
+      Some((key, value)) => {
+        self.value = Some(value);
+        seed.deserialize(StringDeserializer::<Error>::new(key)).map(Some)
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+    let value = self
+      .value
+      .take()
+      .ok_or_else(|| Error::Serde("map value requested before its key".to_string()))?;
+    seed.deserialize(ValueDeserializer(value))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.fields.len())
+  }
+}
+
+struct EnumDeserializer {
+  variant: String,
+  value: Option<FieldValue>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+  type Error = Error;
+  type Variant = VariantDeserializer;
+
+  fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantDeserializer)> {
+    let variant = seed.deserialize(StringDeserializer::<Error>::new(self.variant))?;
+    Ok((variant, VariantDeserializer(self.value)))
+  }
+}
+
+struct VariantDeserializer(Option<FieldValue>);
+
+impl VariantDeserializer {
+  fn content(self) -> Result<ValueDeserializer> {
+    self
+      .0
+      .map(ValueDeserializer)
+      .ok_or_else(|| Error::Serde("expected an enum variant with content, got a unit variant".to_string()))
+  }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+  type Error = Error;
+
+  fn unit_variant(self) -> Result<()> {
+    match self.0 {
+      None | Some(FieldValue::Null) => Ok(()),
+      Some(other) => Err(Error::Serde(format!("expected a unit variant, got {:?}", other))),
+    }
+  }
+
+  fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+    seed.deserialize(self.content()?)
+  }
+
+  fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+    de::Deserializer::deserialize_seq(self.content()?, visitor)
+  }
+
+  fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+    de::Deserializer::deserialize_map(self.content()?, visitor)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::{Deserialize, Serialize};
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  enum Role {
+    Admin,
+    Guest(u32),
+    Member { since: i64, teams: Vec<String> },
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct User {
+    name: String,
+    age: u8,
+    score: f64,
+    email: Option<String>,
+    roles: Vec<Role>,
+    point: (i32, i32),
+    labels: BTreeMap<String, bool>,
+  }
+
+  fn user() -> User {
+    User {
+      name: "ann".into(),
+      age: 31,
+      score: -2.5,
+      email: None,
+      roles: vec![Role::Admin, Role::Guest(3), Role::Member { since: 7, teams: vec!["db".into()] }],
+      point: (1, -1),
+      labels: BTreeMap::from([("vip".to_string(), true)]),
+    }
+  }
+
+  #[test]
+  fn structs_round_trip_through_fields() {
+    let data = to_data(&user()).unwrap();
+    assert_eq!(data["name"], FieldValue::from("ann"));
+    assert_eq!(data["age"], FieldValue::UInt(31));
+    assert_eq!(data["email"], FieldValue::Null);
+    assert_eq!(data["point"], FieldValue::List(vec![FieldValue::Int(1), FieldValue::Int(-1)]));
+    let FieldValue::List(roles) = &data["roles"] else { panic!("roles should be a list") };
+    assert_eq!(roles[0], FieldValue::from("Admin"));
+    assert_eq!(roles[1], FieldValue::Map(BTreeMap::from([("Guest".to_string(), FieldValue::UInt(3))])));
+
+    assert_eq!(from_data::<User>(data).unwrap(), user());
+  }
+
+  #[test]
+  fn lenient_reads_fill_compatible_types() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Loose {
+      small: u8,
+      wide: f64,
+      raw: Vec<u8>,
+      missing: Option<i64>,
+    }
+    let data = BTreeMap::from([
+      ("small".to_string(), FieldValue::Int(7)),
+      ("wide".to_string(), FieldValue::Int(2)),
+      ("raw".to_string(), FieldValue::Bytes(vec![1, 2])),
+    ]);
+    assert_eq!(from_data::<Loose>(data).unwrap(), Loose { small: 7, wide: 2.0, raw: vec![1, 2], missing: None });
+  }
+
+  #[test]
+  fn unsupported_shapes_are_rejected_clearly() {
+    let nan = to_data(&BTreeMap::from([("x", f64::NAN)])).unwrap_err();
+    assert!(matches!(&nan, Error::Serde(reason) if reason.contains("NaN")), "{}", nan);
+
+    assert!(matches!(to_data(&42), Err(Error::Serde(reason)) if reason.contains("structs and maps")));
+    assert!(matches!(to_data(&BTreeMap::from([(1, 2)])), Err(Error::Serde(reason)) if reason.contains("keys must be strings")));
+    assert!(matches!(to_value(&u128::MAX), Err(Error::Serde(_))));
+
+    let wrong = BTreeMap::from([("small".to_string(), FieldValue::Int(-1))]);
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Small {
+      small: u8,
+    }
+    assert!(matches!(from_data::<Small>(wrong), Err(Error::Serde(_))));
+  }
+}
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use std::fs::File;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::SyncMode;
+
+/// Sync state of a `Wal`, shared with writers waiting for their appends to be durable and
+/// with the interval timer, so an fsync never needs whatever lock guards the appends.
+///
+/// Group commit: a writer that needs its append synced either finds an fsync already
+/// covering it, waits for the one in progress, or becomes the leader and fsyncs everything
+/// written so far. Followers that appended in the meantime are covered by that one fsync.
+pub struct SyncGroup {
+  state: Mutex<SyncState>,
+  /// Signalled when an fsync finishes, on a sync mode change and on close.
+  changed: Condvar,
+}
+
+struct SyncState {
+  /// The live log file; replaced when the log is rewritten.
+  file: Arc<File>,
+  sync_mode: SyncMode,
+  /// Appends written so far.
+  written: u64,
+  /// Appends covered by a finished fsync.
+  synced: u64,
+  /// A leader is in fsync.
+  syncing: bool,
+  syncs: u64,
+  last_sync: Instant,
+  closed: bool,
+}
+
+/// An append that may still need an fsync. The append is only as durable as the sync mode
+/// asks once `wait` returns.
+#[must_use = "an append is not durable until `wait` returns"]
+pub struct PendingSync {
+  group: Arc<SyncGroup>,
+  upto: u64,
+  due: bool,
+}
+
+impl PendingSync {
+  /// One wait covering both `self` and `other`, appends of the same log.
+  pub fn and(self, other: PendingSync) -> PendingSync {
+    PendingSync { group: self.group, upto: self.upto.max(other.upto), due: self.due || other.due }
+  }
+
+  /// Sync up to this append if the sync mode asks for it, sharing the fsync with every
+  /// other writer waiting at the same time.
+  pub fn wait(self) -> Result<()> {
+    if self.due {
+      self.group.sync_to(self.upto)?;
+    }
+    Ok(())
+  }
+}
+
+impl SyncGroup {
+  pub(super) fn new(file: Arc<File>, sync_mode: SyncMode) -> Arc<Self> {
+    Arc::new(SyncGroup {
+      state: Mutex::new(SyncState {
+        file,
+        sync_mode,
+        written: 0,
+        synced: 0,
+        syncing: false,
+        syncs: 0,
+        last_sync: Instant::now(),
+        closed: false,
+      }),
+      changed: Condvar::new(),
+    })
+  }
+
+  fn state(&self) -> MutexGuard<'_, SyncState> {
+    self.state.lock().unwrap()
+  }
+
+  pub fn sync_mode(&self) -> SyncMode {
+    self.state().sync_mode
+  }
+
+  pub(super) fn set_sync_mode(&self, sync_mode: SyncMode) {
+    self.state().sync_mode = sync_mode;
+    self.changed.notify_all();
+  }
+
+  /// Number of fsyncs issued on the log so far.
+  pub fn sync_count(&self) -> u64 {
+    self.state().syncs
+  }
+
+  /// Appends written but not yet fsynced.
+  pub fn pending_appends(&self) -> usize {
+    let state = self.state();
+    (state.written - state.synced) as usize
+  }
+
+  /// Count an append whose bytes are already written, and decide whether it needs an fsync.
+  pub(super) fn record_append(self: &Arc<Self>) -> PendingSync {
+    let mut state = self.state();
+    state.written += 1;
+    let pending = state.written - state.synced;
+    let due = match state.sync_mode {
+      SyncMode::Always => true,
+      SyncMode::EveryWrites(n) => pending >= n as u64,
+      SyncMode::Interval(interval) => state.last_sync.elapsed() >= interval,
+      SyncMode::Manual => false,
+    };
+    PendingSync { group: Arc::clone(self), upto: state.written, due }
+  }
+
+  /// A `PendingSync` for when nothing was appended.
+  pub(super) fn nothing_pending(self: &Arc<Self>) -> PendingSync {
+    PendingSync { group: Arc::clone(self), upto: 0, due: false }
+  }
+
+  /// Fsync every append written so far.
+  pub fn sync(&self) -> Result<()> {
+    let written = self.state().written;
+    self.sync_to(written)
+  }
+
+  /// Return once the first `upto` appends are synced: at once if an fsync already covered
+  /// them, after the fsync in progress if it does, or after leading a new one.
+  fn sync_to(&self, upto: u64) -> Result<()> {
+    let mut state = self.state();
+    loop {
+      if state.synced >= upto {
+        return Ok(());
+      }
+      if !state.syncing {
+        break;
+      }
+      state = self.changed.wait(state).unwrap();
+    }
+
+    // Lead: cover everything written by now, not just our own append
+    state.syncing = true;
+    let target = state.written;
+    let file = Arc::clone(&state.file);
+    drop(state);
+
+    let result = file.sync_all();
+
+    let mut state = self.state();
+    state.syncing = false;
+    if result.is_ok() {
+      state.synced = state.synced.max(target);
+      state.syncs += 1;
+      state.last_sync = Instant::now();
+    }
+    self.changed.notify_all();
+    Ok(result?)
+  }
+
+  /// Point at a rewritten log that is already synced in full.
+  pub(super) fn replace_file(&self, file: Arc<File>) {
+    let mut state = self.state();
+    state.file = file;
+    state.synced = state.written;
+    state.last_sync = Instant::now();
+    self.changed.notify_all();
+  }
+
+  pub(super) fn close(&self) {
+    self.state().closed = true;
+    self.changed.notify_all();
+  }
+
+  /// Start a thread that syncs the log once `SyncMode::Interval` has passed since the last
+  /// sync with appends pending, so an idle log doesn't stay unsynced. It sleeps through
+  /// other modes and exits on `close`.
+  pub(super) fn start_timer(self: &Arc<Self>) -> Result<JoinHandle<()>> {
+    let this = Arc::clone(self);
+    let handle = std::thread::Builder::new()
+      .name("shunyadb-wal-sync".to_string())
+      .spawn(move || this.run_timer())?;
+    Ok(handle)
+  }
+
+  fn run_timer(&self) {
+    let mut state = self.state();
+    while !state.closed {
+      let SyncMode::Interval(interval) = state.sync_mode else {
+        state = self.changed.wait(state).unwrap();
+        continue;
+      };
+      let elapsed = state.last_sync.elapsed();
+      if elapsed < interval || state.written == state.synced {
+        let wait = if elapsed < interval { interval - elapsed } else { interval };
+        state = self.changed.wait_timeout(state, wait.max(MIN_TIMER_WAIT)).unwrap().0;
+        continue;
+      }
+      drop(state);
+      let failed = self.sync().is_err();
+      state = self.state();
+      if failed {
+        // Retry after another interval; the error reaches the next writer that syncs
+        state = self.changed.wait_timeout(state, interval.max(MIN_TIMER_WAIT)).unwrap().0;
+      }
+    }
+  }
+}
+
+/// Shortest timer sleep, so a tiny interval can't spin.
+const MIN_TIMER_WAIT: Duration = Duration::from_millis(1);
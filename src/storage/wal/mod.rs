@@ -1,3 +1,4 @@
+pub mod group;
 pub mod replay;
 
 use anyhow::{Context, Result};
@@ -5,14 +6,28 @@ use serde::{Deserialize, Serialize};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
+use crate::error::Error;
+use crate::storage::legacy::{WalEntryV1, WalEntryV2};
 use crate::storage::record::Record;
+use group::{PendingSync, SyncGroup};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum WalOp {
   Insert,
   Update,
   Delete,
+  /// Marks the start of an atomic batch; `seqno` is the first seqno of the batch.
+  BatchBegin,
+  /// Marks the end of an atomic batch; `seqno` is the last seqno of the batch.
+  BatchCommit,
+  /// `table` was created at `seqno`; earlier entries for that name belong to a dropped table.
+  CreateTable,
+  /// `table` was dropped at `seqno`.
+  DropTable,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -36,47 +51,175 @@ impl WalEntry {
   }
 }
 
+/// When appended WAL data is forced to stable storage.
+///
+/// Every mode writes the entry to the OS before the append returns, so a process crash never
+/// loses an acknowledged write. The modes differ in what survives a power loss or kernel crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+  /// fsync every append before it is acknowledged. An acknowledged write is always durable.
+  /// Appends that wait at the same time share one fsync (see `SyncGroup`).
+  #[default]
+  Always,
+  /// fsync once every `n` appends; up to `n - 1` acknowledged appends may be lost.
+  EveryWrites(usize),
+  /// fsync once `interval` has elapsed since the last sync: on the next append, or from a
+  /// timer thread if the log sits idle. Up to `interval` of acknowledged appends may be lost.
+  Interval(Duration),
+  /// fsync only when `Wal::sync` is called (and when the log is dropped).
+  Manual,
+}
+
 pub struct Wal {
-  file: File,
+  file: Arc<File>,
   path: String,
+  sync: Arc<SyncGroup>,
+  /// Runs while the log has been in `SyncMode::Interval`.
+  timer: Option<JoinHandle<()>>,
 }
 
 impl Wal {
   pub fn open(path: impl AsRef<Path>) -> Result<Self> {
-    let file = OpenOptions::new()
+    Self::open_with(path, SyncMode::default())
+  }
+
+  pub fn open_with(path: impl AsRef<Path>, sync_mode: SyncMode) -> Result<Self> {
+    let file = Arc::new(Self::open_file(path.as_ref())?);
+    let mut wal = Wal {
+      sync: SyncGroup::new(Arc::clone(&file), SyncMode::Manual),
+      file,
+      path: path.as_ref().to_string_lossy().to_string(),
+      timer: None,
+    };
+    wal.set_sync_mode(sync_mode);
+    Ok(wal)
+  }
+
+  /// Open an existing log for reading only, e.g. next to a live writer. Appending fails.
+  pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self> {
+    let file = Arc::new(File::open(path.as_ref()).with_context(|| "failed to open WAL file")?);
+    Ok(Wal {
+      sync: SyncGroup::new(Arc::clone(&file), SyncMode::Manual),
+      file,
+      path: path.as_ref().to_string_lossy().to_string(),
+      timer: None,
+    })
+  }
+
+  fn open_file(path: &Path) -> Result<File> {
+    OpenOptions::new()
       .read(true)
       .append(true)
       .create(true)
-      .open(&path)
-      .with_context(|| format!("failed to open WAL file"))?;
-    Ok(Wal { file, path: path.as_ref().to_string_lossy().to_string() })
+      .open(path)
+      .with_context(|| "failed to open WAL file")
+  }
+
+  pub fn path(&self) -> &Path {
+    Path::new(&self.path)
+  }
+
+  pub fn sync_mode(&self) -> SyncMode {
+    self.sync.sync_mode()
+  }
+
+  pub fn set_sync_mode(&mut self, sync_mode: SyncMode) {
+    self.sync.set_sync_mode(sync_mode);
+    if matches!(sync_mode, SyncMode::Interval(_)) && self.timer.is_none() {
+      // Without a timer the log still syncs on appends, so a failed spawn is not fatal
+      self.timer = self.sync.start_timer().ok();
+    }
+  }
+
+  /// The log's sync state, for syncing and waiting on appends without holding the `Wal`.
+  pub fn sync_group(&self) -> Arc<SyncGroup> {
+    Arc::clone(&self.sync)
+  }
+
+  /// Number of fsyncs issued on the log so far.
+  pub fn sync_count(&self) -> u64 {
+    self.sync.sync_count()
+  }
+
+  /// Appends written but not yet fsynced.
+  pub fn pending_appends(&self) -> usize {
+    self.sync.pending_appends()
+  }
+
+  /// Force every pending append to stable storage with a single fsync.
+  pub fn sync(&self) -> Result<()> {
+    self.sync.sync()
   }
 
   /// Append a WAL entry to the log. as [len][payload][len]
   pub fn append(&mut self, entry: &WalEntry) -> Result<()> {
-    let payload = bincode::serialize(entry)?;
+    self.append_nowait(entry)?.wait()
+  }
 
-    let len = payload.len() as u64;
-    let len_bytes = len.to_le_bytes();
+  /// Append a group of entries as one atomic unit:
+  /// [BatchBegin][entries..][BatchCommit], written with a single write and fsync.
+  /// Replay ignores the entries unless the commit marker made it to disk.
+  pub fn append_batch(&mut self, entries: &[WalEntry]) -> Result<()> {
+    self.append_batch_nowait(entries)?.wait()
+  }
 
-    self.file.write_all(&len_bytes)?;
-    self.file.write_all(&payload)?;
-    self.file.write_all(&len_bytes)?;
+  /// Like `append`, but leaves the fsync the sync mode asks for to `PendingSync::wait`, so
+  /// the caller can release its own locks first and share the fsync with other writers.
+  pub fn append_nowait(&mut self, entry: &WalEntry) -> Result<PendingSync> {
+    let mut buf = Vec::new();
+    encode_frame(entry, &mut buf)?;
+    self.write_unsynced(&buf)
+  }
 
-    // durability gurantee
-    self.file.flush()?;
-    self.file.sync_all()?;
+  /// Like `append_batch`, but leaves the fsync to `PendingSync::wait`.
+  pub fn append_batch_nowait(&mut self, entries: &[WalEntry]) -> Result<PendingSync> {
+    let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+      return Ok(self.sync.nothing_pending());
+    };
+
+    let mut buf = Vec::new();
+    encode_frame(&WalEntry::new(WalOp::BatchBegin, String::new(), String::new(), first.seqno, None), &mut buf)?;
+    for entry in entries {
+      encode_frame(entry, &mut buf)?;
+    }
+    encode_frame(&WalEntry::new(WalOp::BatchCommit, String::new(), String::new(), last.seqno, None), &mut buf)?;
+
+    self.write_unsynced(&buf)
+  }
 
+  /// Write `bytes` to the OS; the fsync is up to the returned `PendingSync`.
+  fn write_unsynced(&mut self, bytes: &[u8]) -> Result<PendingSync> {
+    let mut file = &*self.file;
+    file.write_all(bytes)?;
+    file.flush()?;
+    Ok(self.sync.record_append())
+  }
+
+  /// Drop everything after the first `len` bytes (torn tail or uncommitted batch).
+  pub fn truncate(&mut self, len: u64) -> Result<()> {
+    self.file.set_len(len)?;
+    self.file.sync_all()?;
     Ok(())
   }
 
+  pub fn len_bytes(&self) -> Result<u64> {
+    Ok(self.file.metadata()?.len())
+  }
+
   /// Replay all WAL entries in order
   pub fn read_all(&mut self) -> Result<Vec<WalEntry>> {
+    Ok(self.read_frames()?.into_iter().map(|(entry, _)| entry).collect())
+  }
+
+  /// Replay all WAL entries in order, each paired with the byte offset where its frame ends.
+  pub fn read_frames(&mut self) -> Result<Vec<(WalEntry, u64)>> {
     let mut entries = Vec::new();
-    self.file.seek(SeekFrom::Start(0))?;
+    let mut offset = 0u64;
+    let mut file = &*self.file;
+    file.seek(SeekFrom::Start(0))?;
     loop {
       let mut len_buf = [0u8; 8];
-      let n = self.file.read(&mut len_buf)?;
+      let n = file.read(&mut len_buf)?;
       if n == 0 {
         break; // EOF
       }
@@ -88,7 +231,7 @@ impl Wal {
 
       // read payload
       let mut payload = vec![0u8; len as usize];
-      let n = self.file.read(&mut payload)?;
+      let n = file.read(&mut payload)?;
       if n < len as usize {
         // corrupted/truncated WAL
         break;
@@ -96,7 +239,7 @@ impl Wal {
 
       // read trailing len
       let mut len_buf2 = [0u8; 8];
-      let n = self.file.read(&mut len_buf2)?;
+      let n = file.read(&mut len_buf2)?;
       if n < 8 {
         break;
       }
@@ -106,8 +249,10 @@ impl Wal {
       }
 
       // decode entry
-      let entry: WalEntry = bincode::deserialize(&payload)?;
-      entries.push(entry);
+      let entry = decode_entry(&payload)
+        .map_err(|e| Error::corruption(self.path(), format!("undecodable entry at offset {}: {}", offset, e)))?;
+      offset += 16 + len;
+      entries.push((entry, offset));
     }
     Ok(entries)
   }
@@ -149,7 +294,7 @@ impl Wal {
       }
 
       // Deserialize entry
-      let entry: WalEntry = bincode::deserialize(&payload)?;
+      let entry = decode_entry(&payload)?;
 
       if entry.seqno > checkpoint {
         new.write_all(&len_buf)?;
@@ -171,9 +316,46 @@ impl Wal {
       }
     }
 
+    // Keep appending to the new log, not the unlinked one
+    self.file = Arc::new(Self::open_file(wal_path)?);
+    self.sync.replace_file(Arc::clone(&self.file));
+
     Ok(())
   }
 }
 
+impl Drop for Wal {
+  fn drop(&mut self) {
+    self.sync.close();
+    if let Some(timer) = self.timer.take() {
+      let _ = timer.join();
+    }
+    // Clean shutdown never leaves acknowledged writes unsynced, whatever the mode
+    let _ = self.sync();
+  }
+}
+
+/// Decode a frame payload, falling back to the layouts written before TTL and partial updates.
+/// Each layout only appends to the record, so an older payload is too short for a newer one.
+fn decode_entry(payload: &[u8]) -> Result<WalEntry> {
+  match bincode::deserialize::<WalEntry>(payload) {
+    Ok(entry) => Ok(entry),
+    Err(e) => bincode::deserialize::<WalEntryV2>(payload)
+      .map(WalEntry::from)
+      .or_else(|_| bincode::deserialize::<WalEntryV1>(payload).map(WalEntry::from))
+      .map_err(|_| e.into()),
+  }
+}
+
+fn encode_frame(entry: &WalEntry, buf: &mut Vec<u8>) -> Result<()> {
+  let payload = bincode::serialize(entry)?;
+  let len_bytes = (payload.len() as u64).to_le_bytes();
+
+  buf.extend_from_slice(&len_bytes);
+  buf.extend_from_slice(&payload);
+  buf.extend_from_slice(&len_bytes);
+  Ok(())
+}
+
 #[cfg(test)]
 mod tests;
\ No newline at end of file
@@ -1,14 +1,46 @@
 use anyhow::{Result, bail};
-use crate::storage::wal::{Wal, WalEntry};
+use crate::error::Error;
+use crate::storage::wal::{Wal, WalEntry, WalOp};
 
 #[derive(Debug)]
 pub struct ReplayResult {
+  /// Committed data entries in log order (batch markers removed).
   pub entries: Vec<WalEntry>,
   pub max_seqno: u64,
+  /// Length of the log up to the last committed entry.
+  /// Anything past it is a torn frame or a batch whose commit marker never reached disk.
+  pub committed_len: u64,
 }
 
 impl ReplayResult {
-  pub fn new(entries: Vec<WalEntry>) -> Result<Self> {
+  /// Build a replay from `(entry, frame end offset)` pairs as returned by `Wal::read_frames`.
+  pub fn new(frames: Vec<(WalEntry, u64)>) -> Result<Self> {
+    let mut entries = Vec::new();
+    let mut committed_len = 0;
+    let mut pending: Option<Vec<WalEntry>> = None;
+
+    for (entry, end) in frames {
+      match entry.op {
+        WalOp::BatchBegin => {
+          // A begin without a commit before it means the earlier batch never completed
+          pending = Some(Vec::new());
+        }
+        WalOp::BatchCommit => {
+          if let Some(batch) = pending.take() {
+            entries.extend(batch);
+          }
+          committed_len = end;
+        }
+        _ => match pending.as_mut() {
+          Some(batch) => batch.push(entry),
+          None => {
+            entries.push(entry);
+            committed_len = end;
+          }
+        },
+      }
+    }
+
     let mut max_seqno = 0;
 
     // Validate monotonic seqno order:
@@ -21,12 +53,12 @@ impl ReplayResult {
       }
       max_seqno = e.seqno;
     }
-    Ok(Self { entries, max_seqno })
+    Ok(Self { entries, max_seqno, committed_len })
   }
 
   /// Full replay function used during DB startup
   pub fn replay_wal(wal: &mut Wal) -> Result<ReplayResult> {
-    let entries = wal.read_all()?;
-    ReplayResult::new(entries)
+    let frames = wal.read_frames()?;
+    ReplayResult::new(frames).map_err(|e| Error::corruption(wal.path(), e).into())
   } 
 }
@@ -1,23 +1,24 @@
 use super::*;
 use tempfile::tempdir;
 use crate::storage::record::Record;
-use crate::engine::seqno;
+use crate::engine::seqno::SeqnoAllocator;
 use super::replay::ReplayResult;
 
 #[test]
 fn wal_append_and_read() {
+    let seqno = SeqnoAllocator::default();
     let dir = tempdir().unwrap();
     let wal_path = dir.path().join("wal.log");
 
     let mut wal = Wal::open(&wal_path).unwrap();
 
-    let seq1 = seqno::allocate();
+    let seq1 = seqno.allocate();
     let rec1 = Record::from_pairs("1", seq1, vec![("name", "alice")]);
     let e1 = WalEntry::new(WalOp::Insert, "users", "1", seq1, Some(rec1.clone()));
 
     wal.append(&e1).unwrap();
 
-    let seq2 = seqno::allocate();
+    let seq2 = seqno.allocate();
     let rec2 = Record::new_tombstone("1", seq2);
     let e2 = WalEntry::new(WalOp::Delete, "users", "1", seq2, Some(rec2.clone()));
 
@@ -53,17 +54,18 @@ fn wal_handles_truncated_data_gracefully() {
 
 #[test]
 fn replay_detects_seqno_ordering() {
+    let seqno = SeqnoAllocator::default();
     let dir = tempdir().unwrap();
     let wal_path = dir.path().join("wal.log");
 
     let mut wal = Wal::open(&wal_path).unwrap();
 
     // Create correct order
-    let seq1 = seqno::allocate();
+    let seq1 = seqno.allocate();
     let e1 = WalEntry::new(WalOp::Insert, "tbl", "id1", seq1, None);
     wal.append(&e1).unwrap();
 
-    let seq2 = seqno::allocate();
+    let seq2 = seqno.allocate();
     let e2 = WalEntry::new(WalOp::Insert, "tbl", "id2", seq2, None);
     wal.append(&e2).unwrap();
 
@@ -107,4 +109,121 @@ fn replay_rejects_out_of_order_seqno() {
     let replay = ReplayResult::replay_wal(&mut wal);
 
     assert!(replay.is_err()); // correctly rejects bad WAL order
-}
\ No newline at end of file
+}
+
+#[test]
+fn replay_applies_committed_batch() {
+    let seqno = SeqnoAllocator::default();
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let mut wal = Wal::open(&wal_path).unwrap();
+
+    let first = seqno.allocate_range(2);
+    let batch = vec![
+        WalEntry::new(WalOp::Insert, "", "a", first, Some(Record::from_pairs("a", first, vec![("v", 1i64)]))),
+        WalEntry::new(WalOp::Delete, "", "b", first + 1, Some(Record::new_tombstone("b", first + 1))),
+    ];
+    wal.append_batch(&batch).unwrap();
+
+    let mut wal = Wal::open(&wal_path).unwrap();
+    let replay = ReplayResult::replay_wal(&mut wal).unwrap();
+
+    assert_eq!(replay.entries, batch);
+    assert_eq!(replay.max_seqno, first + 1);
+    assert_eq!(replay.committed_len, wal.len_bytes().unwrap());
+}
+
+#[test]
+fn replay_discards_batch_without_commit() {
+    let seqno = SeqnoAllocator::default();
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let mut wal = Wal::open(&wal_path).unwrap();
+
+    let seq = seqno.allocate();
+    let single = WalEntry::new(WalOp::Insert, "", "a", seq, None);
+    wal.append(&single).unwrap();
+    let committed_len = wal.len_bytes().unwrap();
+
+    let first = seqno.allocate_range(3);
+    let batch: Vec<_> = (0..3)
+        .map(|i| WalEntry::new(WalOp::Insert, "", format!("k{}", i), first + i, None))
+        .collect();
+    wal.append_batch(&batch).unwrap();
+
+    // Tear off the commit marker as if the process died mid-write
+    let len = wal.len_bytes().unwrap();
+    wal.truncate(len - 4).unwrap();
+
+    let mut wal = Wal::open(&wal_path).unwrap();
+    let replay = ReplayResult::replay_wal(&mut wal).unwrap();
+
+    assert_eq!(replay.entries, vec![single]);
+    assert_eq!(replay.committed_len, committed_len);
+}
+
+#[test]
+fn reads_entries_written_by_older_formats() {
+    use crate::storage::legacy::{RecordV1, RecordV2, WalEntryV1, WalEntryV2};
+    use crate::storage::patch::Patch;
+    use std::io::Write;
+
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+
+    let v1 = WalEntryV1 {
+        seqno: 3,
+        op: WalOp::Insert,
+        table: String::new(),
+        record_id: "k".into(),
+        record: Some(RecordV1 { id: "k".into(), seqno: 3, is_tombstone: false, data: Default::default() }),
+    };
+    let v2 = WalEntryV2 {
+        seqno: 4,
+        op: WalOp::Update,
+        table: String::new(),
+        record_id: "k".into(),
+        record: Some(RecordV2 {
+            id: "k".into(),
+            seqno: 4,
+            is_tombstone: false,
+            data: Default::default(),
+            patch: Some(Patch::new().set("a", 1i64)),
+        }),
+    };
+    {
+        let mut f = File::create(&wal_path).unwrap();
+        for payload in [bincode::serialize(&v1).unwrap(), bincode::serialize(&v2).unwrap()] {
+            let len = (payload.len() as u64).to_le_bytes();
+            f.write_all(&len).unwrap();
+            f.write_all(&payload).unwrap();
+            f.write_all(&len).unwrap();
+        }
+    }
+
+    let mut wal = Wal::open(&wal_path).unwrap();
+    let entries = wal.read_all().unwrap();
+    assert_eq!(entries, vec![
+        WalEntry::new(WalOp::Insert, "", "k", 3, Some(Record::new("k", 3, Default::default()))),
+        WalEntry::new(WalOp::Update, "", "k", 4, Some(Record::new_patch("k", 4, Patch::new().set("a", 1i64)))),
+    ]);
+}
+
+#[test]
+fn appends_waiting_together_share_one_fsync() {
+    let dir = tempdir().unwrap();
+    let mut wal = Wal::open(dir.path().join("wal.log")).unwrap();
+
+    let first = wal.append_nowait(&WalEntry::new(WalOp::Insert, "", "a", 1, None)).unwrap();
+    let second = wal.append_nowait(&WalEntry::new(WalOp::Insert, "", "b", 2, None)).unwrap();
+    assert_eq!(wal.pending_appends(), 2);
+
+    // The leader syncs everything written so far, so the follower finds itself covered
+    second.wait().unwrap();
+    first.wait().unwrap();
+    assert_eq!(wal.sync_count(), 1);
+    assert_eq!(wal.pending_appends(), 0);
+
+    wal.append(&WalEntry::new(WalOp::Insert, "", "c", 3, None)).unwrap();
+    assert_eq!(wal.sync_count(), 2, "a lone append under Always syncs before returning");
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map.insert("pad".to_string(), FieldValue::Str("x".repeat(64)));
+    map
+}
+
+fn background_options() -> EngineOptions {
+    EngineOptions::new()
+        .background_worker(true)
+        .memtable_flush_bytes(2 * 1024)
+        .l0_pages_limit(2)
+        .l0_page_bytes(1024)
+        .l1_page_bytes(2 * 1024)
+}
+
+/// Poll until `cond` holds; the worker runs on its own schedule.
+fn eventually(mut cond: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if cond() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn worker_flushes_and_compacts_off_the_write_path() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open_with(dir.path(), background_options())?;
+
+    for i in 0..300 {
+        engine.put(format!("k{:03}", i), value(i))?;
+    }
+
+    assert!(eventually(|| engine.metrics().flushes > 0 && engine.metrics().compactions > 0));
+    assert!(eventually(|| !engine.meta().level[1].is_empty()));
+    for i in 0..300 {
+        let record = engine.get(&format!("k{:03}", i), u64::MAX)?.expect("record survives maintenance");
+        assert_eq!(record.data["v"], FieldValue::Int(i));
+    }
+    engine.close()?;
+    Ok(())
+}
+
+#[test]
+fn writes_stall_at_the_l0_hard_limit_and_then_resume() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open_with(dir.path(), background_options().l0_stop_writes_pages(2))?;
+
+    let writers: Vec<_> = (0..4)
+        .map(|w| {
+            let engine = engine.clone();
+            thread::spawn(move || {
+                for i in 0..200 {
+                    engine.put(format!("w{}-{:03}", w, i), value(i)).unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    assert_eq!(engine.scan(.., u64::MAX)?.count(), 800);
+    engine.close()?;
+    Ok(())
+}
+
+#[test]
+fn inline_mode_is_the_default_and_stays_deterministic() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let options = background_options().background_worker(false);
+    assert!(!EngineOptions::default().background_worker);
+    let engine = Engine::open_with(dir.path(), options)?;
+
+    // The write that finds the memtable over its limit flushes it before returning
+    let mut i = 0;
+    while engine.metrics().flushes == 0 {
+        engine.put(format!("k{:03}", i), value(i))?;
+        i += 1;
+    }
+    assert!(engine.meta().level[0].len() + engine.meta().level[1].len() > 0);
+    Ok(())
+}
+
+#[test]
+fn close_stops_the_worker_and_writes_fall_back_to_inline() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    {
+        let engine = Engine::open_with(dir.path(), background_options())?;
+        for i in 0..100 {
+            engine.put(format!("k{:03}", i), value(i))?;
+        }
+        engine.close()?;
+        engine.close()?;
+
+        let flushes = engine.metrics().flushes;
+        for i in 100..200 {
+            engine.put(format!("k{:03}", i), value(i))?;
+        }
+        assert!(engine.metrics().flushes > flushes);
+    }
+
+    // Dropping the last handle without `close` stops the worker as well
+    {
+        let engine = Engine::open_with(dir.path(), background_options())?;
+        engine.put("late".to_string(), value(0))?;
+    }
+
+    let engine = Engine::open(dir.path())?;
+    assert_eq!(engine.scan(.., u64::MAX)?.count(), 201);
+    Ok(())
+}
+
+#[test]
+fn writes_continue_while_memtables_are_flushed() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    {
+        let engine = Engine::open_with(dir.path(), background_options())?;
+        for i in 0..1000 {
+            let id = format!("k{:04}", i);
+            engine.put(id.clone(), value(i))?;
+            // Whether it sits in the active memtable, a queued one or pages, it reads back at once
+            assert!(engine.get(&id, u64::MAX)?.is_some(), "{} vanished during a flush", id);
+        }
+        assert!(eventually(|| engine.metrics().flushes > 0));
+    }
+
+    // Memtables still queued when the engine was dropped are recovered from the WAL
+    let engine = Engine::open(dir.path())?;
+    assert_eq!(engine.scan(.., u64::MAX)?.count(), 1000);
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::batch::WriteBatch;
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::table::DEFAULT_TABLE;
+use shunyadb::storage::patch::Patch;
+use shunyadb::storage::record::FieldValue;
+use shunyadb::storage::wal::WalOp;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map
+}
+
+#[test]
+fn changes_since_lists_committed_changes_in_order() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.put("a".to_string(), value(1))?;
+    let after_first = engine.last_seqno();
+    engine.update("a".to_string(), Patch::new().set("w", 2i64))?;
+    engine.create_table("events")?;
+    let mut batch = WriteBatch::new();
+    batch.put_in("events", "e1", value(3)).delete("a");
+    engine.write(&batch)?;
+
+    let changes: Vec<_> = engine.changes_since(0)?.map(|c| (c.seqno, c.op, c.table, c.record_id)).collect();
+    assert_eq!(changes, vec![
+        (1, WalOp::Insert, DEFAULT_TABLE.to_string(), "a".to_string()),
+        (2, WalOp::Update, DEFAULT_TABLE.to_string(), "a".to_string()),
+        (3, WalOp::CreateTable, "events".to_string(), String::new()),
+        (4, WalOp::Insert, "events".to_string(), "e1".to_string()),
+        (5, WalOp::Delete, DEFAULT_TABLE.to_string(), "a".to_string()),
+    ]);
+
+    let later: Vec<_> = engine.changes_since(after_first)?.map(|c| c.seqno).collect();
+    assert_eq!(later, vec![2, 3, 4, 5]);
+    assert_eq!(engine.changes_since(5)?.count(), 0);
+    Ok(())
+}
+
+#[test]
+fn subscription_replays_the_backlog_then_follows_new_commits() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.put("a".to_string(), value(1))?;
+    engine.put("b".to_string(), value(2))?;
+
+    let mut subscription = engine.subscribe(1)?;
+    assert_eq!(subscription.try_next().map(|c| c.record_id), Some("b".to_string()));
+    assert!(subscription.try_next().is_none());
+
+    let writer = {
+        let engine = engine.clone();
+        thread::spawn(move || {
+            for i in 0..10 {
+                engine.put(format!("k{}", i), value(i)).unwrap();
+            }
+        })
+    };
+    let seqnos: Vec<_> = subscription.by_ref().take(10).map(|c| c.seqno).collect();
+    writer.join().unwrap();
+    assert_eq!(seqnos, (3..13).collect::<Vec<_>>());
+    assert!(subscription.next_timeout(Duration::from_millis(10)).is_none());
+
+    // The feed ends with the engine
+    drop(engine);
+    assert!(subscription.next().is_none());
+    Ok(())
+}
+
+#[test]
+fn checkpoints_wait_for_consumers_to_acknowledge() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    {
+        let engine = Engine::open(dir.path())?;
+        engine.register_consumer("search", 0)?;
+        for i in 0..10 {
+            engine.put(format!("k{}", i), value(i))?;
+        }
+        engine.flush()?;
+        engine.maybe_checkpoint_wal()?;
+        // Everything is in pages, but the consumer has not seen any of it
+        assert_eq!(engine.changes_since(0)?.count(), 10);
+
+        engine.ack_changes("search", 6)?;
+        engine.maybe_checkpoint_wal()?;
+        assert!(matches!(engine.changes_since(0), Err(Error::ChangesTruncated { since: 0, checkpoint: 6 })));
+        assert_eq!(engine.changes_since(6)?.map(|c| c.seqno).collect::<Vec<_>>(), vec![7, 8, 9, 10]);
+    }
+
+    // The consumer and the checkpoint survive a restart
+    let engine = Engine::open(dir.path())?;
+    assert_eq!(engine.consumers(), BTreeMap::from([("search".to_string(), 6)]));
+    assert!(engine.changes_since(5).is_err());
+    assert_eq!(engine.changes_since(6)?.count(), 4);
+    assert!(matches!(engine.register_consumer("late", 2), Err(Error::ChangesTruncated { .. })));
+
+    // Without consumers, a checkpoint drops every flushed change
+    engine.unregister_consumer("search")?;
+    engine.maybe_checkpoint_wal()?;
+    assert!(engine.changes_since(6).is_err());
+    assert_eq!(engine.changes_since(10)?.count(), 0);
+    assert!(matches!(engine.ack_changes("search", 10), Err(Error::NotFound(_))));
+    Ok(())
+}
+
+#[test]
+fn polling_alongside_writes_and_checkpoints_misses_nothing() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.register_consumer("poller", 0)?;
+
+    let writer = {
+        let engine = engine.clone();
+        thread::spawn(move || -> anyhow::Result<()> {
+            for i in 0..300 {
+                engine.put(format!("k{}", i), value(i))?;
+                if i % 50 == 49 {
+                    engine.flush()?;
+                    engine.maybe_checkpoint_wal()?;
+                }
+            }
+            Ok(())
+        })
+    };
+
+    let mut last = 0;
+    while last < 300 {
+        for change in engine.changes_since(last)? {
+            assert_eq!(change.seqno, last + 1, "changes must come without gaps");
+            last = change.seqno;
+        }
+        engine.ack_changes("poller", last)?;
+    }
+    writer.join().unwrap()?;
+    Ok(())
+}
@@ -1,7 +1,6 @@
 use std::collections::BTreeMap;
 use tempfile::tempdir;
 use shunyadb::{engine::engine::Engine, storage::record::FieldValue};
-use shunyadb::engine::seqno::current;
 
 fn value(i: usize) -> BTreeMap<String, FieldValue> {
     let mut map = BTreeMap::new();
@@ -19,7 +18,7 @@ fn compaction_and_wal_checkpoint_are_safe() -> anyhow::Result<()> {
     let data_dir = dir.path();
 
     // 2️⃣ Create engine
-    let mut engine = Engine::open(data_dir)?;
+    let engine = Engine::open(data_dir)?;
 
     // 3️⃣ Write enough entries to:
     //    - trigger flush
@@ -36,18 +35,18 @@ fn compaction_and_wal_checkpoint_are_safe() -> anyhow::Result<()> {
     engine.maybe_compact()?;
 
     // Capture checkpoint (for sanity, not logic)
-    let checkpoint = engine.meta.checkpoint_seqno;
+    let checkpoint = engine.meta().checkpoint_seqno;
 
     // 4️⃣ Drop engine (simulate crash)
     drop(engine);
 
     // 5️⃣ Restart engine (recovery path)
-    let mut engine = Engine::open(data_dir)?;
-    let snapshot = current();
+    let engine = Engine::open(data_dir)?;
+    let snapshot = engine.last_seqno();
 
     // 6️⃣ Verify ALL data is present
     for i in 0..total {
-      let record = engine.get(&i.to_string(), snapshot);
+      let record = engine.get(&i.to_string(), snapshot)?;
       assert!(
           record.is_some(),
           "missing record after restart for key={}",
@@ -70,7 +69,7 @@ fn compaction_and_wal_checkpoint_are_safe() -> anyhow::Result<()> {
 
     // 7️⃣ Sanity check: checkpoint should not regress
     assert!(
-        engine.meta.checkpoint_seqno >= checkpoint,
+        engine.meta().checkpoint_seqno >= checkpoint,
         "checkpoint_seqno regressed after restart"
     );
 
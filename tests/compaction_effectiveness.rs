@@ -6,7 +6,7 @@ use shunyadb::storage::record::FieldValue;
 #[test]
 fn compaction_reduces_l0_pages() -> anyhow::Result<()> {
     let dir = tempdir()?;
-    let mut engine = Engine::open(dir.path())?;
+    let engine = Engine::open(dir.path())?;
 
     // Force many flushes
     for i in 0..5000 {
@@ -18,8 +18,8 @@ fn compaction_reduces_l0_pages() -> anyhow::Result<()> {
     engine.flush()?;
     engine.maybe_compact()?;
 
-    let l0_pages = engine.meta.level[0].len();
-    let l1_pages = engine.meta.level[1].len();
+    let l0_pages = engine.meta().level[0].len();
+    let l1_pages = engine.meta().level[1].len();
 
     assert!(
         l0_pages < 8,
@@ -32,7 +32,7 @@ fn compaction_reduces_l0_pages() -> anyhow::Result<()> {
     );
 
     assert!(
-        engine.metrics.compactions > 0,
+        engine.metrics().compactions > 0,
         "compaction metric should increment"
     );
 
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::Error;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map
+}
+
+fn int(engine: &Engine, id: &str) -> i64 {
+    match engine.get(id, u64::MAX).unwrap().map(|r| r.data["v"].clone()) {
+        Some(FieldValue::Int(i)) => i,
+        _ => 0,
+    }
+}
+
+#[test]
+fn engine_is_a_shareable_handle() {
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<Engine>();
+}
+
+#[test]
+fn readers_run_alongside_a_writer() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let options = EngineOptions::new()
+        .memtable_flush_bytes(2 * 1024)
+        .l0_pages_limit(2)
+        .page_cache_capacity(4);
+    let engine = Engine::open_with(dir.path(), options)?;
+    let done = Arc::new(AtomicBool::new(false));
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let engine = engine.clone();
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    // A snapshot sees the same set however many flushes and compactions run meanwhile
+                    let snap = engine.snapshot();
+                    let first: Vec<_> = engine.scan(.., snap.seqno()).unwrap().map(|r| r.id).collect();
+                    let second: Vec<_> = engine.scan(.., snap.seqno()).unwrap().map(|r| r.id).collect();
+                    assert_eq!(first, second);
+                }
+            })
+        })
+        .collect();
+
+    let writer = {
+        let engine = engine.clone();
+        thread::spawn(move || {
+            for i in 0..500 {
+                engine.put(format!("k{i:04}"), value(i)).unwrap();
+            }
+        })
+    };
+
+    writer.join().unwrap();
+    done.store(true, Ordering::SeqCst);
+    for r in readers {
+        r.join().unwrap();
+    }
+
+    assert!(engine.metrics().flushes > 0);
+    assert_eq!(engine.scan(.., u64::MAX)?.count(), 500);
+    Ok(())
+}
+
+#[test]
+fn conditional_writes_race_to_a_single_winner() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+
+    let winners: usize = (0..8)
+        .map(|i| {
+            let engine = engine.clone();
+            thread::spawn(move || engine.put_if_absent("lock".to_string(), value(i)).is_ok())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|h| h.join().unwrap() as usize)
+        .sum();
+
+    assert_eq!(winners, 1);
+    Ok(())
+}
+
+#[test]
+fn transactions_from_many_threads_do_not_lose_updates() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.put("counter".to_string(), value(0))?;
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let engine = engine.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    loop {
+                        let mut txn = engine.begin_transaction();
+                        let current = match txn.get(&engine, "counter").unwrap().map(|r| r.data["v"].clone()) {
+                            Some(FieldValue::Int(i)) => i,
+                            _ => 0,
+                        };
+                        txn.put("counter", value(current + 1));
+                        match txn.commit(&engine) {
+                            Ok(()) => break,
+                            Err(Error::Conflict(_)) => continue,
+                            Err(e) => panic!("{e}"),
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(int(&engine, "counter"), 100);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::engine::{ConditionFailed, Engine};
+use shunyadb::storage::record::FieldValue;
+
+fn value(v: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(v));
+    map
+}
+
+fn condition_failed(err: Error) -> ConditionFailed {
+    match err {
+        Error::ConditionFailed(condition) => condition,
+        other => panic!("expected a failed condition, got {}", other),
+    }
+}
+
+#[test]
+fn put_if_absent_only_inserts_once() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    let seqno = engine.put_if_absent("k".to_string(), value(1)).unwrap();
+    assert_eq!(engine.get("k", u64::MAX).unwrap().unwrap().seqno, seqno);
+
+    let err = condition_failed(engine.put_if_absent("k".to_string(), value(2)).unwrap_err());
+    assert_eq!(err.expected, None);
+    assert_eq!(err.actual, Some(seqno));
+    assert_eq!(engine.get("k", u64::MAX).unwrap().unwrap().data, value(1));
+
+    // A deleted key counts as absent again
+    engine.delete("k".to_string()).unwrap();
+    engine.put_if_absent("k".to_string(), value(3)).unwrap();
+    assert_eq!(engine.get("k", u64::MAX).unwrap().unwrap().data, value(3));
+}
+
+#[test]
+fn put_if_version_is_compare_and_swap() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.put("k".to_string(), value(1)).unwrap();
+    let v1 = engine.get("k", u64::MAX).unwrap().unwrap().seqno;
+    engine.flush().unwrap();
+
+    let v2 = engine.put_if_version("k".to_string(), v1, value(2)).unwrap();
+    assert!(v2 > v1);
+
+    // A second writer still holding v1 loses
+    let err = condition_failed(engine.put_if_version("k".to_string(), v1, value(3)).unwrap_err());
+    assert_eq!(err.expected, Some(v1));
+    assert_eq!(err.actual, Some(v2));
+    assert_eq!(engine.get("k", u64::MAX).unwrap().unwrap().data, value(2));
+
+    // Missing key never matches a version
+    assert!(engine.put_if_version("missing".to_string(), v1, value(1)).is_err());
+}
+
+#[test]
+fn delete_if_version_checks_current_seqno() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.put("k".to_string(), value(1)).unwrap();
+    let v1 = engine.get("k", u64::MAX).unwrap().unwrap().seqno;
+    engine.put("k".to_string(), value(2)).unwrap();
+    let v2 = engine.get("k", u64::MAX).unwrap().unwrap().seqno;
+
+    assert!(engine.delete_if_version("k".to_string(), v1).is_err());
+    assert!(engine.get("k", u64::MAX).unwrap().is_some());
+
+    engine.delete_if_version("k".to_string(), v2).unwrap();
+    assert!(engine.get("k", u64::MAX).unwrap().is_none());
+
+    let err = condition_failed(engine.delete_if_version("k".to_string(), v2).unwrap_err());
+    assert_eq!(err.actual, None);
+}
@@ -5,7 +5,7 @@ use shunyadb::storage::record::FieldValue;
 #[test]
 fn rough_put_throughput() -> anyhow::Result<()> {
     let dir = tempfile::tempdir()?;
-    let mut engine = Engine::open(dir.path())?;
+    let engine = Engine::open(dir.path())?;
 
     let start = std::time::Instant::now();
 
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map
+}
+
+#[test]
+fn second_open_fails_fast_until_the_first_is_dropped() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    let clone = engine.clone();
+
+    let err = Engine::open(dir.path()).err().expect("directory is locked");
+    assert!(matches!(err, Error::DirectoryLocked(_)), "unexpected error: {}", err);
+
+    // Clones share the lock; it is released with the last handle
+    drop(engine);
+    assert!(Engine::open(dir.path()).is_err());
+    drop(clone);
+    Engine::open(dir.path())?;
+    Ok(())
+}
+
+#[test]
+fn read_only_open_runs_next_to_a_writer() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    for i in 0..50 {
+        engine.put(format!("k{:02}", i), value(i))?;
+    }
+    engine.flush()?;
+    engine.create_table("events")?;
+    engine.put_in("events", "e1".to_string(), value(1))?;
+    engine.delete("k00".to_string())?;
+
+    let wal_before = std::fs::read(dir.path().join("wal.log"))?;
+    let meta_before = std::fs::read(dir.path().join("meta.json"))?;
+
+    let inspector = Engine::open_read_only(dir.path())?;
+    assert!(inspector.is_read_only());
+    // Flushed pages and WAL-only writes are both visible
+    assert_eq!(inspector.scan(.., u64::MAX)?.count(), 49);
+    assert!(inspector.get("k00", u64::MAX)?.is_none());
+    assert!(inspector.get_in("events", "e1", u64::MAX)?.is_some());
+
+    // Nothing it can do writes to the directory
+    let writes = [
+        inspector.put("x".to_string(), value(0)),
+        inspector.delete("k01".to_string()),
+        inspector.create_table("other"),
+        inspector.flush(),
+        inspector.maybe_compact(),
+    ];
+    for result in writes {
+        assert!(matches!(result, Err(Error::ReadOnly)));
+    }
+    drop(inspector);
+    assert_eq!(std::fs::read(dir.path().join("wal.log"))?, wal_before);
+    assert_eq!(std::fs::read(dir.path().join("meta.json"))?, meta_before);
+
+    // The writer carried on regardless
+    engine.put("after".to_string(), value(0))?;
+    Ok(())
+}
+
+#[test]
+fn read_only_reads_survive_the_writer_compacting_its_pages() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open_with(dir.path(), EngineOptions::new().l0_pages_limit(1))?;
+    for i in 0..10 {
+        engine.put(format!("k{}", i), value(i))?;
+    }
+    engine.flush()?;
+    let first_page = dir.path().join(&engine.meta().level[0][0].file_name);
+
+    let inspector = Engine::open_read_only(dir.path())?;
+
+    // The writer moves on and compacts away the page the inspector was opened on
+    for i in 10..20 {
+        engine.put(format!("k{}", i), value(i))?;
+    }
+    engine.put("k3".to_string(), value(33))?;
+    engine.flush()?;
+    engine.maybe_compact()?;
+    assert!(!first_page.exists());
+
+    let k3 = inspector.get("k3", u64::MAX)?.expect("k3 is still there");
+    assert_eq!(k3.data["v"], FieldValue::Int(33), "the reloaded view shows the writer's latest state");
+    assert_eq!(inspector.scan(.., u64::MAX)?.count(), 20);
+    assert_eq!(inspector.scan_prefix("k1", u64::MAX)?.count(), 11);
+    Ok(())
+}
+
+#[test]
+fn read_only_open_requires_an_existing_database() {
+    let dir = tempdir().unwrap();
+    assert!(Engine::open_read_only(dir.path().join("missing")).is_err());
+    assert!(!dir.path().join("missing").exists());
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: usize) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("value".to_string(), FieldValue::Str(format!("value_{}", i)));
+    map
+}
+
+#[test]
+fn open_with_applies_tuning() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let options = EngineOptions::new()
+        .memtable_flush_bytes(4 * 1024)
+        .max_records_per_page(16)
+        .l0_pages_limit(2)
+        .l0_page_bytes(1024)
+        .l1_page_bytes(2 * 1024)
+        .page_cache_capacity(1);
+    let engine = Engine::open_with(dir.path(), options)?;
+
+    for i in 0..500 {
+        engine.put(i.to_string(), value(i))?;
+    }
+
+    assert!(engine.metrics().flushes > 5, "small memtable should flush often");
+    assert!(engine.metrics().compactions > 0, "low L0 limit should compact");
+
+    for i in 0..500 {
+        assert_eq!(engine.get(&i.to_string(), u64::MAX)?.unwrap().data, value(i));
+    }
+    assert!(engine.metrics().page_cache_evictions > 0, "single-page cache should evict");
+
+    Ok(())
+}
+
+#[test]
+fn oversized_record_gets_its_own_page() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open_with(dir.path(), EngineOptions::new().l0_page_bytes(256))?;
+
+    let mut big = BTreeMap::new();
+    big.insert("blob".to_string(), FieldValue::Str("x".repeat(4096)));
+    engine.put("big".to_string(), big.clone())?;
+    engine.put("small".to_string(), value(1))?;
+    engine.flush()?;
+
+    assert_eq!(engine.get("big", u64::MAX)?.unwrap().data, big);
+    Ok(())
+}
+
+#[test]
+fn create_if_missing_false_requires_existing_dir() {
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("nope");
+
+    let res = Engine::open_with(&missing, EngineOptions::new().create_if_missing(false));
+    assert!(res.is_err());
+    assert!(!missing.exists());
+
+    assert!(Engine::open_with(&missing, EngineOptions::new()).is_ok());
+    assert!(missing.join("wal.log").exists());
+}
+
+#[test]
+fn create_if_missing_false_rejects_empty_dir() {
+    let dir = tempdir().unwrap();
+    let empty = dir.path().join("empty");
+    std::fs::create_dir(&empty).unwrap();
+
+    let res = Engine::open_with(&empty, EngineOptions::new().create_if_missing(false));
+    assert!(matches!(res, Err(Error::NotFound(_))));
+    assert_eq!(std::fs::read_dir(&empty).unwrap().count(), 0);
+
+    drop(Engine::open_with(&empty, EngineOptions::new()).unwrap());
+    assert!(Engine::open_with(&empty, EngineOptions::new().create_if_missing(false)).is_ok());
+}
+
+#[test]
+fn error_if_exists_rejects_existing_database() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("db");
+
+    {
+        let engine = Engine::open_with(&path, EngineOptions::new().error_if_exists(true)).unwrap();
+        engine.put("a".to_string(), value(1)).unwrap();
+    }
+
+    let res = Engine::open_with(&path, EngineOptions::new().error_if_exists(true));
+    assert!(res.is_err());
+}
+
+#[test]
+fn invalid_options_are_rejected_before_touching_disk() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("db");
+
+    let res = Engine::open_with(&path, EngineOptions::new().page_cache_capacity(0));
+    assert!(res.is_err());
+    assert!(!path.exists());
+}
@@ -21,14 +21,25 @@ fn value(i: usize) -> BTreeMap<String, FieldValue> {
 }
 
 use shunyadb::engine::engine::Engine;
-use shunyadb::engine::seqno::current;
+
+/// Each test gets its own directory holding the 1000 flushed entries, so they can run in parallel.
+fn populated_dir(name: &str) -> &Path {
+    let base = Path::new(name);
+    clean_dir(base);
+    let engine = Engine::open(base).expect("engine open failed");
+    for i in 0..1000 {
+        engine.put(i.to_string(), value(i)).expect("put failed");
+    }
+    engine.flush().expect("flush failed");
+    base
+}
 
 #[test]
 fn persist_1000_entries_to_disk() {
     let base = std::path::Path::new("test_data/shunyadb_engine_test");
     clean_dir(base);
 
-    let mut engine = Engine::open(base).expect("engine open failed");
+    let engine = Engine::open(base).expect("engine open failed");
 
     for i in 0..1000 {
         engine
@@ -36,7 +47,7 @@ fn persist_1000_entries_to_disk() {
             .expect("put failed");
     }
 
-    let snap_before_flush = current();
+    let snap_before_flush = engine.last_seqno();
 
     engine.flush().expect("flush failed");
 
@@ -60,7 +71,7 @@ fn persist_1000_entries_to_disk() {
     // Verify reads still work after flush
     for i in 0..1000 {
         let rec = engine
-            .get(&i.to_string(), snap_before_flush)
+            .get(&i.to_string(), snap_before_flush).unwrap()
             .expect("missing record");
 
         let v = rec.data.get("value").unwrap();
@@ -71,16 +82,16 @@ fn persist_1000_entries_to_disk() {
 
 #[test]
 fn restart_engine_reads_from_disk() {
-    let base = std::path::Path::new("test_data/shunyadb_engine_test");
+    let base = populated_dir("test_data/shunyadb_engine_restart_test");
 
     // Restart (new Engine instance)
-    let mut engine = Engine::open(base).expect("engine reopen failed");
+    let engine = Engine::open(base).expect("engine reopen failed");
 
-    let snapshot = shunyadb::engine::seqno::current();
+    let snapshot = engine.last_seqno();
 
     for i in 0..1000 {
         let rec = engine
-            .get(&i.to_string(), snapshot)
+            .get(&i.to_string(), snapshot).unwrap()
             .expect("record missing after restart");
 
         let v = rec.data.get("value").unwrap();
@@ -91,8 +102,8 @@ fn restart_engine_reads_from_disk() {
 
 #[test]
 fn update_and_delete_after_restart() {
-    let base = std::path::Path::new("test_data/shunyadb_engine_test");
-    let mut engine = Engine::open(base).unwrap();
+    let base = populated_dir("test_data/shunyadb_engine_update_test");
+    let engine = Engine::open(base).unwrap();
 
     // Update half
     for i in 0..500 {
@@ -106,27 +117,28 @@ fn update_and_delete_after_restart() {
         engine.delete(i.to_string()).unwrap();
     }
 
-    let snap = shunyadb::engine::seqno::current();
+    let snap = engine.last_seqno();
     engine.flush().unwrap();
 
     // Restart again
-    let mut engine = Engine::open(base).unwrap();
+    drop(engine);
+    let engine = Engine::open(base).unwrap();
 
     // Updated records
     for i in 0..500 {
-        let rec = engine.get(&i.to_string(), snap).unwrap();
+        let rec = engine.get(&i.to_string(), snap).unwrap().unwrap();
         let v = rec.data.get("value").unwrap();
         assert_eq!(v, &FieldValue::Str(format!("val_{}", i + 10_000)));
     }
 
     // Deleted records
     for i in 500..750 {
-        assert!(engine.get(&i.to_string(), snap).is_none());
+        assert!(engine.get(&i.to_string(), snap).unwrap().is_none());
     }
 
     // Untouched records
     for i in 750..1000 {
-        let rec = engine.get(&i.to_string(), snap).unwrap();
+        let rec = engine.get(&i.to_string(), snap).unwrap().unwrap();
         let v = rec.data.get("value").unwrap();
         assert_eq!(v, &FieldValue::Str(format!("val_{}", i)));
     }
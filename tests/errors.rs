@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map
+}
+
+#[test]
+fn missing_key_is_ok_none() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.put("k".to_string(), value(1))?;
+    engine.flush()?;
+
+    assert!(engine.get("absent", u64::MAX)?.is_none());
+    engine.delete("k".to_string())?;
+    assert!(engine.get("k", u64::MAX)?.is_none());
+    Ok(())
+}
+
+#[test]
+fn corrupted_page_is_an_error_not_a_missing_key() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let page = {
+        let engine = Engine::open(dir.path())?;
+        engine.put("k".to_string(), value(1))?;
+        engine.flush()?;
+        dir.path().join(&engine.meta().level[0][0].file_name)
+    };
+
+    // Flip the last payload byte of the only page
+    let mut bytes = fs::read(&page)?;
+    *bytes.last_mut().unwrap() ^= 0xff;
+    fs::write(&page, bytes)?;
+
+    let engine = Engine::open(dir.path())?;
+    let err = engine.get("k", u64::MAX).expect_err("damaged page must not read as absent");
+    assert!(err.is_corruption(), "unexpected error: {}", err);
+    match err {
+        Error::ChecksumMismatch { file, .. } => assert_eq!(file, page),
+        other => panic!("expected a checksum mismatch, got {}", other),
+    }
+    // Keys outside the damaged page still read normally
+    assert!(engine.get("z", u64::MAX)?.is_none());
+    Ok(())
+}
+
+#[test]
+fn errors_are_typed() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+
+    assert!(matches!(engine.get_in("nope", "k", u64::MAX), Err(Error::NotFound(_))));
+    assert!(matches!(engine.create_table("../escape"), Err(Error::InvalidArgument(_))));
+    assert!(matches!(
+        EngineOptions::new().memtable_flush_bytes(0).validate(),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        Engine::open_with(dir.path().join("missing"), EngineOptions::new().create_if_missing(false)),
+        Err(Error::NotFound(_))
+    ));
+    Ok(())
+}
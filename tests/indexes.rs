@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::batch::WriteBatch;
+use shunyadb::engine::engine::Engine;
+use shunyadb::storage::patch::Patch;
+use shunyadb::storage::record::{FieldValue, Record};
+
+fn person(age: i64, city: &str) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("age".to_string(), FieldValue::Int(age));
+    map.insert("city".to_string(), FieldValue::from(city));
+    map
+}
+
+fn ids(records: Vec<Record>) -> Vec<String> {
+    records.into_iter().map(|r| r.id).collect()
+}
+
+#[test]
+fn find_by_value_and_range() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.create_index("age")?;
+    engine.create_index("city")?;
+    engine.put("ann".to_string(), person(31, "Oslo"))?;
+    engine.put("bob".to_string(), person(25, "Pune"))?;
+    engine.put("cat".to_string(), person(-4, "Oslo"))?;
+    engine.put("dan".to_string(), person(31, "Lima"))?;
+    let mut score = BTreeMap::new();
+    score.insert("age".to_string(), FieldValue::UInt(31));
+    engine.put("eve".to_string(), score)?;
+
+    let snap = engine.last_seqno();
+    assert_eq!(ids(engine.find_by("age", &FieldValue::Int(31), snap)?), vec!["ann", "dan"]);
+    assert_eq!(ids(engine.find_by("age", &FieldValue::UInt(31), snap)?), vec!["eve"]);
+    assert_eq!(ids(engine.find_by("city", &"Oslo".into(), snap)?), vec!["ann", "cat"]);
+
+    // Ranges come back in value order, numbers of every variant together
+    assert_eq!(ids(engine.find_range("age", FieldValue::Int(-10)..FieldValue::Int(31), snap)?), vec!["cat", "bob"]);
+    assert_eq!(ids(engine.find_range("age", FieldValue::Int(0).., snap)?), vec!["bob", "ann", "dan", "eve"]);
+    assert_eq!(ids(engine.find_range("age", FieldValue::UInt(0)..=FieldValue::Int(31), snap)?), vec!["bob", "ann", "dan"]);
+    assert_eq!(ids(engine.find_range("city", FieldValue::from("M").., snap)?), vec!["ann", "cat", "bob"]);
+
+    let heights = [1.5, -0.25, 2.0];
+    engine.create_index("height")?;
+    for (i, h) in heights.iter().enumerate() {
+        let mut map = BTreeMap::new();
+        map.insert("height".to_string(), FieldValue::try_from(*h)?);
+        engine.put(format!("h{}", i), map)?;
+    }
+    let tall = engine.find_range("height", FieldValue::try_from(0.0)?..=FieldValue::try_from(2.0)?, engine.last_seqno())?;
+    assert_eq!(ids(tall), vec!["h0", "h2"]);
+
+    assert!(matches!(engine.find_by("name", &"x".into(), snap), Err(Error::NotFound(_))));
+    assert!(matches!(engine.find_range("age", FieldValue::Int(1)..FieldValue::from("2"), snap), Err(Error::InvalidArgument(_))));
+    Ok(())
+}
+
+#[test]
+fn writes_move_index_entries_and_snapshots_see_the_old_ones() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.create_index("age")?;
+    engine.put("ann".to_string(), person(31, "Oslo"))?;
+    engine.put("bob".to_string(), person(31, "Pune"))?;
+    let before = engine.last_seqno();
+    assert!(matches!(engine.find_by("age", &FieldValue::Int(31), 0), Err(Error::InvalidArgument(_))));
+
+    engine.update("ann".to_string(), Patch::new().set("age", 32i64))?;
+    engine.delete("bob".to_string())?;
+    let now = engine.last_seqno();
+    assert!(engine.find_by("age", &FieldValue::Int(31), now)?.is_empty());
+    assert_eq!(ids(engine.find_by("age", &FieldValue::Int(32), now)?), vec!["ann"]);
+    assert_eq!(ids(engine.find_by("age", &FieldValue::Int(31), before)?), vec!["ann", "bob"]);
+
+    // A batch is indexed as a whole, later writes in it winning
+    let mut batch = WriteBatch::new();
+    batch.put("bob", person(40, "Pune")).put("bob", person(41, "Pune")).delete("ann");
+    engine.write(&batch)?;
+    let now = engine.last_seqno();
+    assert_eq!(ids(engine.find_range("age", FieldValue::Int(0).., now)?), vec!["bob"]);
+    assert!(engine.find_by("age", &FieldValue::Int(40), now)?.is_empty());
+
+    let mut txn = engine.begin_transaction();
+    txn.put("cat", person(41, "Lima"));
+    txn.commit(&engine)?;
+    assert_eq!(ids(engine.find_by("age", &FieldValue::Int(41), engine.last_seqno())?), vec!["bob", "cat"]);
+    Ok(())
+}
+
+#[test]
+fn index_is_built_from_existing_records_and_survives_restart() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    {
+        let engine = Engine::open(dir.path())?;
+        for i in 0..50 {
+            engine.put(format!("k{:02}", i), person(i % 5, "Oslo"))?;
+            if i == 20 {
+                engine.flush()?;
+            }
+        }
+        engine.create_index("age")?;
+        assert!(matches!(engine.create_index("age"), Err(Error::InvalidArgument(_))));
+        assert_eq!(engine.find_by("age", &FieldValue::Int(3), engine.last_seqno())?.len(), 10);
+        engine.put("k03".to_string(), person(4, "Oslo"))?;
+    }
+
+    let engine = Engine::open(dir.path())?;
+    assert_eq!(engine.indexes(), vec!["age".to_string()]);
+    let snap = engine.last_seqno();
+    assert_eq!(engine.find_by("age", &FieldValue::Int(3), snap)?.len(), 9);
+    assert_eq!(engine.find_by("age", &FieldValue::Int(4), snap)?.len(), 11);
+    engine.flush()?;
+    assert_eq!(engine.find_by("age", &FieldValue::Int(4), snap)?.len(), 11);
+    Ok(())
+}
+
+#[test]
+fn index_tables_stay_hidden() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.create_index("age")?;
+    engine.put("ann".to_string(), person(31, "Oslo"))?;
+
+    assert!(engine.list_tables().is_empty());
+    let changes: Vec<_> = engine.changes_since(0)?.map(|c| c.record_id).collect();
+    assert_eq!(changes, vec!["ann".to_string()]);
+
+    engine.drop_index("age")?;
+    assert!(engine.indexes().is_empty());
+    assert!(matches!(engine.find_by("age", &FieldValue::Int(31), engine.last_seqno()), Err(Error::NotFound(_))));
+    assert!(matches!(engine.drop_index("age"), Err(Error::NotFound(_))));
+    assert_eq!(engine.meta().indexes.len(), 0);
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::patch::Patch;
+use shunyadb::storage::record::FieldValue;
+use shunyadb::storage::wal::{Wal, WalOp};
+
+fn profile(name: &str, visits: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("name".to_string(), FieldValue::Str(name.to_string()));
+    map.insert("visits".to_string(), FieldValue::Int(visits));
+    map
+}
+
+#[test]
+fn update_is_logged_as_wal_update() {
+    let dir = tempdir().unwrap();
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.put("u1".to_string(), profile("ann", 1)).unwrap();
+        engine.update("u1".to_string(), Patch::new().increment("visits", 1i64)).unwrap();
+    }
+
+    let mut wal = Wal::open(dir.path().join("wal.log")).unwrap();
+    let ops: Vec<_> = wal.read_all().unwrap().into_iter().map(|e| e.op).collect();
+    assert_eq!(ops.last(), Some(&WalOp::Update));
+}
+
+#[test]
+fn patches_resolve_across_memtable_and_pages() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.put("u1".to_string(), profile("ann", 1)).unwrap();
+    engine.flush().unwrap();
+
+    engine.update("u1".to_string(), Patch::new().increment("visits", 1i64)).unwrap();
+    let after_first = engine.last_seqno();
+    engine.flush().unwrap();
+
+    engine
+        .update("u1".to_string(), Patch::new().increment("visits", 10i64).set("plan", "pro").remove("name"))
+        .unwrap();
+
+    let rec = engine.get("u1", u64::MAX).unwrap().unwrap();
+    let mut expected = BTreeMap::new();
+    expected.insert("visits".to_string(), FieldValue::Int(12));
+    expected.insert("plan".to_string(), FieldValue::Str("pro".to_string()));
+    assert_eq!(rec.data, expected);
+    assert!(rec.patch.is_none());
+
+    // Older snapshots resolve only the patches they can see
+    assert_eq!(engine.get("u1", after_first).unwrap().unwrap().data, profile("ann", 2));
+
+    let scanned: Vec<_> = engine.scan(.., u64::MAX).unwrap().collect();
+    assert_eq!(scanned.len(), 1);
+    assert_eq!(scanned[0].data, expected);
+}
+
+#[test]
+fn compaction_folds_patches_into_values() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open_with(dir.path(), EngineOptions::new().l0_pages_limit(1)).unwrap();
+
+    engine.put("counter".to_string(), profile("c", 0)).unwrap();
+    for _ in 0..5 {
+        engine.update("counter".to_string(), Patch::new().increment("visits", 1i64)).unwrap();
+        engine.flush().unwrap();
+        engine.maybe_compact().unwrap();
+    }
+
+    assert!(engine.meta().level[0].is_empty());
+    let records: usize = engine.meta().level[1].iter().map(|p| p.number_of_records).sum();
+    assert_eq!(records, 1, "compaction should leave one folded version");
+    assert_eq!(engine.get("counter", u64::MAX).unwrap().unwrap().data, profile("c", 5));
+}
+
+#[test]
+fn update_on_missing_or_deleted_key_starts_empty() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.update("new".to_string(), Patch::new().increment("hits", 1u64)).unwrap();
+    assert_eq!(engine.get("new", u64::MAX).unwrap().unwrap().data.get("hits"), Some(&FieldValue::UInt(1)));
+
+    engine.put("old".to_string(), profile("x", 7)).unwrap();
+    engine.delete("old".to_string()).unwrap();
+    engine.update("old".to_string(), Patch::new().set("name", "y")).unwrap();
+
+    let rec = engine.get("old", u64::MAX).unwrap().unwrap();
+    assert_eq!(rec.data.len(), 1);
+    assert_eq!(rec.data.get("name"), Some(&FieldValue::Str("y".to_string())));
+
+    assert!(engine.update("old".to_string(), Patch::new().increment("name", "bad")).is_err());
+}
+
+#[test]
+fn updates_survive_restart() {
+    let dir = tempdir().unwrap();
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.put("u1".to_string(), profile("ann", 1)).unwrap();
+        engine.flush().unwrap();
+        engine.update("u1".to_string(), Patch::new().increment("visits", 4i64)).unwrap();
+    }
+
+    let engine = Engine::open(dir.path()).unwrap();
+    assert_eq!(engine.get("u1", u64::MAX).unwrap().unwrap().data, profile("ann", 5));
+}
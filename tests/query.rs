@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::query::{Filter, Order, Plan, Query};
+use shunyadb::storage::record::{FieldValue, Record};
+
+fn person(age: i64, city: &str) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("age".to_string(), FieldValue::Int(age));
+    map.insert("city".to_string(), FieldValue::from(city));
+    map
+}
+
+fn ids(records: &[Record]) -> Vec<&str> {
+    records.iter().map(|r| r.id.as_str()).collect()
+}
+
+fn people(engine: &Engine) -> anyhow::Result<()> {
+    engine.put("ann".to_string(), person(31, "Oslo"))?;
+    engine.put("bob".to_string(), person(17, "Pune"))?;
+    engine.put("cat".to_string(), person(45, "Oslo"))?;
+    engine.put("dan".to_string(), person(31, "Lima"))?;
+    let mut nameless = BTreeMap::new();
+    nameless.insert("city".to_string(), FieldValue::from("Oslo"));
+    engine.put("eve".to_string(), nameless)?;
+    engine.flush()?;
+    engine.put("fay".to_string(), person(22, "Oslo"))?;
+    Ok(())
+}
+
+#[test]
+fn indexed_and_scanned_queries_agree() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    people(&engine)?;
+    let queries = [
+        Filter::eq("city", "Oslo").and(Filter::range("age", FieldValue::Int(18)..)),
+        Filter::eq("age", 31i64).or(Filter::eq("city", "Pune")),
+        Filter::range("age", FieldValue::Int(20)..=FieldValue::Int(31)),
+        !Filter::exists("age"),
+    ];
+    let snap = engine.last_seqno();
+    let scanned: Vec<_> = queries.iter().map(|f| engine.query(f.clone(), snap)).collect::<Result<_, _>>()?;
+
+    engine.create_index("age")?;
+    engine.create_index("city")?;
+    let now = engine.last_seqno();
+    for (filter, expected) in queries.iter().zip(&scanned) {
+        assert_eq!(&engine.query(filter.clone(), now)?, expected);
+    }
+    assert_eq!(ids(&scanned[0]), vec!["ann", "cat", "fay"]);
+    assert_eq!(ids(&scanned[1]), vec!["ann", "bob", "dan"]);
+    assert_eq!(ids(&scanned[2]), vec!["ann", "dan", "fay"]);
+    assert_eq!(ids(&scanned[3]), vec!["eve"]);
+    let plans: Vec<_> = queries.into_iter().map(|f| engine.explain(&f.into(), now)).collect();
+    assert!(plans[..3].iter().all(|p| *p != Plan::FullScan));
+    assert_eq!(plans[3], Plan::FullScan);
+    Ok(())
+}
+
+#[test]
+fn explain_shows_the_chosen_plan() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    people(&engine)?;
+    let query = Query::new(Filter::eq("city", "Oslo").and(Filter::range("age", FieldValue::Int(18)..)));
+    let before = engine.last_seqno();
+    assert_eq!(engine.explain(&query, before), Plan::FullScan);
+    assert_eq!(engine.explain(&query, before).to_string(), "full scan");
+
+    engine.create_index("age")?;
+    let plan = engine.explain(&query, engine.last_seqno());
+    assert_eq!(plan.to_string(), "index scan on \"age\" [Int(18), +inf)");
+    engine.create_index("city")?;
+    let plan = engine.explain(&query, engine.last_seqno());
+    assert_eq!(plan.to_string(), "index scan on \"city\" = Str(\"Oslo\")");
+
+    // An index doesn't serve snapshots taken before it was built
+    assert_eq!(engine.explain(&query, before), Plan::FullScan);
+    assert_eq!(engine.query(query.clone(), before)?, engine.query(query, engine.last_seqno())?);
+
+    let either = Query::new(Filter::eq("city", "Lima").or(Filter::range("age", ..FieldValue::Int(18))));
+    assert!(matches!(engine.explain(&either, engine.last_seqno()), Plan::Union(_)));
+    assert_eq!(ids(&engine.query(either, engine.last_seqno())?), vec!["bob", "dan"]);
+    Ok(())
+}
+
+#[test]
+fn queries_project_order_and_limit() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    people(&engine)?;
+    let snap = engine.last_seqno();
+
+    let oldest = Query::new(Filter::eq("city", "Oslo")).order_by("age", Order::Desc).limit(2).select(["age"]);
+    let found = engine.query(oldest.clone(), snap)?;
+    assert_eq!(ids(&found), vec!["cat", "ann"]);
+    assert_eq!(found[0].data, BTreeMap::from([("age".to_string(), FieldValue::Int(45))]));
+
+    engine.create_index("city")?;
+    assert_eq!(engine.query(oldest, engine.last_seqno())?, found);
+
+    // Without an order, a limit keeps the first matches by id
+    let first = engine.query(Query::new(Filter::exists("age")).limit(2), snap)?;
+    assert_eq!(ids(&first), vec!["ann", "bob"]);
+
+    // Queries read as of their snapshot
+    let snap = engine.last_seqno();
+    engine.delete("ann".to_string())?;
+    assert_eq!(ids(&engine.query(Filter::eq("city", "Oslo"), snap)?), vec!["ann", "cat", "eve", "fay"]);
+    assert_eq!(ids(&engine.query(Filter::eq("city", "Oslo"), engine.last_seqno())?), vec!["cat", "eve", "fay"]);
+    Ok(())
+}
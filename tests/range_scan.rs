@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: usize) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("value".to_string(), FieldValue::Str(format!("value_{}", i)));
+    map
+}
+
+fn key(i: usize) -> String {
+    format!("key_{:05}", i)
+}
+
+#[test]
+fn scan_merges_memtable_and_pages() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+
+    // Old versions end up in L1, newer ones in L0 and the memtable
+    for i in 0..2_000 {
+        engine.put(key(i), value(i))?;
+    }
+    engine.flush()?;
+    engine.maybe_compact()?;
+
+    for i in (0..2_000).step_by(10) {
+        engine.put(key(i), value(i + 100_000))?;
+    }
+    engine.flush()?;
+
+    for i in (0..2_000).step_by(100) {
+        engine.put(key(i), value(i + 200_000))?;
+    }
+
+    let records: Vec<_> = engine.scan(key(100)..key(300), u64::MAX)?.collect();
+    assert_eq!(records.len(), 200);
+
+    for (offset, rec) in records.iter().enumerate() {
+        let i = 100 + offset;
+        assert_eq!(rec.id, key(i));
+
+        let expected = if i % 100 == 0 {
+            i + 200_000
+        } else if i % 10 == 0 {
+            i + 100_000
+        } else {
+            i
+        };
+        assert_eq!(rec.data.get("value"), Some(&FieldValue::Str(format!("value_{}", expected))));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn scan_hides_tombstones_and_respects_snapshot() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+
+    for i in 0..50 {
+        engine.put(key(i), value(i))?;
+    }
+    engine.flush()?;
+
+    let before_delete = engine.last_seqno();
+
+    for i in 0..50 {
+        if i % 2 == 0 {
+            engine.delete(key(i))?;
+        }
+    }
+    engine.put(key(999), value(999))?;
+
+    let live: Vec<_> = engine.scan(.., u64::MAX)?.map(|r| r.id).collect();
+    let mut expected: Vec<_> = (0..50).filter(|i| i % 2 == 1).map(key).collect();
+    expected.push(key(999));
+    assert_eq!(live, expected);
+
+    let old: Vec<_> = engine.scan(.., before_delete)?.map(|r| r.id).collect();
+    assert_eq!(old, (0..50).map(key).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[test]
+fn prefix_scan_prunes_pages_outside_prefix() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+
+    for user in 0..20 {
+        for order in 0..50 {
+            engine.put(format!("user:{:02}:order:{:02}", user, order), value(order))?;
+        }
+    }
+    engine.flush()?;
+    engine.put("user:07:order:99".to_string(), value(99))?;
+
+    let reads_before = engine.metrics().pages_read_from_disk;
+    let ids: Vec<_> = engine.scan_prefix("user:07:", u64::MAX)?.map(|r| r.id).collect();
+    let pages_read = engine.metrics().pages_read_from_disk - reads_before;
+
+    let mut expected: Vec<_> = (0..50).map(|o| format!("user:07:order:{:02}", o)).collect();
+    expected.push("user:07:order:99".to_string());
+    assert_eq!(ids, expected);
+
+    let total_pages = engine.meta().level.iter().map(|l| l.len()).sum::<usize>() as u64;
+    assert!(pages_read < total_pages, "prefix scan should skip non-matching pages");
+
+    Ok(())
+}
+
+#[test]
+fn reverse_scan_paginates_newest_id_first() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+
+    for i in 0..300 {
+        engine.put(key(i), value(i))?;
+    }
+    engine.flush()?;
+    for i in 300..400 {
+        engine.put(key(i), value(i))?;
+    }
+    engine.delete(key(399))?;
+
+    let snapshot = engine.last_seqno();
+
+    // Page of 10 from the top, then the next page below the last seen id
+    let first: Vec<_> = engine.scan_reverse(.., snapshot)?.take(10).map(|r| r.id).collect();
+    assert_eq!(first, (389..399).rev().map(key).collect::<Vec<_>>());
+
+    let last_seen = first.last().unwrap().clone();
+    let second: Vec<_> = engine.scan_reverse(..last_seen, snapshot)?.take(10).map(|r| r.id).collect();
+    assert_eq!(second, (379..389).rev().map(key).collect::<Vec<_>>());
+
+    // Reverse order is exactly the forward order flipped
+    let forward: Vec<_> = engine.scan(key(250)..=key(350), snapshot)?.map(|r| r.id).collect();
+    let mut backward: Vec<_> = engine.scan_reverse(key(250)..=key(350), snapshot)?.map(|r| r.id).collect();
+    backward.reverse();
+    assert_eq!(forward, backward);
+
+    Ok(())
+}
@@ -6,7 +6,7 @@ use shunyadb::storage::record::FieldValue;
 #[test]
 fn page_cache_reduces_disk_reads() -> anyhow::Result<()> {
     let dir = tempdir()?;
-    let mut engine = Engine::open(dir.path())?;
+    let engine = Engine::open(dir.path())?;
 
     // Insert data
     for i in 0..1000 {
@@ -20,22 +20,22 @@ fn page_cache_reduces_disk_reads() -> anyhow::Result<()> {
 
     // First read pass
     for i in 0..1000 {
-        engine.get(&i.to_string(), u64::MAX);
+        engine.get(&i.to_string(), u64::MAX)?;
     }
 
-    let disk_reads_after_first = engine.metrics.pages_read_from_disk;
-    let cache_hits_after_first = engine.metrics.page_cache_hits;
+    let disk_reads_after_first = engine.metrics().pages_read_from_disk;
+    let cache_hits_after_first = engine.metrics().page_cache_hits;
 
     assert!(disk_reads_after_first > 0);
     assert!(cache_hits_after_first > 0);
 
     // Second read pass
     for i in 0..1000 {
-        engine.get(&i.to_string(), u64::MAX);
+        engine.get(&i.to_string(), u64::MAX)?;
     }
 
-    let disk_reads_after_second = engine.metrics.pages_read_from_disk;
-    let cache_hits_after_second = engine.metrics.page_cache_hits;
+    let disk_reads_after_second = engine.metrics().pages_read_from_disk;
+    let cache_hits_after_second = engine.metrics().page_cache_hits;
 
     // Cache hits must increase
     assert!(
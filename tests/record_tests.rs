@@ -1,5 +1,4 @@
 use shunyadb::storage::record::*;
-use bincode;
 
 #[test]
 fn record_roundtrip_ser_de() {
@@ -42,7 +41,7 @@ fn from_pairs_constructor() {
 
 #[test]
 fn float_tryfrom_ok_roundtrip() {
-  let f = 3.14159f64;
+  let f = std::f64::consts::PI;
   let fv = FieldValue::try_from(f).expect("should accept normal float");
   let mut map = std::collections::BTreeMap::new();
   map.insert("pi".to_string(), fv.clone());
@@ -54,14 +53,36 @@ fn float_tryfrom_ok_roundtrip() {
   assert_eq!(r, r2);
   // check stored float equals (via NotNan inner value)
   match r2.data.get("pi").unwrap() {
-    FieldValue::Float(n) => assert_eq!(n.into_inner(), 3.14159f64),
+    FieldValue::Float(n) => assert_eq!(n.into_inner(), std::f64::consts::PI),
     _ => panic!("expected float"),
   }
 }
 
 #[test]
 fn float_tryfrom_rejects_nan() {
-  let nan = std::f64::NAN;
+  let nan = f64::NAN;
   let res = FieldValue::try_from(nan);
   assert!(res.is_err(), "NaN must be rejected for FieldValue::Float");
-}
\ No newline at end of file
+}
+
+#[test]
+fn expiry_hides_record_and_patches_inherit_it() {
+  use shunyadb::storage::patch::Patch;
+
+  let live = Record::from_pairs("k", 1, vec![("a", 1i64)]).with_expiry(1_000);
+  let patch = Record::new_patch("k", 2, Patch::new().set("b", 2i64));
+
+  assert!(!live.is_expired(999));
+  assert!(live.is_expired(1_000));
+
+  let resolved = Record::resolve([&patch, &live], 500).unwrap();
+  assert_eq!(resolved.expires_at, Some(1_000));
+  assert_eq!(resolved.data.len(), 2);
+
+  // Once the base expires, a patch above it starts from an empty record
+  let resolved = Record::resolve([&patch, &live], 2_000).unwrap();
+  assert_eq!(resolved.expires_at, None);
+  assert_eq!(resolved.data.len(), 1);
+
+  assert!(Record::resolve([&live], 2_000).is_none());
+}
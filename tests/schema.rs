@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::Error;
+use shunyadb::engine::batch::WriteBatch;
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::schema::{FieldDef, FieldType, Schema};
+use shunyadb::storage::patch::Patch;
+use shunyadb::storage::record::FieldValue;
+
+fn users() -> Schema {
+    Schema::new()
+        .field("name", FieldDef::required(FieldType::Str))
+        .field("age", FieldDef::optional(FieldType::Int))
+        .field("plan", FieldDef::optional(FieldType::Str).default("free"))
+}
+
+fn data(fields: &[(&str, FieldValue)]) -> BTreeMap<String, FieldValue> {
+    fields.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+fn invalid<T: std::fmt::Debug>(result: Result<T, Error>) -> bool {
+    matches!(result, Err(Error::InvalidArgument(_)))
+}
+
+#[test]
+fn writes_are_checked_before_they_are_logged() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.create_table("users")?;
+    engine.set_schema("users", users())?;
+    let before = engine.last_seqno();
+
+    assert!(invalid(engine.put_in("users", "u1".to_string(), data(&[("age", FieldValue::Int(3))]))));
+    assert!(invalid(engine.put_in("users", "u1".to_string(), data(&[("name", "ann".into()), ("age", "3".into())]))));
+    assert!(invalid(engine.put_in("users", "u1".to_string(), data(&[("name", "ann".into()), ("nick", "a".into())]))));
+
+    // A batch with one bad record logs none of them
+    let mut batch = WriteBatch::new();
+    batch
+        .put_in("users", "u1", data(&[("name", "ann".into())]))
+        .put_in("users", "u2", data(&[("name", FieldValue::Null)]));
+    assert!(invalid(engine.write(&batch)));
+    assert_eq!(engine.last_seqno(), before);
+    assert_eq!(engine.changes_since(0)?.filter(|c| c.table == "users" && !c.record_id.is_empty()).count(), 0);
+
+    engine.put_in("users", "u1".to_string(), data(&[("name", "ann".into())]))?;
+    let stored = engine.get_in("users", "u1", engine.last_seqno())?.unwrap();
+    assert_eq!(stored.data, data(&[("name", "ann".into()), ("plan", "free".into())]));
+
+    // Other tables are unaffected
+    engine.put("free".to_string(), data(&[("anything", FieldValue::Int(1))]))?;
+    Ok(())
+}
+
+#[test]
+fn partial_updates_are_checked_op_by_op() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let engine = Engine::open(dir.path())?;
+    engine.set_schema("", users())?;
+    engine.put("u1".to_string(), data(&[("name", "ann".into()), ("age", FieldValue::Int(30))]))?;
+
+    assert!(invalid(engine.update("u1".to_string(), Patch::new().remove("name"))));
+    assert!(invalid(engine.update("u1".to_string(), Patch::new().set("age", "old"))));
+    assert!(invalid(engine.update("u1".to_string(), Patch::new().increment("plan", 1i64))));
+    engine.update("u1".to_string(), Patch::new().increment("age", 1i64).set("plan", "pro"))?;
+    let stored = engine.get("u1", engine.last_seqno())?.unwrap();
+    assert_eq!(stored.data["age"], FieldValue::Int(31));
+    assert_eq!(stored.data["plan"], FieldValue::from("pro"));
+    Ok(())
+}
+
+#[test]
+fn schemas_persist_and_evolve() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    {
+        let engine = Engine::open(dir.path())?;
+        engine.create_table("users")?;
+        engine.set_schema("users", users())?;
+        engine.put_in("users", "u1".to_string(), data(&[("name", "ann".into()), ("age", FieldValue::Int(30))]))?;
+        engine.flush()?;
+    }
+
+    let engine = Engine::open(dir.path())?;
+    assert_eq!(engine.schema("users")?, Some(users()));
+    assert!(invalid(engine.put_in("users", "u2".to_string(), BTreeMap::new())));
+
+    // Widen age to Float and add an optional field; Int ages now widen on write
+    let next = users()
+        .field("age", FieldDef::optional(FieldType::Float))
+        .field("email", FieldDef::optional(FieldType::Str));
+    engine.set_schema("users", next.clone())?;
+    engine.put_in("users", "u2".to_string(), data(&[("name", "bob".into()), ("age", FieldValue::Int(41))]))?;
+    let snap = engine.last_seqno();
+    assert_eq!(engine.get_in("users", "u2", snap)?.unwrap().data["age"], FieldValue::try_from(41.0)?);
+    assert_eq!(engine.get_in("users", "u1", snap)?.unwrap().data["age"], FieldValue::Int(30));
+
+    // Changes that could invalidate stored records are refused
+    assert!(invalid(engine.set_schema("users", users())));
+    assert!(invalid(engine.set_schema("users", next.clone().field("team", FieldDef::required(FieldType::Str)))));
+    assert!(invalid(engine.set_schema("users", Schema::new().field("x", FieldDef::optional(FieldType::Int).default("y")))));
+    assert_eq!(engine.schema("users")?, Some(next));
+
+    engine.drop_schema("users")?;
+    engine.put_in("users", "u3".to_string(), BTreeMap::new())?;
+    assert!(matches!(engine.drop_schema("users"), Err(Error::NotFound(_))));
+    assert!(matches!(engine.set_schema("missing", users()), Err(Error::NotFound(_))));
+    Ok(())
+}
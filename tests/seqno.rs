@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map
+}
+
+#[test]
+fn engines_in_one_process_have_their_own_seqnos() -> anyhow::Result<()> {
+    let (dir_a, dir_b) = (tempdir()?, tempdir()?);
+    let a = Engine::open(dir_a.path())?;
+    let b = Engine::open(dir_b.path())?;
+
+    for i in 0..10 {
+        a.put(format!("k{}", i), value(i))?;
+    }
+    b.put("only".to_string(), value(0))?;
+
+    assert_eq!(a.last_seqno(), 10);
+    assert_eq!(b.last_seqno(), 1);
+    assert_eq!(b.get("only", u64::MAX)?.unwrap().seqno, 1);
+    Ok(())
+}
+
+#[test]
+fn seqnos_continue_after_the_wal_was_emptied() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let last = {
+        let engine = Engine::open(dir.path())?;
+        for i in 0..20 {
+            engine.put(format!("k{}", i), value(i))?;
+        }
+        engine.delete("k0".to_string())?;
+        engine.flush()?;
+        assert_eq!(std::fs::metadata(dir.path().join("wal.log"))?.len(), 0);
+        engine.last_seqno()
+    };
+
+    let engine = Engine::open(dir.path())?;
+    assert_eq!(engine.last_seqno(), last);
+
+    // A new version must outrank the flushed one, or reads would keep returning the old value
+    engine.put("k1".to_string(), value(100))?;
+    assert_eq!(engine.get("k1", u64::MAX)?.unwrap().data["v"], FieldValue::Int(100));
+    assert!(engine.get("k0", u64::MAX)?.is_none());
+    Ok(())
+}
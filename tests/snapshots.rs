@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::record::FieldValue;
+
+fn value(v: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(v));
+    map
+}
+
+// Every flush produces an L0 page and every L0 page triggers compaction
+fn compacting_engine(path: &std::path::Path) -> Engine {
+    Engine::open_with(path, EngineOptions::new().l0_pages_limit(1)).unwrap()
+}
+
+fn flush_and_compact(engine: &Engine) {
+    engine.flush().unwrap();
+    engine.maybe_compact().unwrap();
+    assert!(engine.meta().level[0].is_empty());
+}
+
+#[test]
+fn snapshot_survives_compaction() {
+    let dir = tempdir().unwrap();
+    let engine = compacting_engine(dir.path());
+
+    engine.put("k".to_string(), value(1)).unwrap();
+    flush_and_compact(&engine);
+
+    let snap = engine.snapshot();
+
+    for v in 2..5 {
+        engine.put("k".to_string(), value(v)).unwrap();
+        flush_and_compact(&engine);
+    }
+
+    assert_eq!(engine.get("k", snap.seqno()).unwrap().unwrap().data, value(1));
+    assert_eq!(engine.get("k", u64::MAX).unwrap().unwrap().data, value(4));
+
+    let scanned: Vec<_> = engine.scan(.., snap.seqno()).unwrap().collect();
+    assert_eq!(scanned.len(), 1);
+    assert_eq!(scanned[0].data, value(1));
+}
+
+#[test]
+fn snapshot_sees_value_under_compacted_tombstone() {
+    let dir = tempdir().unwrap();
+    let engine = compacting_engine(dir.path());
+
+    engine.put("k".to_string(), value(1)).unwrap();
+    let snap = engine.snapshot();
+
+    engine.delete("k".to_string()).unwrap();
+    flush_and_compact(&engine);
+
+    assert_eq!(engine.get("k", snap.seqno()).unwrap().unwrap().data, value(1));
+    assert!(engine.get("k", u64::MAX).unwrap().is_none());
+}
+
+#[test]
+fn released_snapshot_versions_are_dropped() {
+    let dir = tempdir().unwrap();
+    let engine = compacting_engine(dir.path());
+
+    engine.put("k".to_string(), value(1)).unwrap();
+    engine.put("gone".to_string(), value(1)).unwrap();
+    let snap = engine.snapshot();
+    let pinned = snap.seqno();
+
+    engine.put("k".to_string(), value(2)).unwrap();
+    engine.delete("gone".to_string()).unwrap();
+    flush_and_compact(&engine);
+    assert_eq!(engine.get("k", pinned).unwrap().unwrap().data, value(1));
+
+    drop(snap);
+
+    // Next compaction over the same range collapses to the newest version
+    engine.put("k".to_string(), value(3)).unwrap();
+    flush_and_compact(&engine);
+
+    assert!(engine.get("k", pinned).unwrap().is_none(), "old version should be gone once unpinned");
+    assert!(engine.get("gone", pinned).unwrap().is_none());
+    assert_eq!(engine.get("k", u64::MAX).unwrap().unwrap().data, value(3));
+
+    let records: usize = engine.meta().level[1].iter().map(|p| p.number_of_records).sum();
+    assert_eq!(records, 1, "only the newest version of `k` should remain on disk");
+}
+
+#[test]
+fn snapshots_taken_during_compaction_stay_readable() {
+    let dir = tempdir().unwrap();
+    let engine = compacting_engine(dir.path());
+    engine.put("k".to_string(), value(0)).unwrap();
+    flush_and_compact(&engine);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writer = {
+        let (engine, done) = (engine.clone(), done.clone());
+        thread::spawn(move || {
+            for v in 1..=100 {
+                engine.put("k".to_string(), value(v)).unwrap();
+                engine.flush().unwrap();
+                engine.maybe_compact().unwrap();
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let (engine, done) = (engine.clone(), done.clone());
+            thread::spawn(move || {
+                let mut pinned = Vec::new();
+                while !done.load(Ordering::SeqCst) && pinned.len() < 200 {
+                    let snap = engine.snapshot();
+                    let seen = engine.get("k", snap.seqno()).unwrap().expect("k is never deleted");
+                    pinned.push((snap, seen));
+                    thread::yield_now();
+                }
+                pinned
+            })
+        })
+        .collect();
+
+    writer.join().unwrap();
+    let pinned: Vec<_> = readers.into_iter().flat_map(|r| r.join().unwrap()).collect();
+    engine.put("k".to_string(), value(-1)).unwrap();
+    flush_and_compact(&engine);
+    for (snap, seen) in &pinned {
+        assert_eq!(engine.get("k", snap.seqno()).unwrap().as_ref(), Some(seen));
+    }
+}
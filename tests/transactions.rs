@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::Error;
+use shunyadb::storage::record::FieldValue;
+
+fn balance(v: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("balance".to_string(), FieldValue::Int(v));
+    map
+}
+
+fn read_balance(engine: &Engine, id: &str) -> i64 {
+    match engine.get(id, u64::MAX).unwrap().unwrap().data.get("balance") {
+        Some(FieldValue::Int(v)) => *v,
+        other => panic!("unexpected balance {:?}", other),
+    }
+}
+
+#[test]
+fn transaction_commits_read_modify_write() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+    engine.put("alice".to_string(), balance(100)).unwrap();
+    engine.put("bob".to_string(), balance(0)).unwrap();
+
+    let mut txn = engine.begin_transaction();
+    let a = txn.get(&engine, "alice").unwrap().unwrap();
+    assert_eq!(a.data, balance(100));
+    txn.put("alice", balance(70));
+    txn.put("bob", balance(30));
+
+    // Read-your-writes; nothing visible outside before commit
+    assert_eq!(txn.get(&engine, "alice").unwrap().unwrap().data, balance(70));
+    assert_eq!(read_balance(&engine, "alice"), 100);
+
+    txn.commit(&engine).unwrap();
+    assert_eq!(read_balance(&engine, "alice"), 70);
+    assert_eq!(read_balance(&engine, "bob"), 30);
+}
+
+#[test]
+fn concurrent_write_to_read_key_conflicts() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+    engine.put("counter".to_string(), balance(1)).unwrap();
+
+    let mut t1 = engine.begin_transaction();
+    let mut t2 = engine.begin_transaction();
+
+    t1.get(&engine, "counter").unwrap();
+    t2.get(&engine, "counter").unwrap();
+    t1.put("counter", balance(2));
+    t2.put("counter", balance(3));
+    t2.put("other", balance(9));
+
+    t1.commit(&engine).unwrap();
+
+    let err = t2.commit(&engine).unwrap_err();
+    let Error::Conflict(conflict) = err else {
+        panic!("expected a conflict, got {}", err);
+    };
+    assert_eq!(conflict.id, "counter");
+
+    // The failed transaction applied nothing
+    assert_eq!(read_balance(&engine, "counter"), 2);
+    assert!(engine.get("other", u64::MAX).unwrap().is_none());
+}
+
+#[test]
+fn conflict_detected_after_flush_to_pages() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+    engine.put("k".to_string(), balance(1)).unwrap();
+    engine.flush().unwrap();
+
+    let mut txn = engine.begin_transaction();
+    txn.get(&engine, "k").unwrap();
+    txn.put("k", balance(5));
+
+    engine.delete("k".to_string()).unwrap();
+    engine.flush().unwrap();
+
+    let err = txn.commit(&engine).unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)), "unexpected error: {}", err);
+}
+
+#[test]
+fn unrelated_writes_and_blind_writes_do_not_conflict() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+    engine.put("a".to_string(), balance(1)).unwrap();
+    engine.put("b".to_string(), balance(1)).unwrap();
+
+    let mut txn = engine.begin_transaction();
+    txn.get(&engine, "a").unwrap();
+    txn.put("b", balance(10));
+
+    engine.put("b".to_string(), balance(2)).unwrap();
+    engine.put("c".to_string(), balance(3)).unwrap();
+
+    txn.commit(&engine).unwrap();
+    assert_eq!(read_balance(&engine, "b"), 10);
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::thread::sleep;
+use std::time::Duration;
+use tempfile::tempdir;
+
+use shunyadb::engine::engine::Engine;
+use shunyadb::engine::options::EngineOptions;
+use shunyadb::storage::record::FieldValue;
+
+fn value(i: i64) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("v".to_string(), FieldValue::Int(i));
+    map
+}
+
+#[test]
+fn expired_records_read_as_absent() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.put_with_ttl("session".to_string(), value(1), Duration::from_millis(50)).unwrap();
+    engine.put_with_ttl("cache".to_string(), value(2), Duration::from_secs(3600)).unwrap();
+    engine.put("user".to_string(), value(3)).unwrap();
+
+    assert!(engine.get("session", u64::MAX).unwrap().unwrap().expires_at.is_some());
+    sleep(Duration::from_millis(100));
+
+    assert!(engine.get("session", u64::MAX).unwrap().is_none());
+    let ids: Vec<_> = engine.scan(.., u64::MAX).unwrap().map(|r| r.id).collect();
+    assert_eq!(ids, vec!["cache".to_string(), "user".to_string()]);
+
+    // An expired key does not fall back to an older version
+    engine.put("k".to_string(), value(1)).unwrap();
+    engine.put_with_ttl("k".to_string(), value(2), Duration::from_millis(1)).unwrap();
+    sleep(Duration::from_millis(10));
+    assert!(engine.get("k", u64::MAX).unwrap().is_none());
+}
+
+#[test]
+fn expiry_applies_to_flushed_pages_and_survives_restart() {
+    let dir = tempdir().unwrap();
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.put_with_ttl("short".to_string(), value(1), Duration::from_millis(50)).unwrap();
+        engine.put_with_ttl("long".to_string(), value(2), Duration::from_secs(3600)).unwrap();
+        engine.flush().unwrap();
+        engine.put_with_ttl("unflushed".to_string(), value(3), Duration::from_secs(3600)).unwrap();
+    }
+
+    sleep(Duration::from_millis(100));
+    let engine = Engine::open(dir.path()).unwrap();
+    assert!(engine.get("short", u64::MAX).unwrap().is_none());
+    assert_eq!(engine.get("long", u64::MAX).unwrap().unwrap().data, value(2));
+    assert!(engine.get("unflushed", u64::MAX).unwrap().unwrap().expires_at.is_some());
+}
+
+#[test]
+fn put_without_ttl_clears_expiry() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.put_with_ttl("k".to_string(), value(1), Duration::from_millis(20)).unwrap();
+    engine.put("k".to_string(), value(2)).unwrap();
+    sleep(Duration::from_millis(40));
+
+    let rec = engine.get("k", u64::MAX).unwrap().unwrap();
+    assert_eq!(rec.data, value(2));
+    assert_eq!(rec.expires_at, None);
+}
+
+#[test]
+fn compaction_drops_expired_records() {
+    let dir = tempdir().unwrap();
+    let engine = Engine::open_with(dir.path(), EngineOptions::new().l0_pages_limit(1)).unwrap();
+
+    for i in 0..10 {
+        engine.put_with_ttl(format!("tmp{i}"), value(i), Duration::from_millis(20)).unwrap();
+    }
+    engine.put("keep".to_string(), value(0)).unwrap();
+    engine.flush().unwrap();
+
+    sleep(Duration::from_millis(40));
+    engine.flush().unwrap();
+    engine.maybe_compact().unwrap();
+
+    assert!(engine.meta().level[0].is_empty());
+    let records: usize = engine.meta().level[1].iter().map(|p| p.number_of_records).sum();
+    assert_eq!(records, 1);
+    assert_eq!(engine.get("keep", u64::MAX).unwrap().unwrap().data, value(0));
+}
@@ -2,8 +2,10 @@ use std::collections::BTreeMap;
 use tempfile::TempDir;
 
 use shunyadb::engine::engine::Engine;
-use shunyadb::engine::seqno;
 use shunyadb::storage::record::FieldValue;
+use shunyadb::storage::wal::SyncMode;
+use std::thread;
+use std::time::{Duration, Instant};
 
 fn sample_value(v: &str) -> BTreeMap<String, FieldValue> {
     let mut map = BTreeMap::new();
@@ -16,18 +18,147 @@ fn wal_recovery_works() {
     let dir = TempDir::new().unwrap();
 
     {
-        let mut engine = Engine::open(dir.path()).unwrap();
+        let engine = Engine::open(dir.path()).unwrap();
         engine.put("1".to_string(), sample_value("a")).unwrap();
         engine.put("1".to_string(), sample_value("b")).unwrap();
         engine.flush().unwrap();
     }
 
-    let mut engine = Engine::open(dir.path()).unwrap();
-    let snapshot = seqno::current();
+    let engine = Engine::open(dir.path()).unwrap();
+    let snapshot = engine.last_seqno();
 
-    let rec = engine.get("1", snapshot).unwrap();
+    let rec = engine.get("1", snapshot).unwrap().unwrap();
     assert_eq!(
         rec.data.get("val").unwrap(),
          &FieldValue::Str("b".to_string())
     );
 }
+
+fn write_and_recover(mode: SyncMode, writes: usize) -> (u64, Engine, TempDir) {
+    let dir = TempDir::new().unwrap();
+
+    let syncs = {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.set_sync_mode(mode);
+        for i in 0..writes {
+            engine.put(i.to_string(), sample_value(&i.to_string())).unwrap();
+        }
+        engine.metrics().wal_syncs
+    };
+
+    let engine = Engine::open(dir.path()).unwrap();
+    (syncs, engine, dir)
+}
+
+fn assert_all_present(engine: &Engine, writes: usize) {
+    let snapshot = engine.last_seqno();
+    for i in 0..writes {
+        let rec = engine.get(&i.to_string(), snapshot).unwrap().expect("record lost after restart");
+        assert_eq!(rec.data.get("val").unwrap(), &FieldValue::Str(i.to_string()));
+    }
+}
+
+#[test]
+fn sync_always_fsyncs_every_write() {
+    let (syncs, engine, _dir) = write_and_recover(SyncMode::Always, 20);
+    assert_eq!(syncs, 20);
+    assert_all_present(&engine, 20);
+}
+
+#[test]
+fn sync_every_n_writes_groups_fsyncs() {
+    let (syncs, engine, _dir) = write_and_recover(SyncMode::EveryWrites(8), 20);
+    assert_eq!(syncs, 2, "20 writes with n = 8 should fsync after the 8th and 16th");
+    assert_all_present(&engine, 20);
+}
+
+#[test]
+fn sync_interval_shares_fsync_between_writes() {
+    let (syncs, engine, _dir) = write_and_recover(SyncMode::Interval(Duration::from_secs(3600)), 20);
+    assert_eq!(syncs, 0, "no write should wait for an fsync inside the interval");
+    assert_all_present(&engine, 20);
+}
+
+#[test]
+fn sync_interval_syncs_an_idle_log() {
+    let dir = TempDir::new().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+    engine.set_sync_mode(SyncMode::Interval(Duration::from_millis(50)));
+    for i in 0..5 {
+        engine.put(i.to_string(), sample_value(&i.to_string())).unwrap();
+    }
+
+    // No more writes: the timer has to sync on its own
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while engine.pending_wal_appends() > 0 {
+        assert!(Instant::now() < deadline, "idle log was never synced");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(engine.metrics().wal_syncs >= 1);
+}
+
+#[test]
+fn sync_always_shares_fsyncs_between_concurrent_writers() {
+    let dir = TempDir::new().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+    let writers: Vec<_> = (0..8)
+        .map(|t| {
+            let engine = engine.clone();
+            thread::spawn(move || {
+                for i in 0..50 {
+                    let id = format!("{}-{}", t, i);
+                    engine.put(id.clone(), sample_value(&id)).unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let metrics = engine.metrics();
+    assert_eq!(metrics.wal_appends, 400);
+    assert!(metrics.wal_syncs < metrics.wal_appends, "{} syncs for {} appends", metrics.wal_syncs, metrics.wal_appends);
+    assert_eq!(engine.pending_wal_appends(), 0, "every acknowledged write is synced");
+}
+
+#[test]
+fn sync_manual_only_syncs_on_request() {
+    let dir = TempDir::new().unwrap();
+
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.set_sync_mode(SyncMode::Manual);
+        for i in 0..10 {
+            engine.put(i.to_string(), sample_value(&i.to_string())).unwrap();
+        }
+        assert_eq!(engine.metrics().wal_syncs, 0);
+        assert_eq!(engine.pending_wal_appends(), 10);
+
+        engine.sync().unwrap();
+        assert_eq!(engine.metrics().wal_syncs, 1, "pending appends should share one fsync");
+        assert_eq!(engine.pending_wal_appends(), 0);
+    }
+
+    let engine = Engine::open(dir.path()).unwrap();
+    assert_all_present(&engine, 10);
+}
+
+#[test]
+fn writes_after_wal_checkpoint_are_recovered() {
+    let dir = TempDir::new().unwrap();
+
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.put("1".to_string(), sample_value("a")).unwrap();
+        engine.flush().unwrap();
+        assert!(engine.metrics().wal_rewrites > 0);
+
+        // Lands in the rewritten log, never flushed to a page
+        engine.put("2".to_string(), sample_value("b")).unwrap();
+    }
+
+    let engine = Engine::open(dir.path()).unwrap();
+    let snapshot = engine.last_seqno();
+    assert_eq!(engine.get("2", snapshot).unwrap().unwrap().data, sample_value("b"));
+}
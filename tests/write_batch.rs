@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use tempfile::TempDir;
+
+use shunyadb::engine::batch::WriteBatch;
+use shunyadb::engine::engine::Engine;
+use shunyadb::storage::record::FieldValue;
+
+fn sample_value(v: &str) -> BTreeMap<String, FieldValue> {
+    let mut map = BTreeMap::new();
+    map.insert("val".to_string(), FieldValue::Str(v.to_string()));
+    map
+}
+
+#[test]
+fn batch_is_applied_as_a_unit() {
+    let dir = TempDir::new().unwrap();
+    let engine = Engine::open(dir.path()).unwrap();
+
+    engine.put("gone".to_string(), sample_value("x")).unwrap();
+    let before = engine.last_seqno();
+
+    let mut batch = WriteBatch::new();
+    batch
+        .put("a", sample_value("1"))
+        .put("b", sample_value("2"))
+        .put("a", sample_value("3"))
+        .delete("gone");
+    engine.write(&batch).unwrap();
+
+    let after = engine.last_seqno();
+    assert!(after - before >= 4, "batch should consume one seqno per op");
+
+    assert_eq!(engine.get("a", after).unwrap().unwrap().data, sample_value("3"));
+    assert_eq!(engine.get("b", after).unwrap().unwrap().data, sample_value("2"));
+    assert!(engine.get("gone", after).unwrap().is_none());
+
+    // Nothing from the batch is visible before it
+    assert!(engine.get("a", before).unwrap().is_none());
+    assert!(engine.get("gone", before).unwrap().is_some());
+}
+
+#[test]
+fn batch_survives_restart() {
+    let dir = TempDir::new().unwrap();
+
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        let mut batch = WriteBatch::new();
+        batch.put("1", sample_value("a")).put("2", sample_value("b"));
+        engine.write(&batch).unwrap();
+    }
+
+    let engine = Engine::open(dir.path()).unwrap();
+    let snapshot = engine.last_seqno();
+
+    assert_eq!(engine.get("1", snapshot).unwrap().unwrap().data, sample_value("a"));
+    assert_eq!(engine.get("2", snapshot).unwrap().unwrap().data, sample_value("b"));
+}
+
+#[test]
+fn torn_batch_is_dropped_on_recovery() {
+    let dir = TempDir::new().unwrap();
+    let wal_path = dir.path().join("wal.log");
+
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        engine.put("before".to_string(), sample_value("ok")).unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch.put("x", sample_value("1")).put("y", sample_value("2"));
+        engine.write(&batch).unwrap();
+    }
+
+    // Simulate a crash that lost the tail of the batch (its commit marker)
+    let file = OpenOptions::new().write(true).open(&wal_path).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - 3).unwrap();
+    drop(file);
+
+    {
+        let engine = Engine::open(dir.path()).unwrap();
+        let snapshot = engine.last_seqno();
+
+        assert!(engine.get("before", snapshot).unwrap().is_some());
+        assert!(engine.get("x", snapshot).unwrap().is_none());
+        assert!(engine.get("y", snapshot).unwrap().is_none());
+
+        // Writes after recovery must not be swallowed by the discarded tail
+        engine.put("after".to_string(), sample_value("ok")).unwrap();
+    }
+
+    let engine = Engine::open(dir.path()).unwrap();
+    let snapshot = engine.last_seqno();
+    assert!(engine.get("after", snapshot).unwrap().is_some());
+    assert!(engine.get("x", snapshot).unwrap().is_none());
+}
@@ -6,7 +6,7 @@ use shunyadb::storage::record::FieldValue;
 #[test]
 fn writes_trigger_flush_reasonably() -> anyhow::Result<()> {
     let dir = tempdir()?;
-    let mut engine = Engine::open(dir.path())?;
+    let engine = Engine::open(dir.path())?;
 
     let writes = 2000;
 
@@ -17,12 +17,12 @@ fn writes_trigger_flush_reasonably() -> anyhow::Result<()> {
     }
 
     assert!(
-        engine.metrics.flushes > 0,
+        engine.metrics().flushes > 0,
         "writes should trigger memtable flushes"
     );
 
     assert!(
-        engine.metrics.wal_appends >= writes as u64,
+        engine.metrics().wal_appends >= writes as u64,
         "each write should append to WAL"
     );
 